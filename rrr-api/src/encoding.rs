@@ -0,0 +1,84 @@
+//! Wire formats of the API messages. JSON is always available; the `binary`
+//! feature adds postcard, which is several times smaller and cheaper to
+//! produce on the board, for `/state`, `/command` and the telemetry socket.
+
+use std::fmt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+#[cfg(feature = "binary")]
+pub const POSTCARD_CONTENT_TYPE: &str = "application/x-postcard";
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Encoding {
+    #[default]
+    Json,
+    #[cfg(feature = "binary")]
+    Postcard,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct DecodeError(String);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Encoding {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Json => JSON_CONTENT_TYPE,
+            #[cfg(feature = "binary")]
+            Encoding::Postcard => POSTCARD_CONTENT_TYPE,
+        }
+    }
+
+    /// Whether encoded messages are UTF-8, i.e. go into WebSocket text frames.
+    pub fn is_text(self) -> bool {
+        self == Encoding::Json
+    }
+
+    /// The encoding named by a `Content-Type` header, parameters are ignored.
+    pub fn from_content_type(value: &str) -> Option<Self> {
+        let media_type = value.split(';').next().unwrap_or_default().trim();
+        if media_type.eq_ignore_ascii_case(JSON_CONTENT_TYPE) {
+            return Some(Encoding::Json);
+        }
+        #[cfg(feature = "binary")]
+        if media_type.eq_ignore_ascii_case(POSTCARD_CONTENT_TYPE) {
+            return Some(Encoding::Postcard);
+        }
+        None
+    }
+
+    /// Picks the response encoding for an `Accept` header: the first supported
+    /// type listed, JSON for wildcards, unsupported types or no header.
+    /// Quality values are not weighed.
+    pub fn negotiate(accept: Option<&str>) -> Self {
+        accept.into_iter()
+            .flat_map(|accept| accept.split(','))
+            .find_map(Self::from_content_type)
+            .unwrap_or_default()
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Vec<u8> {
+        match self {
+            Encoding::Json => serde_json::to_vec(value).unwrap(),
+            #[cfg(feature = "binary")]
+            Encoding::Postcard => postcard::to_stdvec(value).unwrap(),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, data: &[u8]) -> Result<T, DecodeError> {
+        match self {
+            Encoding::Json => serde_json::from_slice(data).map_err(|e| DecodeError(e.to_string())),
+            #[cfg(feature = "binary")]
+            Encoding::Postcard => postcard::from_bytes(data).map_err(|e| DecodeError(e.to_string())),
+        }
+    }
+}
@@ -1,18 +1,277 @@
+use std::fmt;
 use serde::{Deserialize, Serialize};
 
+mod encoding;
+mod validate;
+
+pub use encoding::*;
+pub use validate::*;
+
+/// Fields an older board does not send yet take their default in JSON;
+/// postcard has no such fallback, see [`API_VERSION`].
 #[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct State {
     pub battery: BatteryState,
     pub pyro: PyroState,
     pub wifi_state: WifiConnectionConfiguration,
+    /// SSIDs of the stored networks in the order they are tried at boot.
+    pub wifi_networks: Vec<String>,
     pub barometer: BarometerState,
     pub servo: ServoState,
+    pub flight: FlightState,
+    pub recovery: RecoveryState,
+    pub log: LogState,
+    /// Milliseconds since boot when the board last checked the sections for staleness.
+    pub time_ms: u64,
+    pub samples: Samples,
+    pub health: Health,
+    pub self_test: SelfTestReport,
+    /// Why the board last started.
+    pub reset_reason: ResetReason,
+    pub ota: OtaState,
+    pub firmware: FirmwareSlots,
+}
+
+/// The two app partitions the board boots from.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct FirmwareSlots {
+    pub running: Option<SlotInfo>,
+    /// The other partition: the image that ran before, or an update waiting
+    /// for the next reset.
+    pub previous: Option<SlotInfo>,
+}
+
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct SlotInfo {
+    /// Partition label, e.g. `ota_0`.
+    pub label: String,
+    /// Firmware version of the image, `None` if the partition holds none.
+    pub version: Option<String>,
+    pub status: SlotStatus,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Debug)]
+pub enum SlotStatus {
+    #[default]
+    Unknown,
+    Factory,
+    Valid,
+    /// Booted for the first time; rolled back on the next reset unless marked valid.
+    Unverified,
+    Invalid,
+}
+
+/// Progress of a firmware upload to `POST /ota`.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct OtaState {
+    pub status: OtaStatus,
+    /// Bytes written to the update partition.
+    pub received: u32,
+    /// Size of the image being uploaded, bytes.
+    pub size: u32,
+    /// Firmware version from the header of the image being uploaded.
+    pub version: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Debug)]
+pub enum OtaStatus {
+    #[default]
+    Idle,
+    Receiving,
+    /// The new image boots after the next reset.
+    Complete,
+    Failed,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Debug)]
+pub enum ResetReason {
+    #[default]
+    Unknown,
+    PowerOn,
+    /// Reset pin or a debugger.
+    External,
+    /// Requested by the firmware, e.g. `Command::Reset` or an update.
+    Software,
+    Panic,
+    Watchdog,
+    Brownout,
+    DeepSleep,
+}
+
+/// When each section of [`State`] was last updated by its sensor, or by a
+/// command for the servos.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct Samples {
+    pub battery: SampleInfo,
+    pub barometer: SampleInfo,
+    /// Continuity readings; arming and firing do not count.
+    pub pyro: SampleInfo,
+    pub servo: SampleInfo,
+}
+
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct SampleInfo {
+    /// Milliseconds since boot, `None` before the first update.
+    pub time_ms: Option<u64>,
+    /// Updates since boot, wrapping around.
+    pub sequence: u32,
+    /// The sensor missed several periods in a row, its values are old.
+    pub stale: bool,
+}
+
+impl SampleInfo {
+    pub fn record(&mut self, time_ms: u64) {
+        self.time_ms = Some(time_ms);
+        self.sequence = self.sequence.wrapping_add(1);
+        self.stale = false;
+    }
+}
+
+/// Condition of each sensor, as seen by its sampling loop.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct Health {
+    pub barometer: SensorHealth,
+    pub fuel_gauge: SensorHealth,
+    pub continuity: SensorHealth,
+}
+
+impl Health {
+    pub fn sensor(&self, sensor: Sensor) -> &SensorHealth {
+        match sensor {
+            Sensor::Barometer => &self.barometer,
+            Sensor::FuelGauge => &self.fuel_gauge,
+            Sensor::PyroContinuity => &self.continuity,
+        }
+    }
+
+    pub fn sensor_mut(&mut self, sensor: Sensor) -> &mut SensorHealth {
+        match sensor {
+            Sensor::Barometer => &mut self.barometer,
+            Sensor::FuelGauge => &mut self.fuel_gauge,
+            Sensor::PyroContinuity => &mut self.continuity,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Debug)]
+pub enum SensorStatus {
+    #[default]
+    Ok,
+    /// The last reads failed, the sensor is being retried.
+    Degraded,
+    /// Too many reads failed in a row, the sensor is re-initialised with backoff.
+    Failed,
+}
+
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct SensorHealth {
+    pub status: SensorStatus,
+    /// Failed reads since the last successful one.
+    pub consecutive_errors: u32,
+    /// Failed reads since boot.
+    pub total_errors: u32,
+    /// Re-initialisations attempted since boot.
+    pub reinits: u32,
+    pub last_error: Option<String>,
+    /// Milliseconds since boot of the last failed read.
+    pub last_error_ms: Option<u64>,
+}
+
+/// Peripheral checked by the boot-time self-test.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum Check {
+    FuelGauge,
+    Barometer,
+    Continuity,
+    Led,
+    Nvs,
+    LogStorage,
+    OtaPartition,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Debug)]
+pub enum CheckStatus {
+    #[default]
+    Pass,
+    /// Worth a look, but does not keep the rocket from flying.
+    Warn,
+    Fail,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct CheckResult {
+    pub check: Check,
+    pub status: CheckStatus,
+    pub message: Option<String>,
+}
+
+/// Result of the boot-time self-test, returned by `GET /selftest`.
+/// Readiness is re-evaluated while the board runs, so a sensor failing after
+/// boot also blocks the flight.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct SelfTestReport {
+    /// Milliseconds since boot when the self-test finished, `None` before.
+    pub time_ms: Option<u64>,
+    pub checks: Vec<CheckResult>,
+    pub ready_to_fly: bool,
+    /// Why the rocket is not ready to fly, empty when it is.
+    pub blocking: Vec<String>,
+}
+
+impl Check {
+    /// Whether a failure shows that the firmware cannot drive the board, as
+    /// opposed to the board not being ready to fly yet, like a low battery.
+    pub fn proves_firmware(self) -> bool {
+        matches!(self, Check::Barometer | Check::Continuity | Check::Nvs)
+    }
+}
+
+impl SelfTestReport {
+    /// Whether the self-test ran and none of the checks proving the firmware
+    /// failed. A new firmware image is kept only if it works.
+    pub fn firmware_works(&self) -> bool {
+        self.time_ms.is_some() && self.checks.iter()
+            .all(|result| !result.check.proves_firmware() || result.status != CheckStatus::Fail)
+    }
 }
 
 #[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct ServoState {
     pub servo1_duty: Option<f32>,
     pub servo2_duty: Option<f32>,
+    pub servo1_position: Option<f32>,
+    pub servo2_position: Option<f32>,
+    pub servo1_calibration: ServoCalibration,
+    pub servo2_calibration: ServoCalibration,
+}
+
+/// Pulse widths are in microseconds, positions are normalized to -1..1.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ServoCalibration {
+    pub min_pulse_us: u16,
+    pub center_pulse_us: u16,
+    pub max_pulse_us: u16,
+    pub trim_us: i16,
+    pub reverse: bool,
+    pub min_position: f32,
+    pub max_position: f32,
+}
+
+impl Default for ServoCalibration {
+    fn default() -> Self {
+        Self {
+            min_pulse_us: 1000,
+            center_pulse_us: 1500,
+            max_pulse_us: 2000,
+            trim_us: 0,
+            reverse: false,
+            min_position: -1.0,
+            max_position: 1.0,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
@@ -40,34 +299,327 @@ pub enum WifiConnectionType {
     StartAccessPoint,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for WifiConnectionType {
     fn default() -> Self { WifiConnectionType::StartAccessPoint }
 }
 
+/// A network found by `GET /wifi/scan`.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct WifiNetwork {
+    pub ssid: String,
+    /// Signal strength, dBm.
+    pub rssi: i8,
+    pub channel: u8,
+    pub auth: WifiAuth,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum WifiAuth {
+    Open,
+    Wep,
+    Wpa,
+    Wpa2,
+    Wpa3,
+    /// Needs a user name as well, the board cannot join these.
+    Enterprise,
+    Other,
+}
+
+impl WifiAuth {
+    pub fn needs_password(&self) -> bool {
+        *self != WifiAuth::Open
+    }
+}
+
 #[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct PyroChannelState {
     pub fire: bool,
     pub test_voltage: f32,
+    pub fire_time_ms: Option<u64>,
+    /// Continuity measured after the ignition pulse; `Some(false)` means the igniter burnt through.
+    pub post_fire_continuity: Option<bool>,
 }
 
 #[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct PyroState {
+    pub armed: bool,
     pub channel1: PyroChannelState,
     pub channel2: PyroChannelState,
 }
 
 #[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct BarometerState {
-    pub altitude: f32,
+    /// Filtered altitude above mean sea level for the configured QNH, metres.
+    pub altitude_msl: f32,
+    /// Filtered altitude above the zeroed ground level, metres.
+    pub altitude_agl: f32,
+    /// Raw pressure, pascals.
+    pub pressure: f32,
+    pub ground_pressure: Option<f32>,
+    pub qnh: f32,
+    pub vertical_velocity: f32,
+    pub vertical_acceleration: f32,
     pub temperature: f32,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Debug)]
+pub enum FlightPhase {
+    #[default]
+    Idle,
+    Armed,
+    Boost,
+    Coast,
+    Apogee,
+    Descent,
+    Landed,
+}
+
+impl FlightPhase {
+    /// Between launch and landing.
+    pub fn is_flying(self) -> bool {
+        matches!(self, FlightPhase::Boost | FlightPhase::Coast | FlightPhase::Apogee | FlightPhase::Descent)
+    }
+}
+
+/// Flight phase reported by the firmware. Timestamps are milliseconds since boot.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct FlightState {
+    pub phase: FlightPhase,
+    pub ground_altitude: f32,
+    pub max_altitude: f32,
+    pub armed_time_ms: Option<u64>,
+    pub launch_time_ms: Option<u64>,
+    pub burnout_time_ms: Option<u64>,
+    pub apogee_time_ms: Option<u64>,
+    pub descent_time_ms: Option<u64>,
+    pub landing_time_ms: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct LogState {
+    pub session: u16,
+    pub recording_fast: bool,
+    pub records_written: u32,
+    pub capacity_records: u32,
+}
+
+/// A logging session stored on the board, as listed by `GET /logs`.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct LogInfo {
+    pub id: u16,
+    /// Size of the raw log served by `GET /logs/{id}`, bytes.
+    pub size: u32,
+    pub records: u32,
+    /// Unix time in seconds when the session started, if the board clock was set.
+    pub start_time: Option<u64>,
+    pub duration_ms: u32,
+    pub max_altitude: f32,
+    /// The session currently being recorded, which cannot be deleted.
+    pub active: bool,
+}
+
+/// Assignment of pyro channels to recovery events. A `None` channel disables the event,
+/// backup timers are measured from launch detection.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RecoveryConfig {
+    pub drogue_channel: Option<u8>,
+    pub drogue_delay_ms: u32,
+    pub drogue_backup_ms: Option<u32>,
+    pub main_channel: Option<u8>,
+    pub main_altitude: f32,
+    pub main_backup_ms: Option<u32>,
+    pub fire_duration_ms: u32,
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self {
+            drogue_channel: None,
+            drogue_delay_ms: 0,
+            drogue_backup_ms: None,
+            main_channel: None,
+            main_altitude: 150.0,
+            main_backup_ms: None,
+            fire_duration_ms: 500,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct RecoveryState {
+    pub config: RecoveryConfig,
+    pub drogue_deploy_time_ms: Option<u64>,
+    pub main_deploy_time_ms: Option<u64>,
+}
+
+/// Version of the HTTP protocol built from the types in this crate. Bumped on
+/// every change that older clients or boards cannot deserialize.
+pub const API_VERSION: u32 = 9;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum Sensor {
+    Barometer,
+    FuelGauge,
+    PyroContinuity,
+}
+
+/// Returned by `GET /info`, for clients to check compatibility on connect.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct BoardInfo {
+    pub api_version: u32,
+    pub firmware_version: String,
+    pub git_hash: Option<String>,
+    pub board: String,
+    /// Names of the accepted commands, see [`Command::name`].
+    pub commands: Vec<String>,
+    pub sensors: Vec<Sensor>,
+    pub pyro_channels: u8,
+    pub servo_channels: u8,
+}
+
+impl BoardInfo {
+    pub fn is_compatible(&self) -> bool {
+        self.api_version == API_VERSION
+    }
+
+    pub fn supports(&self, command: &Command) -> bool {
+        self.commands.iter().any(|c| c == command.name())
+    }
+}
 
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum Command {
-    Reset,
-    SetWifi { ssid: String, password: String },
-    ResetNvs,
+    /// Reboots the board once the logs are flushed. Refused in flight.
+    Reset { confirm: String },
+    /// Stores a network, tried after those stored before, or changes the
+    /// password of a stored one. Open networks have an empty password.
+    AddWifiNetwork { ssid: String, password: String },
+    RemoveWifiNetwork { ssid: String },
+    /// Moves a stored network to `priority` in the list, 0 is tried first.
+    MoveWifiNetwork { ssid: String, priority: u8 },
+    /// Erases the stored settings, restores the defaults and reboots. Refused in flight.
+    ResetNvs { confirm: String },
     SetLedColor { r: u8, g: u8, b: u8 },
     SetPwmDutyCycle { duty_1: Option<f32>, duty_2: Option<f32> },
-}
\ No newline at end of file
+    ArmPyro,
+    DisarmPyro,
+    FirePyro { channel: u8, duration_ms: u32 },
+    SetRecoveryConfig { config: RecoveryConfig },
+    SetServoCalibration { channel: u8, calibration: ServoCalibration },
+    SetServoPosition { position_1: Option<f32>, position_2: Option<f32> },
+    /// Zeroes the reported altitude above ground. Recovery deployment does not
+    /// change: it uses the ground level the flight state machine tracks on the pad.
+    ZeroAltitude,
+    SetQnh { pressure: f32 },
+    SetFieldElevation { altitude: f32 },
+}
+
+impl Command {
+    /// Names of all commands, as they appear in the serialized form.
+    pub const NAMES: &'static [&'static str] = &[
+        "Reset",
+        "AddWifiNetwork",
+        "RemoveWifiNetwork",
+        "MoveWifiNetwork",
+        "ResetNvs",
+        "SetLedColor",
+        "SetPwmDutyCycle",
+        "ArmPyro",
+        "DisarmPyro",
+        "FirePyro",
+        "SetRecoveryConfig",
+        "SetServoCalibration",
+        "SetServoPosition",
+        "ZeroAltitude",
+        "SetQnh",
+        "SetFieldElevation",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Reset { .. } => "Reset",
+            Command::AddWifiNetwork { .. } => "AddWifiNetwork",
+            Command::RemoveWifiNetwork { .. } => "RemoveWifiNetwork",
+            Command::MoveWifiNetwork { .. } => "MoveWifiNetwork",
+            Command::ResetNvs { .. } => "ResetNvs",
+            Command::SetLedColor { .. } => "SetLedColor",
+            Command::SetPwmDutyCycle { .. } => "SetPwmDutyCycle",
+            Command::ArmPyro => "ArmPyro",
+            Command::DisarmPyro => "DisarmPyro",
+            Command::FirePyro { .. } => "FirePyro",
+            Command::SetRecoveryConfig { .. } => "SetRecoveryConfig",
+            Command::SetServoCalibration { .. } => "SetServoCalibration",
+            Command::SetServoPosition { .. } => "SetServoPosition",
+            Command::ZeroAltitude => "ZeroAltitude",
+            Command::SetQnh { .. } => "SetQnh",
+            Command::SetFieldElevation { .. } => "SetFieldElevation",
+        }
+    }
+}
+
+/// Why a command was rejected or could not be carried out.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum ApiError {
+    /// The request body is not a command.
+    MalformedCommand,
+    /// An argument is out of range or names something that does not exist.
+    InvalidArgument,
+    /// Firing requires the pyro channels to be armed first.
+    NotArmed,
+    /// A sensor or output did not respond as expected.
+    HardwareFault,
+    /// Settings could not be written to or read from flash.
+    StorageError,
+    /// The command is not allowed between launch and landing.
+    InFlight,
+    /// Another firmware update is in progress.
+    Busy,
+    /// The firmware image is not signed with the board's update key.
+    Unauthorized,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ApiError::MalformedCommand => "Malformed command",
+            ApiError::InvalidArgument => "Invalid argument",
+            ApiError::NotArmed => "Pyro not armed",
+            ApiError::HardwareFault => "Hardware fault",
+            ApiError::StorageError => "Storage error",
+            ApiError::InFlight => "Not allowed in flight",
+            ApiError::Busy => "Busy",
+            ApiError::Unauthorized => "Not authorized",
+        })
+    }
+}
+
+/// Body of every `/command` response.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum CommandResponse {
+    Ok,
+    Failed { error: ApiError, message: String },
+}
+
+/// Telemetry rates a `/ws` client can subscribe to, milliseconds between states.
+pub const MIN_TELEMETRY_PERIOD_MS: u32 = 50;
+pub const MAX_TELEMETRY_PERIOD_MS: u32 = 10_000;
+
+/// Text frames sent by clients on the `/ws` socket.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// Pushes a [`State`] every `period_ms`, clamped to the supported range.
+    Subscribe { period_ms: u32 },
+    Unsubscribe,
+    /// Same as `POST /command`, answered with a `CommandResponse` carrying `id`.
+    Command { id: u32, command: Command },
+}
+
+/// Text frames sent by the board on the `/ws` socket.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    State(Box<State>),
+    CommandResponse { id: u32, response: CommandResponse },
+    /// The client sent something that is not a [`ClientMessage`].
+    Rejected { message: String },
+}
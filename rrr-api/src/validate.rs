@@ -0,0 +1,237 @@
+//! Argument checks shared by the firmware, which rejects invalid commands
+//! before acting on them, and the frontend, which checks its forms before
+//! sending.
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::{Command, RecoveryConfig, ServoCalibration};
+
+/// Longest SSID allowed by 802.11, in bytes.
+pub const MAX_SSID_LEN: usize = 32;
+/// WPA2 passphrase length limits, in characters.
+pub const MIN_PASSWORD_LEN: usize = 8;
+pub const MAX_PASSWORD_LEN: usize = 63;
+/// Networks the board stores, tried in order at boot.
+pub const MAX_WIFI_NETWORKS: usize = 8;
+pub const PYRO_CHANNELS: u8 = 2;
+pub const SERVO_CHANNELS: u8 = 2;
+pub const MIN_FIRE_DURATION_MS: u32 = 10;
+pub const MAX_FIRE_DURATION_MS: u32 = 2000;
+/// PWM period of a standard 50 Hz hobby servo.
+pub const SERVO_PERIOD_US: u32 = 20_000;
+/// Sea level pressures outside this range have never been observed, pascals.
+pub const QNH_RANGE: (f32, f32) = (85_000.0, 110_000.0);
+/// Launch site elevations accepted, meters.
+pub const FIELD_ELEVATION_RANGE: (f32, f32) = (-500.0, 9_000.0);
+/// Confirmations `Command::Reset` and `Command::ResetNvs` must carry, so that a
+/// stray or truncated command cannot reboot or wipe the board.
+pub const RESET_CONFIRMATION: &str = "reset";
+pub const RESET_NVS_CONFIRMATION: &str = "erase settings";
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum ValidationError {
+    SsidLength { length: usize },
+    PasswordLength { length: usize },
+    PasswordNotAscii,
+    DutyCycleOutOfRange { channel: u8, value: f32 },
+    PositionOutOfRange { channel: u8, value: f32 },
+    PyroChannel { channel: u8 },
+    FireDuration { duration_ms: u32 },
+    SameRecoveryChannel { channel: u8 },
+    MainAltitude { altitude: f32 },
+    ServoChannel { channel: u8 },
+    ServoPulses,
+    ServoTravel,
+    Qnh { pressure: f32 },
+    FieldElevation { altitude: f32 },
+    NotConfirmed { expected: String },
+    WifiPriority { priority: u8 },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::SsidLength { length } =>
+                write!(f, "SSID must be 1 to {} bytes long, not {}", MAX_SSID_LEN, length),
+            ValidationError::PasswordLength { length } =>
+                write!(f, "Password must be empty or {} to {} characters long, not {}", MIN_PASSWORD_LEN, MAX_PASSWORD_LEN, length),
+            ValidationError::PasswordNotAscii =>
+                write!(f, "Password may only contain printable ASCII characters"),
+            ValidationError::DutyCycleOutOfRange { channel, value } =>
+                write!(f, "Duty cycle of servo {} must be between 0 and 1, not {}", channel, value),
+            ValidationError::PositionOutOfRange { channel, value } =>
+                write!(f, "Position of servo {} must be between -1 and 1, not {}", channel, value),
+            ValidationError::PyroChannel { channel } =>
+                write!(f, "Pyro channel must be 1 to {}, not {}", PYRO_CHANNELS, channel),
+            ValidationError::FireDuration { duration_ms } =>
+                write!(f, "Ignition pulse must be {} to {} ms, not {}", MIN_FIRE_DURATION_MS, MAX_FIRE_DURATION_MS, duration_ms),
+            ValidationError::SameRecoveryChannel { channel } =>
+                write!(f, "Drogue and main cannot both use pyro channel {}", channel),
+            ValidationError::MainAltitude { altitude } =>
+                write!(f, "Main deployment altitude must be above ground, not {}", altitude),
+            ValidationError::ServoChannel { channel } =>
+                write!(f, "Servo channel must be 1 to {}, not {}", SERVO_CHANNELS, channel),
+            ValidationError::ServoPulses =>
+                write!(f, "Servo pulses must increase from min to center to max and stay below {} us", SERVO_PERIOD_US),
+            ValidationError::ServoTravel =>
+                write!(f, "Servo travel limits must satisfy -1 <= min < max <= 1"),
+            ValidationError::Qnh { pressure } =>
+                write!(f, "QNH must be {} to {} hPa, not {}", QNH_RANGE.0 / 100.0, QNH_RANGE.1 / 100.0, pressure / 100.0),
+            ValidationError::FieldElevation { altitude } =>
+                write!(f, "Field elevation must be {} to {} m, not {}", FIELD_ELEVATION_RANGE.0, FIELD_ELEVATION_RANGE.1, altitude),
+            ValidationError::NotConfirmed { expected } =>
+                write!(f, "Confirm with \"{}\"", expected),
+            ValidationError::WifiPriority { priority } =>
+                write!(f, "Network priority must be 0 to {}, not {}", MAX_WIFI_NETWORKS - 1, priority),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+fn in_range(value: f32, (min, max): (f32, f32)) -> bool {
+    (min..=max).contains(&value)
+}
+
+fn check_ssid(ssid: &str) -> Result<(), ValidationError> {
+    if ssid.is_empty() || ssid.len() > MAX_SSID_LEN {
+        return Err(ValidationError::SsidLength { length: ssid.len() });
+    }
+    Ok(())
+}
+
+fn check_pyro_channel(channel: u8) -> Result<(), ValidationError> {
+    if (1..=PYRO_CHANNELS).contains(&channel) {
+        Ok(())
+    } else {
+        Err(ValidationError::PyroChannel { channel })
+    }
+}
+
+fn check_fire_duration(duration_ms: u32) -> Result<(), ValidationError> {
+    if (MIN_FIRE_DURATION_MS..=MAX_FIRE_DURATION_MS).contains(&duration_ms) {
+        Ok(())
+    } else {
+        Err(ValidationError::FireDuration { duration_ms })
+    }
+}
+
+fn check_confirmation(confirm: &str, expected: &str) -> Result<(), ValidationError> {
+    if confirm == expected {
+        Ok(())
+    } else {
+        Err(ValidationError::NotConfirmed { expected: expected.into() })
+    }
+}
+
+fn check_servo_values(values: [Option<f32>; 2], range: (f32, f32), error: fn(u8, f32) -> ValidationError) -> Result<(), ValidationError> {
+    for (channel, value) in (1..).zip(values) {
+        match value {
+            Some(value) if !in_range(value, range) => return Err(error(channel, value)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl RecoveryConfig {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        for channel in [self.drogue_channel, self.main_channel].into_iter().flatten() {
+            check_pyro_channel(channel)?;
+        }
+        if let (Some(drogue), Some(main)) = (self.drogue_channel, self.main_channel) {
+            if drogue == main {
+                return Err(ValidationError::SameRecoveryChannel { channel: drogue });
+            }
+        }
+        if !(self.main_altitude > 0.0 && self.main_altitude.is_finite()) {
+            return Err(ValidationError::MainAltitude { altitude: self.main_altitude });
+        }
+        check_fire_duration(self.fire_duration_ms)
+    }
+}
+
+impl ServoCalibration {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let pulses_ordered = self.min_pulse_us < self.center_pulse_us
+            && self.center_pulse_us < self.max_pulse_us
+            && (self.max_pulse_us as u32) < SERVO_PERIOD_US;
+        if !pulses_ordered {
+            return Err(ValidationError::ServoPulses);
+        }
+        let travel_ordered = -1.0 <= self.min_position
+            && self.min_position < self.max_position
+            && self.max_position <= 1.0;
+        if !travel_ordered {
+            return Err(ValidationError::ServoTravel);
+        }
+        Ok(())
+    }
+}
+
+impl Command {
+    /// Checks the arguments without looking at the board's state.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self {
+            Command::Reset { confirm } => check_confirmation(confirm, RESET_CONFIRMATION),
+            Command::ResetNvs { confirm } => check_confirmation(confirm, RESET_NVS_CONFIRMATION),
+            Command::SetLedColor { .. }
+            | Command::ArmPyro
+            | Command::DisarmPyro
+            | Command::ZeroAltitude => Ok(()),
+            Command::AddWifiNetwork { ssid, password } => {
+                check_ssid(ssid)?;
+                if !password.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+                    return Err(ValidationError::PasswordNotAscii);
+                }
+                if !password.is_empty() && !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&password.len()) {
+                    return Err(ValidationError::PasswordLength { length: password.len() });
+                }
+                Ok(())
+            }
+            Command::RemoveWifiNetwork { ssid } => check_ssid(ssid),
+            Command::MoveWifiNetwork { ssid, priority } => {
+                check_ssid(ssid)?;
+                if *priority as usize >= MAX_WIFI_NETWORKS {
+                    return Err(ValidationError::WifiPriority { priority: *priority });
+                }
+                Ok(())
+            }
+            Command::SetPwmDutyCycle { duty_1, duty_2 } => {
+                check_servo_values([*duty_1, *duty_2], (0.0, 1.0), |channel, value| {
+                    ValidationError::DutyCycleOutOfRange { channel, value }
+                })
+            }
+            Command::SetServoPosition { position_1, position_2 } => {
+                check_servo_values([*position_1, *position_2], (-1.0, 1.0), |channel, value| {
+                    ValidationError::PositionOutOfRange { channel, value }
+                })
+            }
+            Command::FirePyro { channel, duration_ms } => {
+                check_pyro_channel(*channel)?;
+                check_fire_duration(*duration_ms)
+            }
+            Command::SetRecoveryConfig { config } => config.validate(),
+            Command::SetServoCalibration { channel, calibration } => {
+                if !(1..=SERVO_CHANNELS).contains(channel) {
+                    return Err(ValidationError::ServoChannel { channel: *channel });
+                }
+                calibration.validate()
+            }
+            Command::SetQnh { pressure } => {
+                if in_range(*pressure, QNH_RANGE) {
+                    Ok(())
+                } else {
+                    Err(ValidationError::Qnh { pressure: *pressure })
+                }
+            }
+            Command::SetFieldElevation { altitude } => {
+                if in_range(*altitude, FIELD_ELEVATION_RANGE) {
+                    Ok(())
+                } else {
+                    Err(ValidationError::FieldElevation { altitude: *altitude })
+                }
+            }
+        }
+    }
+}
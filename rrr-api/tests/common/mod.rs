@@ -0,0 +1,60 @@
+//! Messages with every variant and field populated.
+
+#![allow(dead_code)]
+
+use rrr_api::*;
+
+pub fn all_commands() -> Vec<Command> {
+    vec![
+        Command::Reset { confirm: RESET_CONFIRMATION.into() },
+        Command::AddWifiNetwork { ssid: "field".into(), password: "12345678".into() },
+        Command::RemoveWifiNetwork { ssid: "field".into() },
+        Command::MoveWifiNetwork { ssid: "field".into(), priority: 0 },
+        Command::ResetNvs { confirm: RESET_NVS_CONFIRMATION.into() },
+        Command::SetLedColor { r: 0, g: 0, b: 0 },
+        Command::SetPwmDutyCycle { duty_1: None, duty_2: None },
+        Command::ArmPyro,
+        Command::DisarmPyro,
+        Command::FirePyro { channel: 1, duration_ms: 500 },
+        Command::SetRecoveryConfig { config: RecoveryConfig::default() },
+        Command::SetServoCalibration { channel: 1, calibration: ServoCalibration::default() },
+        Command::SetServoPosition { position_1: None, position_2: None },
+        Command::ZeroAltitude,
+        Command::SetQnh { pressure: 101_325.0 },
+        Command::SetFieldElevation { altitude: 0.0 },
+    ]
+}
+
+pub fn sample_state() -> State {
+    let mut state = State {
+        battery: BatteryState { soc: 0.87, voltage: 4.05, charge_rate: -6.0 },
+        ..Default::default()
+    };
+    state.pyro.armed = true;
+    state.pyro.channel1 = PyroChannelState {
+        fire: false,
+        test_voltage: 3.3,
+        fire_time_ms: Some(41_250),
+        post_fire_continuity: Some(false),
+    };
+    state.wifi_state.credentials = WifiCredentials { ssid: "field".into(), password: "12345678".into() };
+    state.wifi_networks = vec!["field".into(), "club-guest".into()];
+    state.barometer.altitude_agl = 481.5;
+    state.barometer.pressure = 92_960.0;
+    state.barometer.ground_pressure = Some(98_360.0);
+    state.servo.servo1_position = Some(-0.5);
+    state.flight.phase = FlightPhase::Descent;
+    state.flight.apogee_time_ms = Some(41_200);
+    state.recovery.config.drogue_channel = Some(1);
+    state.log.records_written = 12_000;
+    state.reset_reason = ResetReason::Brownout;
+    state.health.barometer = SensorHealth {
+        status: SensorStatus::Degraded,
+        consecutive_errors: 2,
+        total_errors: 7,
+        reinits: 1,
+        last_error: Some("Device did not respond".into()),
+        last_error_ms: Some(40_980),
+    };
+    state
+}
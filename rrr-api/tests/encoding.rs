@@ -0,0 +1,86 @@
+mod common;
+
+use common::sample_state;
+use rrr_api::*;
+
+#[test]
+fn json_is_the_default() {
+    assert_eq!(Encoding::negotiate(None), Encoding::Json);
+    assert_eq!(Encoding::negotiate(Some("*/*")), Encoding::Json);
+    assert_eq!(Encoding::negotiate(Some("text/html, application/json;q=0.9")), Encoding::Json);
+    assert_eq!(Encoding::from_content_type("Application/JSON; charset=utf-8"), Some(Encoding::Json));
+    assert_eq!(Encoding::from_content_type("text/plain"), None);
+
+    let state = sample_state();
+    let json = Encoding::Json.encode(&state);
+    assert!(Encoding::Json.decode::<State>(&json).unwrap() == state);
+    // Sections an older board does not send are left at their default.
+    assert!(Encoding::Json.decode::<State>(b"{}").unwrap() == State::default());
+    assert!(Encoding::Json.decode::<State>(b"null").is_err());
+}
+
+#[cfg(feature = "binary")]
+mod binary {
+    use super::*;
+    use common::all_commands;
+
+    const ENCODINGS: [Encoding; 2] = [Encoding::Json, Encoding::Postcard];
+
+    /// Encodes with one encoding, decodes, re-encodes with the other and decodes again.
+    fn round_trip<T>(value: &T) -> bool
+        where T: PartialEq + serde::Serialize + serde::de::DeserializeOwned
+    {
+        ENCODINGS.iter().all(|first| ENCODINGS.iter().all(|second| {
+            let decoded: T = first.decode(&first.encode(value)).unwrap();
+            let decoded: T = second.decode(&second.encode(&decoded)).unwrap();
+            decoded == *value
+        }))
+    }
+
+    #[test]
+    fn negotiation() {
+        assert_eq!(Encoding::negotiate(Some(POSTCARD_CONTENT_TYPE)), Encoding::Postcard);
+        assert_eq!(Encoding::negotiate(Some("application/x-postcard, application/json")), Encoding::Postcard);
+        assert_eq!(Encoding::negotiate(Some("application/json, application/x-postcard")), Encoding::Json);
+        assert_eq!(Encoding::from_content_type(POSTCARD_CONTENT_TYPE), Some(Encoding::Postcard));
+        assert!(!Encoding::Postcard.is_text());
+    }
+
+    #[test]
+    fn state_round_trips() {
+        assert!(round_trip(&sample_state()));
+        assert!(round_trip(&State::default()));
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        for command in all_commands() {
+            assert!(round_trip(&command), "{}", command.name());
+        }
+    }
+
+    #[test]
+    fn socket_messages_round_trip() {
+        assert!(round_trip(&ClientMessage::Subscribe { period_ms: 100 }));
+        assert!(round_trip(&ClientMessage::Command { id: 3, command: Command::ArmPyro }));
+        assert!(round_trip(&ServerMessage::State(Box::new(sample_state()))));
+        assert!(round_trip(&ServerMessage::CommandResponse {
+            id: 3,
+            response: CommandResponse::Failed { error: ApiError::NotArmed, message: "Pyro channels are not armed".into() },
+        }));
+    }
+
+    #[test]
+    fn postcard_is_compact() {
+        let state = sample_state();
+        let json = Encoding::Json.encode(&state).len();
+        let postcard = Encoding::Postcard.encode(&state).len();
+        assert!(postcard * 4 < json, "{} vs {} bytes", postcard, json);
+    }
+
+    #[test]
+    fn truncated_postcard_is_rejected() {
+        let data = Encoding::Postcard.encode(&sample_state());
+        assert!(Encoding::Postcard.decode::<State>(&data[..data.len() / 2]).is_err());
+    }
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use common::all_commands;
+use rrr_api::*;
+
+#[test]
+fn command_names_match_serialized_form() {
+    let commands = all_commands();
+    assert_eq!(commands.len(), Command::NAMES.len());
+    for (command, name) in commands.iter().zip(Command::NAMES) {
+        assert_eq!(command.name(), *name);
+        let json = serde_json::to_string(command).unwrap();
+        assert!(json == format!("\"{}\"", name) || json.starts_with(&format!("{{\"{}\":", name)), "{}", json);
+    }
+}
+
+#[test]
+fn compatibility() {
+    let info = BoardInfo {
+        api_version: API_VERSION,
+        commands: vec!["ArmPyro".into(), "DisarmPyro".into()],
+        ..Default::default()
+    };
+    assert!(info.is_compatible());
+    assert!(info.supports(&Command::ArmPyro));
+    assert!(!info.supports(&Command::FirePyro { channel: 1, duration_ms: 500 }));
+
+    assert!(!BoardInfo { api_version: API_VERSION + 1, ..info.clone() }.is_compatible());
+    assert!(!BoardInfo { api_version: 0, ..info }.is_compatible());
+}
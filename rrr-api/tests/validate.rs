@@ -0,0 +1,177 @@
+use rrr_api::*;
+
+fn wifi(ssid: &str, password: &str) -> Command {
+    Command::AddWifiNetwork { ssid: ssid.into(), password: password.into() }
+}
+
+fn calibration(channel: u8, calibration: ServoCalibration) -> Command {
+    Command::SetServoCalibration { channel, calibration }
+}
+
+fn recovery(config: RecoveryConfig) -> Command {
+    Command::SetRecoveryConfig { config }
+}
+
+#[test]
+fn commands_without_arguments_are_valid() {
+    for command in [
+        Command::ArmPyro,
+        Command::DisarmPyro,
+        Command::ZeroAltitude,
+        Command::SetLedColor { r: 255, g: 0, b: 255 },
+    ] {
+        assert_eq!(command.validate(), Ok(()));
+    }
+}
+
+#[test]
+fn resets_need_confirmation() {
+    assert_eq!(Command::Reset { confirm: RESET_CONFIRMATION.into() }.validate(), Ok(()));
+    assert_eq!(Command::ResetNvs { confirm: RESET_NVS_CONFIRMATION.into() }.validate(), Ok(()));
+
+    let expected = ValidationError::NotConfirmed { expected: RESET_NVS_CONFIRMATION.into() };
+    assert_eq!(Command::ResetNvs { confirm: RESET_CONFIRMATION.into() }.validate(), Err(expected));
+    let expected = ValidationError::NotConfirmed { expected: RESET_CONFIRMATION.into() };
+    assert_eq!(Command::Reset { confirm: String::new() }.validate(), Err(expected.clone()));
+    assert_eq!(expected.to_string(), "Confirm with \"reset\"");
+}
+
+#[test]
+fn wifi_credentials() {
+    assert_eq!(wifi("field", "12345678").validate(), Ok(()));
+    assert_eq!(wifi(&"s".repeat(32), &"p".repeat(63)).validate(), Ok(()));
+    assert_eq!(wifi("with space", "pass word!").validate(), Ok(()));
+    // Open networks.
+    assert_eq!(wifi("club-guest", "").validate(), Ok(()));
+
+    assert_eq!(wifi("", "12345678").validate(), Err(ValidationError::SsidLength { length: 0 }));
+    assert_eq!(wifi(&"s".repeat(33), "12345678").validate(), Err(ValidationError::SsidLength { length: 33 }));
+    // Multi-byte characters count with their UTF-8 length.
+    assert_eq!(wifi(&"ü".repeat(17), "12345678").validate(), Err(ValidationError::SsidLength { length: 34 }));
+    assert_eq!(wifi("field", "1234567").validate(), Err(ValidationError::PasswordLength { length: 7 }));
+    assert_eq!(wifi("field", &"p".repeat(64)).validate(), Err(ValidationError::PasswordLength { length: 64 }));
+    assert_eq!(wifi("field", "pässword").validate(), Err(ValidationError::PasswordNotAscii));
+    assert_eq!(wifi("field", "pass\tword").validate(), Err(ValidationError::PasswordNotAscii));
+}
+
+#[test]
+fn wifi_network_list() {
+    assert_eq!(Command::RemoveWifiNetwork { ssid: "field".into() }.validate(), Ok(()));
+    assert_eq!(Command::RemoveWifiNetwork { ssid: String::new() }.validate(), Err(ValidationError::SsidLength { length: 0 }));
+    let move_to = |priority| Command::MoveWifiNetwork { ssid: "field".into(), priority }.validate();
+    assert_eq!(move_to(0), Ok(()));
+    assert_eq!(move_to(MAX_WIFI_NETWORKS as u8 - 1), Ok(()));
+    assert_eq!(move_to(MAX_WIFI_NETWORKS as u8), Err(ValidationError::WifiPriority { priority: 8 }));
+}
+
+#[test]
+fn pwm_duty_cycles() {
+    assert_eq!(Command::SetPwmDutyCycle { duty_1: Some(0.0), duty_2: Some(1.0) }.validate(), Ok(()));
+    assert_eq!(Command::SetPwmDutyCycle { duty_1: None, duty_2: None }.validate(), Ok(()));
+
+    assert_eq!(
+        Command::SetPwmDutyCycle { duty_1: Some(1.5), duty_2: None }.validate(),
+        Err(ValidationError::DutyCycleOutOfRange { channel: 1, value: 1.5 })
+    );
+    assert_eq!(
+        Command::SetPwmDutyCycle { duty_1: None, duty_2: Some(-0.1) }.validate(),
+        Err(ValidationError::DutyCycleOutOfRange { channel: 2, value: -0.1 })
+    );
+    assert!(Command::SetPwmDutyCycle { duty_1: Some(f32::NAN), duty_2: None }.validate().is_err());
+}
+
+#[test]
+fn servo_positions() {
+    assert_eq!(Command::SetServoPosition { position_1: Some(-1.0), position_2: Some(1.0) }.validate(), Ok(()));
+    assert_eq!(
+        Command::SetServoPosition { position_1: Some(0.0), position_2: Some(1.01) }.validate(),
+        Err(ValidationError::PositionOutOfRange { channel: 2, value: 1.01 })
+    );
+    assert!(Command::SetServoPosition { position_1: Some(f32::INFINITY), position_2: None }.validate().is_err());
+}
+
+#[test]
+fn fire_pyro() {
+    assert_eq!(Command::FirePyro { channel: 2, duration_ms: 500 }.validate(), Ok(()));
+    assert_eq!(Command::FirePyro { channel: 0, duration_ms: 500 }.validate(), Err(ValidationError::PyroChannel { channel: 0 }));
+    assert_eq!(Command::FirePyro { channel: 3, duration_ms: 500 }.validate(), Err(ValidationError::PyroChannel { channel: 3 }));
+    assert_eq!(
+        Command::FirePyro { channel: 1, duration_ms: MAX_FIRE_DURATION_MS + 1 }.validate(),
+        Err(ValidationError::FireDuration { duration_ms: MAX_FIRE_DURATION_MS + 1 })
+    );
+    assert_eq!(
+        Command::FirePyro { channel: 1, duration_ms: 0 }.validate(),
+        Err(ValidationError::FireDuration { duration_ms: 0 })
+    );
+}
+
+#[test]
+fn recovery_config() {
+    let config = RecoveryConfig { drogue_channel: Some(1), main_channel: Some(2), ..Default::default() };
+    assert_eq!(recovery(config.clone()).validate(), Ok(()));
+    assert_eq!(recovery(RecoveryConfig::default()).validate(), Ok(()));
+
+    assert_eq!(
+        recovery(RecoveryConfig { main_channel: Some(3), ..config.clone() }).validate(),
+        Err(ValidationError::PyroChannel { channel: 3 })
+    );
+    assert_eq!(
+        recovery(RecoveryConfig { main_channel: Some(1), ..config.clone() }).validate(),
+        Err(ValidationError::SameRecoveryChannel { channel: 1 })
+    );
+    assert_eq!(
+        recovery(RecoveryConfig { main_altitude: -10.0, ..config.clone() }).validate(),
+        Err(ValidationError::MainAltitude { altitude: -10.0 })
+    );
+    assert_eq!(
+        recovery(RecoveryConfig { fire_duration_ms: 5000, ..config }).validate(),
+        Err(ValidationError::FireDuration { duration_ms: 5000 })
+    );
+}
+
+#[test]
+fn servo_calibration() {
+    let default = ServoCalibration::default();
+    assert_eq!(calibration(2, default.clone()).validate(), Ok(()));
+
+    assert_eq!(calibration(0, default.clone()).validate(), Err(ValidationError::ServoChannel { channel: 0 }));
+    assert_eq!(calibration(3, default.clone()).validate(), Err(ValidationError::ServoChannel { channel: 3 }));
+    assert_eq!(
+        calibration(1, ServoCalibration { center_pulse_us: 2100, ..default.clone() }).validate(),
+        Err(ValidationError::ServoPulses)
+    );
+    assert_eq!(
+        calibration(1, ServoCalibration { max_pulse_us: 20_000, ..default.clone() }).validate(),
+        Err(ValidationError::ServoPulses)
+    );
+    assert_eq!(
+        calibration(1, ServoCalibration { min_position: 0.5, max_position: 0.2, ..default.clone() }).validate(),
+        Err(ValidationError::ServoTravel)
+    );
+    assert_eq!(
+        calibration(1, ServoCalibration { min_position: -1.5, ..default }).validate(),
+        Err(ValidationError::ServoTravel)
+    );
+}
+
+#[test]
+fn altimeter_settings() {
+    assert_eq!(Command::SetQnh { pressure: 101_325.0 }.validate(), Ok(()));
+    assert_eq!(Command::SetQnh { pressure: 1013.25 }.validate(), Err(ValidationError::Qnh { pressure: 1013.25 }));
+    assert!(Command::SetQnh { pressure: f32::NAN }.validate().is_err());
+
+    assert_eq!(Command::SetFieldElevation { altitude: 250.0 }.validate(), Ok(()));
+    assert_eq!(Command::SetFieldElevation { altitude: -420.0 }.validate(), Ok(()));
+    assert_eq!(
+        Command::SetFieldElevation { altitude: 25_000.0 }.validate(),
+        Err(ValidationError::FieldElevation { altitude: 25_000.0 })
+    );
+}
+
+#[test]
+fn errors_have_messages() {
+    let message = wifi("field", "short").validate().unwrap_err().to_string();
+    assert_eq!(message, "Password must be empty or 8 to 63 characters long, not 5");
+    let message = Command::SetQnh { pressure: 50_000.0 }.validate().unwrap_err().to_string();
+    assert_eq!(message, "QNH must be 850 to 1100 hPa, not 500");
+}
@@ -0,0 +1,130 @@
+/// ISA sea level standard pressure, pascals.
+pub const STANDARD_PRESSURE: f32 = 101_325.0;
+/// Number of samples averaged by [`Altimeter::zero`] when no other value is given.
+pub const DEFAULT_ZERO_SAMPLES: u32 = 50;
+
+const ISA_SCALE_HEIGHT: f32 = 44_330.77;
+const ISA_EXPONENT: f32 = 0.190_263;
+
+/// Altitude of `pressure` above the level where pressure equals `reference`,
+/// using the ISA troposphere model.
+pub fn pressure_altitude(pressure: f32, reference: f32) -> f32 {
+    ISA_SCALE_HEIGHT * (1.0 - (pressure / reference).powf(ISA_EXPONENT))
+}
+
+/// Pressure at `altitude` above the level where pressure equals `reference`.
+pub fn altitude_pressure(altitude: f32, reference: f32) -> f32 {
+    reference * (1.0 - altitude / ISA_SCALE_HEIGHT).powf(1.0 / ISA_EXPONENT)
+}
+
+/// Sea level pressure that puts a station measuring `pressure` at `altitude`.
+pub fn sea_level_pressure(pressure: f32, altitude: f32) -> f32 {
+    pressure / (1.0 - altitude / ISA_SCALE_HEIGHT).powf(1.0 / ISA_EXPONENT)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AltitudeReading {
+    pub pressure: f32,
+    pub altitude_msl: f32,
+    pub altitude_agl: f32,
+}
+
+struct Zeroing {
+    remaining: u32,
+    sum: f64,
+    count: u32,
+}
+
+/// Converts pressure to altitude above sea level and above the launch site.
+///
+/// Ground level is set by averaging samples after [`Altimeter::zero`]. The sea
+/// level reference is either the QNH given with [`Altimeter::set_qnh`] or is
+/// derived from the ground pressure and a known field elevation.
+///
+/// Filtering should happen on the standard pressure altitude returned by
+/// [`Altimeter::update`], which does not jump when the references change; the
+/// filtered value is then converted with [`Altimeter::reading_at`].
+pub struct Altimeter {
+    qnh: f32,
+    field_elevation: Option<f32>,
+    ground_pressure: Option<f32>,
+    zeroing: Option<Zeroing>,
+}
+
+impl Default for Altimeter {
+    fn default() -> Self {
+        Self {
+            qnh: STANDARD_PRESSURE,
+            field_elevation: None,
+            ground_pressure: None,
+            zeroing: None,
+        }
+    }
+}
+
+impl Altimeter {
+    pub fn ground_pressure(&self) -> Option<f32> {
+        self.ground_pressure
+    }
+
+    pub fn is_zeroing(&self) -> bool {
+        self.zeroing.is_some()
+    }
+
+    /// Sea level pressure currently in use.
+    pub fn qnh(&self) -> f32 {
+        match (self.field_elevation, self.ground_pressure) {
+            (Some(elevation), Some(ground)) => sea_level_pressure(ground, elevation),
+            _ => self.qnh,
+        }
+    }
+
+    pub fn set_qnh(&mut self, qnh: f32) {
+        self.qnh = qnh;
+        self.field_elevation = None;
+    }
+
+    /// Takes effect once a ground reference is available.
+    pub fn set_field_elevation(&mut self, altitude: f32) {
+        self.field_elevation = Some(altitude);
+    }
+
+    /// Averages the next `samples` pressure readings into a new ground reference.
+    pub fn zero(&mut self, samples: u32) {
+        self.zeroing = Some(Zeroing { remaining: samples.max(1), sum: 0.0, count: 0 });
+    }
+
+    pub fn ground_altitude_msl(&self) -> f32 {
+        self.ground_pressure.map_or(0.0, |ground| pressure_altitude(ground, self.qnh()))
+    }
+
+    /// Feeds a raw sample to a zeroing in progress and returns its pressure
+    /// altitude in the standard atmosphere.
+    pub fn update(&mut self, pressure: f32) -> f32 {
+        if let Some(zeroing) = &mut self.zeroing {
+            zeroing.sum += pressure as f64;
+            zeroing.count += 1;
+            zeroing.remaining -= 1;
+            if zeroing.remaining == 0 {
+                self.ground_pressure = Some((zeroing.sum / zeroing.count as f64) as f32);
+                self.zeroing = None;
+            }
+        }
+
+        pressure_altitude(pressure, STANDARD_PRESSURE)
+    }
+
+    pub fn reading(&self, pressure: f32) -> AltitudeReading {
+        let altitude_msl = pressure_altitude(pressure, self.qnh());
+        AltitudeReading {
+            pressure,
+            altitude_msl,
+            altitude_agl: altitude_msl - self.ground_altitude_msl(),
+        }
+    }
+
+    /// Reading for a pressure altitude in the standard atmosphere.
+    pub fn reading_at(&self, standard_altitude: f32) -> AltitudeReading {
+        self.reading(altitude_pressure(standard_altitude, STANDARD_PRESSURE))
+    }
+}
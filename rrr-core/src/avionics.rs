@@ -0,0 +1,616 @@
+//! The firmware's sampling loops and command handler, generic over the
+//! [`crate::hal`] traits so the same code runs on the board and on the host.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::*;
+use rrr_api::{ApiError, BoardInfo, Command, RecoveryConfig, SampleInfo, Sensor, SensorHealth, ServoCalibration, State};
+use rrr_api::{ValidationError, WifiCredentials, API_VERSION, SERVO_CHANNELS};
+use rrr_log::{LogHeader, LogRecord};
+use thiserror::Error;
+use crate::altitude::{Altimeter, DEFAULT_ZERO_SAMPLES};
+use crate::estimator::{AltitudeEstimator, EstimatorConfig};
+use crate::flight::{FlightConfig, FlightStateMachine};
+use crate::hal::*;
+use crate::health::SensorSupervisor;
+use crate::logger::{FlightLogger, LogStorage};
+use crate::pyro::{PyroController, PyroError, CONTINUITY_THRESHOLD_VOLTS};
+use crate::recovery::RecoverySequencer;
+use crate::selftest::{self, SelfTest};
+use crate::servo::{ServoBank, ServoError};
+use crate::wifi::{self, WifiError};
+
+pub const BAROMETER_PERIOD_MS: u64 = 20;
+pub const BATTERY_PERIOD_MS: u64 = 1000;
+pub const CONTINUITY_PERIOD_MS: u64 = 1000;
+pub const PYRO_POLL_PERIOD_MS: u64 = 10;
+pub const LOG_PERIOD_MS: u64 = 20;
+pub const STALENESS_PERIOD_MS: u64 = 100;
+/// Sampling periods a sensor may miss before its section is flagged stale.
+pub const STALE_AFTER_PERIODS: u64 = 10;
+/// Time for the reply to a reset command to reach the client before the board shuts down.
+pub const RESET_DELAY_MS: u64 = 500;
+
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error(transparent)]
+    Invalid(#[from] ValidationError),
+    #[error(transparent)]
+    Pyro(#[from] PyroError),
+    #[error(transparent)]
+    Servo(#[from] ServoError),
+    #[error(transparent)]
+    Hal(#[from] HalError),
+    #[error(transparent)]
+    Wifi(#[from] WifiError),
+    #[error("Not allowed in flight")]
+    InFlight,
+}
+
+impl CommandError {
+    /// The error category reported to API clients.
+    pub fn api_error(&self) -> ApiError {
+        match self {
+            CommandError::Invalid(_) => ApiError::InvalidArgument,
+            CommandError::InFlight => ApiError::InFlight,
+            CommandError::Pyro(PyroError::NotArmed) => ApiError::NotArmed,
+            CommandError::Pyro(PyroError::InvalidChannel | PyroError::InvalidDuration) => ApiError::InvalidArgument,
+            CommandError::Pyro(PyroError::HardwareFault) => ApiError::HardwareFault,
+            CommandError::Servo(ServoError::InvalidChannel | ServoError::InvalidCalibration) => ApiError::InvalidArgument,
+            CommandError::Servo(ServoError::HardwareFault) => ApiError::HardwareFault,
+            CommandError::Wifi(_) => ApiError::InvalidArgument,
+            CommandError::Hal(HalError::StorageFailed) => ApiError::StorageError,
+            CommandError::Hal(HalError::InvalidChannel | HalError::InvalidImage) => ApiError::InvalidArgument,
+            CommandError::Hal(HalError::NoResponse | HalError::InvalidReading | HalError::OutputFailed) => ApiError::HardwareFault,
+        }
+    }
+}
+
+/// A reboot requested by a command, carried out by the platform once it has
+/// shut down its services, see [`Avionics::wait_for_reset`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResetRequest {
+    Restart,
+    /// The settings were erased, the board restarts with the defaults.
+    FactoryReset,
+}
+
+/// Everything the sampling loops and the command handler share. Cloning
+/// yields another handle to the same subsystems.
+pub struct Avionics<P: PyroOutput, S: ServoOutput, L: StatusLed, N: Settings> {
+    boot_time: Instant,
+    state: Arc<Mutex<State>>,
+    pyro: Arc<Mutex<PyroController<P>>>,
+    recovery: Arc<Mutex<RecoverySequencer>>,
+    altimeter: Arc<Mutex<Altimeter>>,
+    servos: Arc<Mutex<ServoBank<S>>>,
+    led: Arc<Mutex<L>>,
+    settings: Arc<Mutex<N>>,
+    reset: Arc<(Mutex<Option<ResetRequest>>, Condvar)>,
+}
+
+impl<P: PyroOutput, S: ServoOutput, L: StatusLed, N: Settings> Clone for Avionics<P, S, L, N> {
+    fn clone(&self) -> Self {
+        Self {
+            boot_time: self.boot_time,
+            state: self.state.clone(),
+            pyro: self.pyro.clone(),
+            recovery: self.recovery.clone(),
+            altimeter: self.altimeter.clone(),
+            servos: self.servos.clone(),
+            led: self.led.clone(),
+            settings: self.settings.clone(),
+            reset: self.reset.clone(),
+        }
+    }
+}
+
+impl<P: PyroOutput, S: ServoOutput, L: StatusLed, N: Settings> Avionics<P, S, L, N> {
+    /// Drives all outputs to their safe state, loads the stored configuration
+    /// and starts zeroing the altimeter. Settings that cannot be read fall back
+    /// to the defaults, so the board still boots and `ResetNvs` stays reachable.
+    pub fn new(pyro: Vec<P>, servos: [S; 2], led: L, mut settings: N) -> Result<Self, CommandError> {
+        let pyro = PyroController::new(pyro)?;
+        let recovery = RecoverySequencer::new(stored_or_default("recovery config", settings.recovery_config()));
+        info!("recovery config {:?}", recovery.config());
+        let calibrations = [
+            stored_or_default("servo 1 calibration", settings.servo_calibration(0)),
+            stored_or_default("servo 2 calibration", settings.servo_calibration(1)),
+        ];
+        let servos = ServoBank::new(servos, calibrations)?;
+        let mut altimeter = Altimeter::default();
+        altimeter.zero(DEFAULT_ZERO_SAMPLES);
+
+        let mut state = State {
+            pyro: pyro.state(),
+            recovery: recovery.state().clone(),
+            servo: servos.state(),
+            wifi_networks: wifi::ssids(&stored_or_default("Wi-Fi networks", settings.wifi_networks().map(Some))),
+            ..Default::default()
+        };
+        selftest::update_readiness(&mut state);
+
+        Ok(Self {
+            boot_time: Instant::now(),
+            state: Arc::new(Mutex::new(state)),
+            pyro: Arc::new(Mutex::new(pyro)),
+            recovery: Arc::new(Mutex::new(recovery)),
+            altimeter: Arc::new(Mutex::new(altimeter)),
+            servos: Arc::new(Mutex::new(servos)),
+            led: Arc::new(Mutex::new(led)),
+            settings: Arc::new(Mutex::new(settings)),
+            reset: Arc::default(),
+        })
+    }
+
+    pub fn state(&self) -> Arc<Mutex<State>> {
+        self.state.clone()
+    }
+
+    pub fn settings(&self) -> Arc<Mutex<N>> {
+        self.settings.clone()
+    }
+
+    /// The stored networks to join, none when they cannot be read so the
+    /// board opens its access point.
+    pub fn wifi_credentials(&self) -> Vec<WifiCredentials> {
+        stored_or_default("Wi-Fi networks", self.settings.lock().unwrap().wifi_networks().map(Some))
+    }
+
+    /// Milliseconds since the subsystems were created.
+    pub fn now_ms(&self) -> u64 {
+        self.boot_time.elapsed().as_millis() as u64
+    }
+
+    /// Describes the board for `GET /info`. All commands are handled and all
+    /// sensors are sampled, only the channel counts depend on the hardware.
+    pub fn board_info(&self, board: &str, firmware_version: &str, git_hash: Option<&str>) -> BoardInfo {
+        BoardInfo {
+            api_version: API_VERSION,
+            firmware_version: firmware_version.into(),
+            git_hash: git_hash.map(Into::into),
+            board: board.into(),
+            commands: Command::NAMES.iter().map(|&name| name.into()).collect(),
+            sensors: vec![Sensor::Barometer, Sensor::FuelGauge, Sensor::PyroContinuity],
+            pyro_channels: self.pyro.lock().unwrap().channels(),
+            servo_channels: 2,
+        }
+    }
+
+    pub fn set_status_led(&self, r: u8, g: u8, b: u8) -> Result<(), HalError> {
+        self.led.lock().unwrap().set_rgb(r, g, b)
+    }
+
+    pub fn barometer_sampler(&self) -> BarometerSampler<P> {
+        BarometerSampler {
+            estimator: AltitudeEstimator::new(EstimatorConfig::default()),
+            flight: FlightStateMachine::new(FlightConfig::default()),
+            state: self.state.clone(),
+            pyro: self.pyro.clone(),
+            recovery: self.recovery.clone(),
+            recovery_error: None,
+            altimeter: self.altimeter.clone(),
+        }
+    }
+
+    pub fn sample_battery<G: FuelGauge>(&self, gauge: &mut G) -> Result<(), HalError> {
+        let reading = gauge.read()?;
+        let time_ms = self.now_ms();
+        let mut state = self.state.lock().unwrap();
+        state.battery.soc = reading.soc;
+        state.battery.voltage = reading.voltage;
+        state.battery.charge_rate = reading.charge_rate;
+        state.samples.battery.record(time_ms);
+        Ok(())
+    }
+
+    /// Reads every channel, even if one of them fails; the first error is returned.
+    pub fn sample_continuity<C: ContinuityMonitor>(&self, monitor: &mut C) -> Result<(), HalError> {
+        let mut result = Ok(());
+        for channel in 1..=monitor.channels() {
+            match monitor.read_volts(channel) {
+                Ok(volts) => {
+                    if self.pyro.lock().unwrap().set_test_voltage(channel, volts).is_err() {
+                        result = result.and(Err(HalError::InvalidChannel));
+                    }
+                }
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+        let pyro_state = self.pyro.lock().unwrap().state();
+        let time_ms = self.now_ms();
+        let mut state = self.state.lock().unwrap();
+        state.pyro = pyro_state;
+        if result.is_ok() {
+            state.samples.pyro.record(time_ms);
+        }
+        result
+    }
+
+    pub fn report_health(&self, sensor: Sensor, health: &SensorHealth) {
+        self.state.lock().unwrap().health.sensor_mut(sensor).clone_from(health);
+    }
+
+    /// Flags the sensor sections that missed [`STALE_AFTER_PERIODS`] updates,
+    /// e.g. because their sampling thread died.
+    pub fn check_staleness(&self, now_ms: u64) {
+        let is_stale = |sample: &SampleInfo, period_ms: u64| {
+            now_ms.saturating_sub(sample.time_ms.unwrap_or(0)) > period_ms * STALE_AFTER_PERIODS
+        };
+        let mut state = self.state.lock().unwrap();
+        let samples = &mut state.samples;
+        samples.battery.stale = is_stale(&samples.battery, BATTERY_PERIOD_MS);
+        samples.barometer.stale = is_stale(&samples.barometer, BAROMETER_PERIOD_MS);
+        samples.pyro.stale = is_stale(&samples.pyro, CONTINUITY_PERIOD_MS);
+        state.time_ms = now_ms;
+        selftest::update_readiness(&mut state);
+    }
+
+    /// Publishes the self-test report, from then on readiness follows the sensor health.
+    pub fn finish_self_test(&self, test: SelfTest) {
+        let report = test.finish(self.now_ms());
+        let mut state = self.state.lock().unwrap();
+        state.self_test = report;
+        selftest::update_readiness(&mut state);
+        if state.self_test.ready_to_fly {
+            info!("Ready to fly");
+        } else {
+            warn!("Not ready to fly: {}", state.self_test.blocking.join("; "));
+        }
+    }
+
+    /// Ends ignition pulses that are due.
+    pub fn poll_pyro(&self, time_ms: u64) {
+        if let Err(e) = self.with_pyro(|pyro| pyro.poll(time_ms)) {
+            error!("Pyro release failed: {}", e);
+        }
+    }
+
+    /// Writes the current state to the flight log. `header` carries the
+    /// fields the caller knows (firmware version, start time) and is
+    /// completed from the state; the logger is told when it changes.
+    pub fn log_sample<St: LogStorage>(&self, logger: &Mutex<FlightLogger<St>>, header: &mut LogHeader, time_ms: u64) {
+        let state = self.state.lock().unwrap().clone();
+        let record = LogRecord {
+            phase: state.flight.phase,
+            time_ms: time_ms as u32,
+            altitude_agl: state.barometer.altitude_agl,
+            vertical_velocity: state.barometer.vertical_velocity,
+            pressure: state.barometer.pressure,
+            battery_voltage: state.battery.voltage,
+            temperature: state.barometer.temperature,
+            pyro_armed: state.pyro.armed,
+            pyro_fire: [state.pyro.channel1.fire, state.pyro.channel2.fire],
+            pyro_continuity: [
+                state.pyro.channel1.test_voltage > CONTINUITY_THRESHOLD_VOLTS,
+                state.pyro.channel2.test_voltage > CONTINUITY_THRESHOLD_VOLTS,
+            ],
+            servo_position: [state.servo.servo1_position, state.servo.servo2_position],
+            ..Default::default()
+        };
+
+        let log_state = {
+            let mut logger = logger.lock().unwrap();
+            let current_header = log_header(&state, header);
+            if current_header != *header {
+                *header = current_header;
+                logger.set_header(header.clone());
+            }
+            if let Err(e) = logger.log(record) {
+                error!("Logging failed: {}", e);
+            }
+            logger.state()
+        };
+        self.state.lock().unwrap().log = log_state;
+    }
+
+    pub fn handle_command(&self, command: &Command) -> Result<(), CommandError> {
+        command.validate()?;
+        match command {
+            Command::Reset { .. } => {
+                self.check_on_ground()?;
+                info!("reset requested");
+                self.request_reset(ResetRequest::Restart);
+            }
+            Command::ResetNvs { .. } => {
+                self.check_on_ground()?;
+                info!("erasing settings");
+                self.restore_defaults()?;
+                self.request_reset(ResetRequest::FactoryReset);
+            }
+            Command::AddWifiNetwork { ssid, password } => {
+                info!("storing wifi network {}", ssid);
+                let credentials = WifiCredentials { ssid: ssid.clone(), password: password.clone() };
+                self.edit_wifi_networks(|networks| wifi::add_network(networks, credentials))?;
+            }
+            Command::RemoveWifiNetwork { ssid } => {
+                info!("removing wifi network {}", ssid);
+                self.edit_wifi_networks(|networks| wifi::remove_network(networks, ssid))?;
+            }
+            Command::MoveWifiNetwork { ssid, priority } => {
+                info!("moving wifi network {} to priority {}", ssid, priority);
+                self.edit_wifi_networks(|networks| wifi::move_network(networks, ssid, *priority as usize))?;
+            }
+            Command::ArmPyro => {
+                info!("arming pyro");
+                self.with_pyro(|pyro| {
+                    pyro.arm();
+                    Ok(())
+                })?;
+            }
+            Command::DisarmPyro => {
+                info!("disarming pyro");
+                self.with_pyro(|pyro| pyro.disarm())?;
+            }
+            Command::FirePyro { channel, duration_ms } => {
+                info!("firing pyro channel {} for {} ms", channel, duration_ms);
+                let time_ms = self.now_ms();
+                self.with_pyro(|pyro| pyro.fire(*channel, *duration_ms, time_ms))?;
+            }
+            Command::SetRecoveryConfig { config } => {
+                info!("setting recovery config {:?}", config);
+                self.settings.lock().unwrap().set_recovery_config(config)?;
+                let mut recovery = self.recovery.lock().unwrap();
+                recovery.set_config(config.clone());
+                self.state.lock().unwrap().recovery = recovery.state().clone();
+            }
+            Command::ZeroAltitude => {
+                info!("zeroing altitude");
+                self.altimeter.lock().unwrap().zero(DEFAULT_ZERO_SAMPLES);
+            }
+            Command::SetQnh { pressure } => {
+                info!("setting QNH {}", pressure);
+                self.altimeter.lock().unwrap().set_qnh(*pressure);
+            }
+            Command::SetFieldElevation { altitude } => {
+                info!("setting field elevation {}", altitude);
+                self.altimeter.lock().unwrap().set_field_elevation(*altitude);
+            }
+            Command::SetLedColor { r, g, b } => self.set_status_led(*r, *g, *b)?,
+            Command::SetPwmDutyCycle { duty_1, duty_2 } => {
+                info!("setting pwm");
+                self.set_servo_positions([duty_1.map(|d| d * 2.0 - 1.0), duty_2.map(|d| d * 2.0 - 1.0)])?;
+            }
+            Command::SetServoPosition { position_1, position_2 } => {
+                info!("setting servo position");
+                self.set_servo_positions([*position_1, *position_2])?;
+            }
+            Command::SetServoCalibration { channel, calibration } => {
+                info!("setting servo {} calibration {:?}", channel, calibration);
+                self.settings.lock().unwrap().set_servo_calibration(*channel as usize - 1, calibration)?;
+                let mut servos = self.servos.lock().unwrap();
+                servos.set_calibration(*channel, calibration.clone())?;
+                self.publish_servos(&servos);
+            }
+        }
+        Ok(())
+    }
+
+    /// The reset requested by a command, if any.
+    pub fn reset_request(&self) -> Option<ResetRequest> {
+        *self.reset.0.lock().unwrap()
+    }
+
+    /// Blocks until a command requests a reset. The platform then waits
+    /// [`RESET_DELAY_MS`] for the reply to go out, stops its services,
+    /// flushes the log and reboots.
+    pub fn wait_for_reset(&self) -> ResetRequest {
+        let (request, requested) = &*self.reset;
+        let mut request = request.lock().unwrap();
+        loop {
+            if let Some(request) = *request {
+                return request;
+            }
+            request = requested.wait(request).unwrap();
+        }
+    }
+
+    fn request_reset(&self, request: ResetRequest) {
+        let (pending, requested) = &*self.reset;
+        *pending.lock().unwrap() = Some(request);
+        requested.notify_all();
+    }
+
+    /// Resets and erases are refused between launch and landing.
+    fn check_on_ground(&self) -> Result<(), CommandError> {
+        if self.state.lock().unwrap().flight.phase.is_flying() {
+            return Err(CommandError::InFlight);
+        }
+        Ok(())
+    }
+
+    /// Erases the stored settings and applies the defaults to the running subsystems.
+    fn restore_defaults(&self) -> Result<(), CommandError> {
+        self.settings.lock().unwrap().erase()?;
+        self.state.lock().unwrap().wifi_networks.clear();
+        {
+            let mut recovery = self.recovery.lock().unwrap();
+            recovery.set_config(RecoveryConfig::default());
+            self.state.lock().unwrap().recovery = recovery.state().clone();
+        }
+        let mut servos = self.servos.lock().unwrap();
+        for channel in 1..=SERVO_CHANNELS {
+            servos.set_calibration(channel, ServoCalibration::default())?;
+        }
+        self.publish_servos(&servos);
+        Ok(())
+    }
+
+    /// Changes the stored networks with `f` and publishes the result. The
+    /// board joins them after the next reset.
+    fn edit_wifi_networks(&self, f: impl FnOnce(&mut Vec<WifiCredentials>) -> Result<(), WifiError>) -> Result<(), CommandError> {
+        let mut settings = self.settings.lock().unwrap();
+        let mut networks = settings.wifi_networks()?;
+        f(&mut networks)?;
+        settings.set_wifi_networks(&networks)?;
+        self.state.lock().unwrap().wifi_networks = wifi::ssids(&networks);
+        Ok(())
+    }
+
+    /// Runs `f` on the pyro controller and publishes the resulting state, also on failure.
+    fn with_pyro(&self, f: impl FnOnce(&mut PyroController<P>) -> Result<(), PyroError>) -> Result<(), PyroError> {
+        let (result, pyro_state) = {
+            let mut pyro = self.pyro.lock().unwrap();
+            (f(&mut pyro), pyro.state())
+        };
+        self.state.lock().unwrap().pyro = pyro_state;
+        result
+    }
+
+    fn set_servo_positions(&self, positions: [Option<f32>; 2]) -> Result<(), ServoError> {
+        let mut servos = self.servos.lock().unwrap();
+        let result = servos.set_positions(positions);
+        self.publish_servos(&servos);
+        result
+    }
+
+    fn publish_servos(&self, servos: &ServoBank<S>) {
+        let time_ms = self.now_ms();
+        let mut state = self.state.lock().unwrap();
+        state.servo = servos.state();
+        state.samples.servo.record(time_ms);
+    }
+}
+
+impl<P, S, L, N> Avionics<P, S, L, N>
+where
+    P: PyroOutput + Send + 'static,
+    S: ServoOutput + Send + 'static,
+    L: StatusLed + Send + 'static,
+    N: Settings + Send + 'static,
+{
+    pub fn spawn_barometer<B: Barometer + Send + 'static>(&self, barometer: B) {
+        let mut sampler = self.barometer_sampler();
+        self.spawn_supervised(Sensor::Barometer, BAROMETER_PERIOD_MS, barometer, B::reinit, move |_, barometer, time_ms| {
+            sampler.sample(barometer, time_ms)
+        });
+    }
+
+    pub fn spawn_fuel_gauge<G: FuelGauge + Send + 'static>(&self, gauge: G) {
+        self.spawn_supervised(Sensor::FuelGauge, BATTERY_PERIOD_MS, gauge, G::reinit, |avionics, gauge, _| {
+            avionics.sample_battery(gauge)
+        });
+    }
+
+    pub fn spawn_continuity<C: ContinuityMonitor + Send + 'static>(&self, monitor: C) {
+        self.spawn_supervised(Sensor::PyroContinuity, CONTINUITY_PERIOD_MS, monitor, C::reinit, |avionics, monitor, _| {
+            avionics.sample_continuity(monitor)
+        });
+    }
+
+    /// Samples `device` every `period_ms`, retrying and re-initialising it on
+    /// errors instead of giving up, and publishes its health.
+    fn spawn_supervised<D, F>(&self, sensor: Sensor, period_ms: u64, mut device: D, reinit: fn(&mut D) -> Result<(), HalError>, mut sample: F)
+        where D: Send + 'static,
+              F: FnMut(&Self, &mut D, u64) -> Result<(), HalError> + Send + 'static,
+    {
+        let avionics = self.clone();
+        let mut supervisor = SensorSupervisor::new(sensor);
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_millis(period_ms));
+            let now_ms = avionics.now_ms();
+            if supervisor.run(&mut device, now_ms, reinit, |device| sample(&avionics, device, now_ms)).is_some() {
+                avionics.report_health(sensor, supervisor.health());
+            }
+        });
+    }
+
+    pub fn spawn_pyro_poll(&self) {
+        let avionics = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_millis(PYRO_POLL_PERIOD_MS));
+            avionics.poll_pyro(avionics.now_ms());
+        });
+    }
+
+    pub fn spawn_staleness_check(&self) {
+        let avionics = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_millis(STALENESS_PERIOD_MS));
+            avionics.check_staleness(avionics.now_ms());
+        });
+    }
+
+    pub fn spawn_logger<St: LogStorage + Send + 'static>(&self, logger: Arc<Mutex<FlightLogger<St>>>, mut header: LogHeader) {
+        let avionics = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_millis(LOG_PERIOD_MS));
+            avionics.log_sample(&logger, &mut header, avionics.now_ms());
+        });
+    }
+}
+
+/// Barometer processing: altitude filtering, flight phase detection and
+/// recovery deployment, one sample at a time.
+pub struct BarometerSampler<P: PyroOutput> {
+    estimator: AltitudeEstimator,
+    flight: FlightStateMachine,
+    state: Arc<Mutex<State>>,
+    pyro: Arc<Mutex<PyroController<P>>>,
+    recovery: Arc<Mutex<RecoverySequencer>>,
+    /// Last deployment failure, logged once instead of on every sample while
+    /// e.g. the channels stay disarmed.
+    recovery_error: Option<PyroError>,
+    altimeter: Arc<Mutex<Altimeter>>,
+}
+
+impl<P: PyroOutput> BarometerSampler<P> {
+    pub fn sample<B: Barometer>(&mut self, barometer: &mut B, time_ms: u64) -> Result<(), HalError> {
+        let reading = barometer.read()?;
+        let (estimate, altitude, ground_pressure, qnh) = {
+            let mut altimeter = self.altimeter.lock().unwrap();
+            let estimate = self.estimator.update(time_ms, altimeter.update(reading.pressure));
+            (estimate, altimeter.reading_at(estimate.altitude), altimeter.ground_pressure(), altimeter.qnh())
+        };
+
+        if let Some(phase) = self.flight.update(time_ms, &estimate) {
+            info!("Flight phase: {:?}", phase);
+        }
+
+        let recovery_state = {
+            let mut recovery = self.recovery.lock().unwrap();
+            let altitude_agl = estimate.altitude - self.flight.state().ground_altitude;
+            let error = recovery.update(time_ms, self.flight.state(), altitude_agl, &mut self.pyro.lock().unwrap()).err();
+            if error != self.recovery_error {
+                if let Some(e) = &error {
+                    error!("Recovery deployment failed: {}", e);
+                }
+                self.recovery_error = error;
+            }
+            recovery.state().clone()
+        };
+
+        let mut state = self.state.lock().unwrap();
+        state.barometer.temperature = reading.temperature;
+        state.barometer.pressure = reading.pressure;
+        state.barometer.altitude_msl = altitude.altitude_msl;
+        state.barometer.altitude_agl = altitude.altitude_agl;
+        state.barometer.ground_pressure = ground_pressure;
+        state.barometer.qnh = qnh;
+        state.barometer.vertical_velocity = estimate.vertical_velocity;
+        state.barometer.vertical_acceleration = estimate.vertical_acceleration;
+        state.flight = self.flight.state().clone();
+        state.recovery = recovery_state;
+        state.samples.barometer.record(time_ms);
+        Ok(())
+    }
+}
+
+/// The stored value, or the default when none is stored or reading it fails.
+fn stored_or_default<T: Default>(name: &str, stored: Result<Option<T>, HalError>) -> T {
+    stored.unwrap_or_else(|e| {
+        error!("Reading the stored {} failed, using the defaults: {}", name, e);
+        None
+    }).unwrap_or_default()
+}
+
+/// Completes `template` with the configuration in `state` that affects the flight.
+pub fn log_header(state: &State, template: &LogHeader) -> LogHeader {
+    LogHeader {
+        qnh: state.barometer.qnh,
+        ground_pressure: state.barometer.ground_pressure,
+        recovery: state.recovery.config.clone(),
+        ..template.clone()
+    }
+}
@@ -0,0 +1,115 @@
+use std::array::from_fn;
+
+/// Noise parameters of [`AltitudeEstimator`].
+#[derive(Clone, Debug)]
+pub struct EstimatorConfig {
+    /// Standard deviation of the barometric altitude measurement, metres.
+    pub measurement_noise: f32,
+    /// Spectral density of the jerk driving the constant-acceleration model, (m/s³)²/Hz.
+    /// Larger values track motor ignition and burnout faster at the cost of noisier output.
+    pub process_noise: f32,
+}
+
+impl Default for EstimatorConfig {
+    fn default() -> Self {
+        Self {
+            measurement_noise: 0.5,
+            process_noise: 500.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Estimate {
+    pub altitude: f32,
+    pub vertical_velocity: f32,
+    pub vertical_acceleration: f32,
+}
+
+/// Kalman filter estimating altitude, vertical velocity and vertical acceleration
+/// from barometric altitude samples with a constant-acceleration model.
+pub struct AltitudeEstimator {
+    config: EstimatorConfig,
+    x: [f32; 3],
+    p: [[f32; 3]; 3],
+    last_time_ms: Option<u64>,
+}
+
+impl AltitudeEstimator {
+    pub fn new(config: EstimatorConfig) -> Self {
+        Self {
+            config,
+            x: [0.0; 3],
+            p: [[0.0; 3]; 3],
+            last_time_ms: None,
+        }
+    }
+
+    pub fn estimate(&self) -> Estimate {
+        Estimate {
+            altitude: self.x[0],
+            vertical_velocity: self.x[1],
+            vertical_acceleration: self.x[2],
+        }
+    }
+
+    /// Forgets the current estimate; the next sample re-initialises the filter.
+    pub fn reset(&mut self) {
+        self.last_time_ms = None;
+    }
+
+    /// Processes one altitude sample taken at `time_ms` since boot.
+    pub fn update(&mut self, time_ms: u64, altitude: f32) -> Estimate {
+        let r = self.config.measurement_noise * self.config.measurement_noise;
+
+        let last_time_ms = match self.last_time_ms {
+            None => {
+                self.x = [altitude, 0.0, 0.0];
+                self.p = [[r, 0.0, 0.0], [0.0, 100.0, 0.0], [0.0, 0.0, 100.0]];
+                self.last_time_ms = Some(time_ms);
+                return self.estimate();
+            }
+            Some(t) => t,
+        };
+        self.last_time_ms = Some(time_ms);
+
+        if time_ms > last_time_ms {
+            self.predict((time_ms - last_time_ms) as f32 / 1000.0);
+        }
+
+        // H = [1, 0, 0]
+        let s = self.p[0][0] + r;
+        let k = [self.p[0][0] / s, self.p[1][0] / s, self.p[2][0] / s];
+        let innovation = altitude - self.x[0];
+        let p = self.p;
+        self.x = from_fn(|i| self.x[i] + k[i] * innovation);
+        self.p = from_fn(|i| from_fn(|j| p[i][j] - k[i] * p[0][j]));
+
+        self.estimate()
+    }
+
+    fn predict(&mut self, dt: f32) {
+        let f = [
+            [1.0, dt, dt * dt / 2.0],
+            [0.0, 1.0, dt],
+            [0.0, 0.0, 1.0],
+        ];
+
+        let x = self.x;
+        self.x = from_fn(|i| (0..3).map(|j| f[i][j] * x[j]).sum());
+
+        let p = self.p;
+        let fp: [[f32; 3]; 3] = from_fn(|i| from_fn(|j| (0..3).map(|k| f[i][k] * p[k][j]).sum()));
+
+        let q = self.config.process_noise;
+        let (dt2, dt3) = (dt * dt, dt * dt * dt);
+        let (dt4, dt5) = (dt3 * dt, dt3 * dt2);
+        let noise = [
+            [dt5 / 20.0, dt4 / 8.0, dt3 / 6.0],
+            [dt4 / 8.0, dt3 / 3.0, dt2 / 2.0],
+            [dt3 / 6.0, dt2 / 2.0, dt],
+        ];
+
+        self.p = from_fn(|i| from_fn(|j| (0..3).map(|k| fp[i][k] * f[j][k]).sum::<f32>() + q * noise[i][j]));
+    }
+}
@@ -0,0 +1,184 @@
+use rrr_api::{FlightPhase, FlightState};
+use crate::estimator::Estimate;
+
+/// Detection thresholds for [`FlightStateMachine`]. Altitudes are metres above ground level.
+#[derive(Clone, Debug)]
+pub struct FlightConfig {
+    /// Number of samples averaged to establish the ground altitude before arming.
+    pub ground_samples: u32,
+    /// Gain used to follow slow barometric drift while waiting on the pad.
+    pub ground_tracking_gain: f32,
+    /// Altitude above ground that counts as liftoff.
+    pub launch_altitude: f32,
+    /// Drop from the peak vertical velocity (m/s) that counts as motor burnout.
+    pub burnout_velocity_drop: f32,
+    /// Drop below the highest altitude that counts as apogee.
+    pub apogee_altitude_drop: f32,
+    /// Drop below the highest altitude that counts as descent.
+    pub descent_altitude_drop: f32,
+    /// Altitude band the rocket has to stay within to be considered stationary.
+    pub landing_altitude_window: f32,
+    /// How long the rocket has to stay stationary to be considered landed.
+    pub landing_time_ms: u64,
+    /// Consecutive samples a condition has to hold before a transition happens.
+    pub confirm_samples: u32,
+}
+
+impl Default for FlightConfig {
+    fn default() -> Self {
+        Self {
+            ground_samples: 50,
+            ground_tracking_gain: 0.001,
+            launch_altitude: 15.0,
+            burnout_velocity_drop: 5.0,
+            apogee_altitude_drop: 2.0,
+            descent_altitude_drop: 10.0,
+            landing_altitude_window: 2.0,
+            landing_time_ms: 5000,
+            confirm_samples: 5,
+        }
+    }
+}
+
+/// Barometer driven flight phase detector.
+///
+/// Feed it every [`AltitudeEstimator`](crate::estimator::AltitudeEstimator)
+/// output with [`FlightStateMachine::update`]; the first
+/// `ground_samples` samples establish the ground reference, after which the
+/// machine arms itself and waits for liftoff.
+pub struct FlightStateMachine {
+    config: FlightConfig,
+    state: FlightState,
+    ground_sum: f32,
+    ground_count: u32,
+    velocity: f32,
+    max_velocity: f32,
+    confirm_count: u32,
+    stationary_since: Option<(u64, f32)>,
+}
+
+impl FlightStateMachine {
+    pub fn new(config: FlightConfig) -> Self {
+        Self {
+            config,
+            state: FlightState::default(),
+            ground_sum: 0.0,
+            ground_count: 0,
+            velocity: 0.0,
+            max_velocity: 0.0,
+            confirm_count: 0,
+            stationary_since: None,
+        }
+    }
+
+    pub fn state(&self) -> &FlightState {
+        &self.state
+    }
+
+    pub fn phase(&self) -> FlightPhase {
+        self.state.phase
+    }
+
+    pub fn vertical_velocity(&self) -> f32 {
+        self.velocity
+    }
+
+    /// Drops all flight data and starts establishing the ground reference again.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+
+    /// Processes one altitude (metres, any datum) and vertical velocity estimate
+    /// taken at `time_ms` since boot. Returns the new phase if a transition happened.
+    pub fn update(&mut self, time_ms: u64, estimate: &Estimate) -> Option<FlightPhase> {
+        let altitude = estimate.altitude;
+        self.velocity = estimate.vertical_velocity;
+
+        let agl = altitude - self.state.ground_altitude;
+
+        match self.state.phase {
+            FlightPhase::Idle => {
+                self.ground_sum += altitude;
+                self.ground_count += 1;
+                if self.ground_count >= self.config.ground_samples {
+                    self.state.ground_altitude = self.ground_sum / self.ground_count as f32;
+                    return self.transition(FlightPhase::Armed, time_ms);
+                }
+            }
+            FlightPhase::Armed => {
+                if self.confirm(agl > self.config.launch_altitude) {
+                    return self.transition(FlightPhase::Boost, time_ms);
+                }
+                if self.confirm_count == 0 {
+                    self.state.ground_altitude += agl * self.config.ground_tracking_gain;
+                }
+            }
+            FlightPhase::Boost => {
+                self.track_maximums(agl);
+                let burnout = self.velocity < self.max_velocity - self.config.burnout_velocity_drop;
+                if self.confirm(burnout) {
+                    return self.transition(FlightPhase::Coast, time_ms);
+                }
+            }
+            FlightPhase::Coast => {
+                self.track_maximums(agl);
+                let apogee = self.velocity < 0.0
+                    || agl < self.state.max_altitude - self.config.apogee_altitude_drop;
+                if self.confirm(apogee) {
+                    return self.transition(FlightPhase::Apogee, time_ms);
+                }
+            }
+            FlightPhase::Apogee => {
+                self.track_maximums(agl);
+                let descent = agl < self.state.max_altitude - self.config.descent_altitude_drop;
+                if self.confirm(descent) {
+                    return self.transition(FlightPhase::Descent, time_ms);
+                }
+            }
+            FlightPhase::Descent => {
+                match self.stationary_since {
+                    Some((since_ms, reference)) if (agl - reference).abs() < self.config.landing_altitude_window => {
+                        if time_ms - since_ms >= self.config.landing_time_ms {
+                            return self.transition(FlightPhase::Landed, time_ms);
+                        }
+                    }
+                    _ => self.stationary_since = Some((time_ms, agl)),
+                }
+            }
+            FlightPhase::Landed => {}
+        }
+
+        None
+    }
+
+    fn track_maximums(&mut self, agl: f32) {
+        self.state.max_altitude = self.state.max_altitude.max(agl);
+        self.max_velocity = self.max_velocity.max(self.velocity);
+    }
+
+    fn confirm(&mut self, condition: bool) -> bool {
+        if condition {
+            self.confirm_count += 1;
+            self.confirm_count >= self.config.confirm_samples
+        } else {
+            self.confirm_count = 0;
+            false
+        }
+    }
+
+    fn transition(&mut self, phase: FlightPhase, time_ms: u64) -> Option<FlightPhase> {
+        self.confirm_count = 0;
+        self.state.phase = phase;
+        let timestamp = match phase {
+            FlightPhase::Idle => return Some(phase),
+            FlightPhase::Armed => &mut self.state.armed_time_ms,
+            FlightPhase::Boost => &mut self.state.launch_time_ms,
+            FlightPhase::Coast => &mut self.state.burnout_time_ms,
+            FlightPhase::Apogee => &mut self.state.apogee_time_ms,
+            FlightPhase::Descent => &mut self.state.descent_time_ms,
+            FlightPhase::Landed => &mut self.state.landing_time_ms,
+        };
+        *timestamp = Some(time_ms);
+        Some(phase)
+    }
+}
@@ -0,0 +1,119 @@
+//! Interfaces to the board's sensors and actuators. The firmware implements
+//! them on top of the ESP-IDF drivers, [`crate::mock`] provides host versions.
+
+use rrr_api::{FirmwareSlots, RecoveryConfig, ServoCalibration, WifiCredentials, WifiNetwork};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum HalError {
+    #[error("Device did not respond")]
+    NoResponse,
+    #[error("Device returned an invalid reading")]
+    InvalidReading,
+    #[error("Output could not be driven")]
+    OutputFailed,
+    #[error("Channel does not exist")]
+    InvalidChannel,
+    #[error("Settings storage failed")]
+    StorageFailed,
+    #[error("Firmware image was rejected")]
+    InvalidImage,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct BarometerReading {
+    /// Pascals.
+    pub pressure: f32,
+    /// Degrees Celsius.
+    pub temperature: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct BatteryReading {
+    /// State of charge, percent.
+    pub soc: f32,
+    pub voltage: f32,
+    /// Percent per hour, negative while discharging.
+    pub charge_rate: f32,
+}
+
+pub trait Barometer {
+    fn read(&mut self) -> Result<BarometerReading, HalError>;
+    /// Brings the device back into a known state after repeated failures.
+    fn reinit(&mut self) -> Result<(), HalError> {
+        Ok(())
+    }
+}
+
+pub trait FuelGauge {
+    fn read(&mut self) -> Result<BatteryReading, HalError>;
+    /// Brings the device back into a known state after repeated failures.
+    fn reinit(&mut self) -> Result<(), HalError> {
+        Ok(())
+    }
+}
+
+/// Voltage across the igniters, used to check that they are connected.
+pub trait ContinuityMonitor {
+    /// Number of monitored pyro channels, numbered from 1.
+    fn channels(&self) -> u8;
+    fn read_volts(&mut self, channel: u8) -> Result<f32, HalError>;
+    /// Brings the device back into a known state after repeated failures.
+    fn reinit(&mut self) -> Result<(), HalError> {
+        Ok(())
+    }
+}
+
+/// PWM output for one servo running at the 50 Hz servo frame rate.
+pub trait ServoOutput {
+    fn max_duty(&self) -> u32;
+    fn set_duty(&mut self, duty: u32) -> Result<(), HalError>;
+}
+
+pub trait StatusLed {
+    fn set_rgb(&mut self, r: u8, g: u8, b: u8) -> Result<(), HalError>;
+}
+
+/// Output stage driving one igniter.
+pub trait PyroOutput {
+    fn set_firing(&mut self, firing: bool) -> Result<(), HalError>;
+}
+
+/// Persistent configuration, kept in NVS on the board.
+pub trait Settings {
+    /// Networks to join, in the order they are tried.
+    fn wifi_networks(&mut self) -> Result<Vec<WifiCredentials>, HalError>;
+    fn set_wifi_networks(&mut self, networks: &[WifiCredentials]) -> Result<(), HalError>;
+    fn recovery_config(&mut self) -> Result<Option<RecoveryConfig>, HalError>;
+    fn set_recovery_config(&mut self, config: &RecoveryConfig) -> Result<(), HalError>;
+    /// Calibration of the servo at `index`, counted from 0.
+    fn servo_calibration(&mut self, index: usize) -> Result<Option<ServoCalibration>, HalError>;
+    fn set_servo_calibration(&mut self, index: usize, calibration: &ServoCalibration) -> Result<(), HalError>;
+    /// Removes everything stored, the defaults apply from then on.
+    fn erase(&mut self) -> Result<(), HalError>;
+}
+
+/// The firmware partition updates are written to, the one not running.
+pub trait FirmwareSlot {
+    /// Largest image the partition holds, bytes.
+    fn capacity(&self) -> u32;
+    /// Starts writing a new image of `size` bytes, discarding an unfinished one.
+    fn begin(&mut self, size: u32) -> Result<(), HalError>;
+    fn write(&mut self, data: &[u8]) -> Result<(), HalError>;
+    /// Checks the written image and boots it after the next reset.
+    fn complete(&mut self) -> Result<(), HalError>;
+    /// Drops an unfinished image.
+    fn abort(&mut self);
+    /// The running image and the one in the other partition.
+    fn slots(&self) -> Result<FirmwareSlots, HalError>;
+    /// Keeps booting the running image, the bootloader no longer rolls it back.
+    fn mark_valid(&mut self) -> Result<(), HalError>;
+    /// Marks the running image invalid and restarts into the previous one.
+    fn roll_back(&mut self) -> Result<(), HalError>;
+}
+
+/// The radio's station interface, which scans while the access point stays up.
+pub trait WifiScanner {
+    /// Networks in range, in no particular order.
+    fn scan(&mut self) -> Result<Vec<WifiNetwork>, HalError>;
+}
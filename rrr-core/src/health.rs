@@ -0,0 +1,107 @@
+//! Error accounting for the sensor loops. A [`SensorSupervisor`] decides when a
+//! loop reads, when it re-initialises the device first and when it backs off,
+//! and keeps the [`SensorHealth`] reported in the state.
+
+use log::*;
+use rrr_api::{Sensor, SensorHealth, SensorStatus};
+use crate::hal::HalError;
+
+/// Consecutive failed reads after which a sensor is considered failed.
+pub const FAILED_AFTER_ERRORS: u32 = 5;
+/// Delay before the first re-initialisation, doubled after every failed one.
+pub const INITIAL_BACKOFF_MS: u64 = 100;
+pub const MAX_BACKOFF_MS: u64 = 10_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Attempt {
+    /// Backing off, do not touch the device.
+    Wait,
+    Read,
+    /// Re-initialise the device, then read.
+    Reinit,
+}
+
+pub struct SensorSupervisor {
+    sensor: Sensor,
+    health: SensorHealth,
+    backoff_ms: u64,
+    next_attempt_ms: u64,
+}
+
+impl SensorSupervisor {
+    pub fn new(sensor: Sensor) -> Self {
+        Self { sensor, health: SensorHealth::default(), backoff_ms: INITIAL_BACKOFF_MS, next_attempt_ms: 0 }
+    }
+
+    pub fn health(&self) -> &SensorHealth {
+        &self.health
+    }
+
+    /// What the sampling loop should do at `now_ms`.
+    fn attempt(&mut self, now_ms: u64) -> Attempt {
+        match self.health.status {
+            SensorStatus::Ok | SensorStatus::Degraded => Attempt::Read,
+            SensorStatus::Failed if now_ms < self.next_attempt_ms => Attempt::Wait,
+            SensorStatus::Failed => {
+                self.health.reinits += 1;
+                Attempt::Reinit
+            }
+        }
+    }
+
+    /// Accounts for the outcome of an attempt and returns the new status if it changed.
+    fn record(&mut self, result: Result<(), &HalError>, now_ms: u64) -> Option<SensorStatus> {
+        let previous = self.health.status;
+        match result {
+            Ok(()) => {
+                self.health.status = SensorStatus::Ok;
+                self.health.consecutive_errors = 0;
+                self.backoff_ms = INITIAL_BACKOFF_MS;
+            }
+            Err(e) => {
+                self.health.consecutive_errors += 1;
+                self.health.total_errors += 1;
+                self.health.last_error = Some(e.to_string());
+                self.health.last_error_ms = Some(now_ms);
+                if previous == SensorStatus::Failed {
+                    self.backoff_ms = (self.backoff_ms * 2).min(MAX_BACKOFF_MS);
+                }
+                self.health.status = if self.health.consecutive_errors >= FAILED_AFTER_ERRORS {
+                    self.next_attempt_ms = now_ms + self.backoff_ms;
+                    SensorStatus::Failed
+                } else {
+                    SensorStatus::Degraded
+                };
+            }
+        }
+        (self.health.status != previous).then_some(self.health.status)
+    }
+
+    /// One pass of a sampling loop: samples `device`, re-initialising it first
+    /// when due. Returns `None` while backing off.
+    pub fn run<D>(
+        &mut self,
+        device: &mut D,
+        now_ms: u64,
+        reinit: impl FnOnce(&mut D) -> Result<(), HalError>,
+        sample: impl FnOnce(&mut D) -> Result<(), HalError>,
+    ) -> Option<Result<(), HalError>> {
+        let result = match self.attempt(now_ms) {
+            Attempt::Wait => return None,
+            Attempt::Read => sample(device),
+            Attempt::Reinit => {
+                info!("Re-initialising {:?}", self.sensor);
+                reinit(device).and_then(|()| sample(device))
+            }
+        };
+        if let Err(e) = &result {
+            error!("{:?} read failed: {}", self.sensor, e);
+        }
+        match self.record(result.as_ref().map(|_| ()), now_ms) {
+            Some(SensorStatus::Ok) => info!("{:?} recovered", self.sensor),
+            Some(status) => warn!("{:?} is {:?}", self.sensor, status),
+            None => {}
+        }
+        Some(result)
+    }
+}
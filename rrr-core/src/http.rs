@@ -0,0 +1,234 @@
+//! Request handling shared by the board's HTTP server and the virtual board.
+//! Each handler turns a parsed request into a [`Response`]; the servers only
+//! move bytes between their transport and these functions.
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use log::*;
+use rrr_api::{ApiError, BoardInfo, Command, CommandResponse, Encoding, State};
+use serde::Serialize;
+use crate::avionics::CommandError;
+use crate::hal::{FirmwareSlot, HalError, WifiScanner};
+use crate::logger::{FlightLogger, LogError, LogStorage};
+use crate::ota::{FirmwareUpdater, OtaError};
+
+/// Largest `/command` body accepted.
+pub const MAX_COMMAND_SIZE: usize = 4096;
+
+/// Bytes of log data read per chunk of a `GET /logs/{id}` response.
+pub const LOG_CHUNK_SIZE: usize = 1024;
+
+/// Sent with every response so the frontend can be served from another origin during development.
+pub const CORS_HEADER: (&str, &str) = ("Access-Control-Allow-Origin", "*");
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Response {
+    pub status: u16,
+    /// Reason phrase for error statuses.
+    pub message: Option<&'static str>,
+    pub content_type: Option<&'static str>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Self { status: 200, message: None, content_type: None, body: Vec::new() }
+    }
+
+    pub fn error(status: u16, message: &'static str) -> Self {
+        Self { status, message: Some(message), content_type: None, body: Vec::new() }
+    }
+
+    pub fn json<T: Serialize>(value: &T) -> Self {
+        Self::encoded(value, Encoding::Json)
+    }
+
+    pub fn encoded<T: Serialize>(value: &T, encoding: Encoding) -> Self {
+        Self {
+            status: 200,
+            message: None,
+            content_type: Some(encoding.content_type()),
+            body: encoding.encode(value),
+        }
+    }
+}
+
+/// `GET /state`, encoded as the `Accept` header asks.
+pub fn get_state(state: &Mutex<State>, accept: Option<&str>) -> Response {
+    let state = state.lock().unwrap().clone();
+    Response::encoded(&state, Encoding::negotiate(accept))
+}
+
+/// `GET /selftest`
+pub fn get_self_test(state: &Mutex<State>, accept: Option<&str>) -> Response {
+    let report = state.lock().unwrap().self_test.clone();
+    Response::encoded(&report, Encoding::negotiate(accept))
+}
+
+/// `GET /info`
+pub fn get_info(info: &BoardInfo) -> Response {
+    Response::json(info)
+}
+
+/// HTTP status matching a command failure.
+fn command_status(error: ApiError) -> u16 {
+    match error {
+        ApiError::MalformedCommand | ApiError::InvalidArgument => 400,
+        ApiError::NotArmed | ApiError::InFlight | ApiError::Busy => 409,
+        ApiError::Unauthorized => 403,
+        ApiError::HardwareFault | ApiError::StorageError => 500,
+    }
+}
+
+/// A `/command` response reporting `error`.
+pub fn command_failed(error: ApiError, message: String, encoding: Encoding) -> Response {
+    Response {
+        status: command_status(error),
+        ..Response::encoded(&CommandResponse::Failed { error, message }, encoding)
+    }
+}
+
+/// `POST /command`. The body is decoded as its `Content-Type` says, JSON if
+/// there is none, and the reply encoded as the `Accept` header asks.
+pub fn post_command<F>(body: &[u8], content_type: Option<&str>, accept: Option<&str>, command_handler: F) -> Response
+    where F: FnOnce(&Command) -> Result<(), CommandError>
+{
+    let encoding = Encoding::negotiate(accept);
+    let Some(body_encoding) = content_type.map_or(Some(Encoding::Json), Encoding::from_content_type) else {
+        return command_failed(ApiError::MalformedCommand, "Unsupported content type".into(), encoding);
+    };
+    let command = match body_encoding.decode::<Command>(body) {
+        Ok(command) => command,
+        Err(e) => return command_failed(ApiError::MalformedCommand, e.to_string(), encoding),
+    };
+    match command_handler(&command) {
+        Ok(()) => Response::encoded(&CommandResponse::Ok, encoding),
+        Err(e) => {
+            error!("Command failed: {}", e);
+            command_failed(e.api_error(), e.to_string(), encoding)
+        }
+    }
+}
+
+/// Session number of a `/logs/{id}` URI.
+pub fn log_id(uri: &str) -> Option<u16> {
+    uri.strip_prefix("/logs/")?.split('?').next()?.parse().ok()
+}
+
+/// `GET /logs`
+pub fn get_logs<S: LogStorage>(logger: Option<&Mutex<FlightLogger<S>>>) -> Response {
+    let Some(logger) = logger else {
+        return Response::error(503, "No log partition");
+    };
+    let sessions = logger.lock().unwrap().sessions();
+    match sessions {
+        Ok(sessions) => Response::json(&sessions),
+        Err(_) => Response::error(500, "Log storage failure"),
+    }
+}
+
+/// Checks a `GET /logs/{id}` request before the download is streamed with
+/// [`FlightLogger::read`]; returns the logger and session to send, or the
+/// error response.
+pub fn check_log_download<'a, S: LogStorage>(
+    logger: Option<&'a Mutex<FlightLogger<S>>>,
+    uri: &str,
+) -> Result<(&'a Mutex<FlightLogger<S>>, u16), Response> {
+    let logger = logger.ok_or(Response::error(503, "No log partition"))?;
+    let id = log_id(uri).ok_or(Response::error(400, "Invalid log id"))?;
+    let sessions = logger.lock().unwrap().sessions().map_err(|_| Response::error(500, "Log storage failure"))?;
+    if !sessions.iter().any(|s| s.id == id) {
+        return Err(Response::error(404, "No such log"));
+    }
+    Ok((logger, id))
+}
+
+/// `Content-Disposition` of a log download.
+pub fn log_disposition(id: u16) -> String {
+    format!("attachment; filename=\"flight-{}.rrrlog\"", id)
+}
+
+/// `DELETE /logs/{id}`
+pub fn delete_log<S: LogStorage>(logger: Option<&Mutex<FlightLogger<S>>>, uri: &str) -> Response {
+    let Some(logger) = logger else {
+        return Response::error(503, "No log partition");
+    };
+    let Some(id) = log_id(uri) else {
+        return Response::error(400, "Invalid log id");
+    };
+
+    let result = logger.lock().unwrap().delete_session(id);
+    match result {
+        Ok(()) => Response::ok(),
+        Err(LogError::SessionNotFound) => Response::error(404, "No such log"),
+        Err(LogError::SessionActive) => Response::error(409, "Log is still being recorded"),
+        Err(_) => Response::error(500, "Log storage failure"),
+    }
+}
+
+/// `POST /ota`. The body is the raw image; `read` fills a buffer from it and
+/// returns 0 at its end.
+pub fn post_ota<F: FirmwareSlot, E: std::fmt::Display>(
+    updater: &Mutex<FirmwareUpdater<F>>,
+    content_length: Option<usize>,
+    read: impl FnMut(&mut [u8]) -> Result<usize, E>,
+) -> Response {
+    // The lock is held for the whole upload.
+    let result = match updater.try_lock() {
+        Ok(mut updater) => content_length.ok_or(OtaError::LengthRequired).and_then(|size| updater.update(size, read)),
+        Err(_) => Err(OtaError::Busy),
+    };
+    match result {
+        Ok(()) => Response::json(&CommandResponse::Ok),
+        Err(e) => {
+            let status = match e {
+                OtaError::LengthRequired => 411,
+                OtaError::TooLarge { .. } => 413,
+                OtaError::Busy | OtaError::InFlight => 409,
+                OtaError::Signature(_) | OtaError::NoUpdateKey => 403,
+                OtaError::InvalidImage(_) | OtaError::Hal(HalError::InvalidImage) => 400,
+                OtaError::Empty | OtaError::Interrupted { .. } | OtaError::Connection(_) => 400,
+                OtaError::Hal(_) => 500,
+            };
+            Response {
+                status,
+                ..Response::json(&CommandResponse::Failed { error: e.api_error(), message: e.to_string() })
+            }
+        }
+    }
+}
+
+/// `GET /wifi/scan`: the networks in range, strongest first and each SSID
+/// once. Hidden networks are left out. Refused in flight, the radio leaves the
+/// access point's channel while it scans.
+pub fn get_wifi_scan<W: WifiScanner>(scanner: &Mutex<W>, state: &Mutex<State>, accept: Option<&str>) -> Response {
+    if state.lock().unwrap().flight.phase.is_flying() {
+        return Response::error(409, "No scan in flight");
+    }
+    let mut networks = match scanner.lock().unwrap().scan() {
+        Ok(networks) => networks,
+        Err(e) => {
+            warn!("Wi-Fi scan failed: {}", e);
+            return Response::error(500, "Wi-Fi scan failed");
+        }
+    };
+    networks.retain(|network| !network.ssid.is_empty());
+    networks.sort_by_key(|network| Reverse(network.rssi));
+    let mut seen = HashSet::new();
+    networks.retain(|network| seen.insert(network.ssid.clone()));
+    Response::encoded(&networks, Encoding::negotiate(accept))
+}
+
+/// `Content-Type` of a frontend asset.
+pub fn content_type(path: &str) -> &'static str {
+    match path.rsplit('.').next() {
+        Some("js") => "application/javascript",
+        Some("wasm") => "application/wasm",
+        Some("css") => "text/css",
+        Some("ico") => "image/x-icon",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        _ => "text/html",
+    }
+}
@@ -0,0 +1,20 @@
+//! Hardware-independent firmware logic. Everything in here is plain Rust so it
+//! can be unit-tested on the host with recorded or synthetic sensor data.
+
+pub mod altitude;
+pub mod avionics;
+pub mod estimator;
+pub mod flight;
+pub mod hal;
+pub mod health;
+pub mod http;
+pub mod logger;
+pub mod mock;
+pub mod ota;
+pub mod pyro;
+pub mod recovery;
+pub mod selftest;
+pub mod servo;
+pub mod signing;
+pub mod telemetry;
+pub mod wifi;
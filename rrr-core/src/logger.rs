@@ -0,0 +1,309 @@
+use std::collections::VecDeque;
+use rrr_api::{LogInfo, LogState};
+use rrr_log::format::{decode_frame, frame_sequence, frame_session, FormatError};
+use rrr_log::{LogHeader, LogRecord, Summary, FRAME_SIZE};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LogError {
+    #[error("Log storage access failed")]
+    StorageFailure,
+    #[error("Log storage is smaller than one sector")]
+    StorageTooSmall,
+    #[error("No such log session")]
+    SessionNotFound,
+    #[error("Log session is still being recorded")]
+    SessionActive,
+}
+
+/// Frames read from storage at once when scanning or downloading.
+const READ_BATCH: usize = 16;
+
+/// Raw access to the flash area holding the log. Offsets are relative to the
+/// start of the area; erased flash reads as `0xFF`.
+pub trait LogStorage {
+    fn capacity(&self) -> u32;
+    fn sector_size(&self) -> u32;
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), LogError>;
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), LogError>;
+    fn erase_sector(&mut self, offset: u32) -> Result<(), LogError>;
+}
+
+/// Download position within the log, see [`FlightLogger::read`].
+pub struct SessionReader {
+    session: u16,
+    start_slot: u32,
+    position: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct LoggerConfig {
+    /// Interval between records written while on the pad or after landing.
+    pub pad_interval_ms: u32,
+    /// Interval between records written in flight.
+    pub flight_interval_ms: u32,
+    /// How much history before liftoff is kept in RAM and written once launch is detected.
+    pub pre_launch_ms: u32,
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self {
+            pad_interval_ms: 1000,
+            flight_interval_ms: 20,
+            pre_launch_ms: 3000,
+        }
+    }
+}
+
+/// Ring buffer of [`rrr_log`] frames in a flash area.
+///
+/// Frames are written sequentially and the sector ahead of the write position
+/// is erased when it is reached, so the oldest data is overwritten once the
+/// area is full. On startup the area is scanned for the highest sequence
+/// number to find where writing resumes and to pick a new session number.
+///
+/// While the rocket is on the pad, records are written at `pad_interval_ms`
+/// and every sample of the last `pre_launch_ms` is kept in RAM; once the
+/// flight starts that backlog is flushed and every sample at
+/// `flight_interval_ms` is written until landing. The session header is
+/// written at startup and again at launch, so the flight is described by the
+/// configuration that was in effect when it started. Flushed pad samples get
+/// later sequence numbers than the ones written at the pad rate, so readers
+/// should order records by time within a session.
+pub struct FlightLogger<S: LogStorage> {
+    storage: S,
+    config: LoggerConfig,
+    slots: u32,
+    next_slot: u32,
+    next_sequence: u32,
+    session: u16,
+    header: LogHeader,
+    records_written: u32,
+    last_written_ms: Option<u32>,
+    /// Recent pad samples and whether each was already written at the pad rate.
+    pre_launch: VecDeque<(LogRecord, bool)>,
+    in_flight: bool,
+}
+
+impl<S: LogStorage> FlightLogger<S> {
+    pub fn new(storage: S, config: LoggerConfig, header: LogHeader) -> Result<Self, LogError> {
+        let sector_size = storage.sector_size();
+        let usable = storage.capacity() / sector_size * sector_size;
+        if usable == 0 || sector_size as usize % FRAME_SIZE != 0 {
+            return Err(LogError::StorageTooSmall);
+        }
+        let slots = usable / FRAME_SIZE as u32;
+
+        let mut logger = Self {
+            storage,
+            config,
+            slots,
+            next_slot: 0,
+            next_sequence: 0,
+            session: 0,
+            header: LogHeader::default(),
+            records_written: 0,
+            last_written_ms: None,
+            pre_launch: VecDeque::new(),
+            in_flight: false,
+        };
+
+        let mut latest: Option<(u32, u32, u16)> = None;
+        logger.for_each_frame(|slot, frame| {
+            if matches!(decode_frame(frame), Err(FormatError::Erased | FormatError::Deleted)) {
+                return;
+            }
+            let sequence = frame_sequence(frame);
+            if latest.map_or(true, |(latest_sequence, _, _)| sequence > latest_sequence) {
+                latest = Some((sequence, slot, frame_session(frame)));
+            }
+        })?;
+
+        if let Some((sequence, slot, session)) = latest {
+            logger.next_sequence = sequence + 1;
+            logger.next_slot = (slot + 1) % slots;
+            logger.session = session.wrapping_add(1);
+        }
+        logger.set_header(header);
+        logger.write_header()?;
+        Ok(logger)
+    }
+
+    pub fn state(&self) -> LogState {
+        LogState {
+            session: self.session,
+            recording_fast: self.in_flight,
+            records_written: self.records_written,
+            capacity_records: self.slots,
+        }
+    }
+
+    /// How long a flight the storage holds at the flight rate.
+    pub fn flight_capacity_ms(&self) -> u64 {
+        self.slots as u64 * self.config.flight_interval_ms as u64
+    }
+
+    /// Sessions stored in the log, oldest first.
+    pub fn sessions(&mut self) -> Result<Vec<LogInfo>, LogError> {
+        let mut summary = Summary::default();
+        self.for_each_frame(|_, frame| summary.add(frame))?;
+
+        let mut sessions = summary.finish();
+        sessions.iter_mut().for_each(|info| info.active = info.id == self.session);
+        Ok(sessions)
+    }
+
+    /// Starts reading the frames of `session` in storage order, oldest first.
+    pub fn reader(&self, session: u16) -> SessionReader {
+        SessionReader { session, start_slot: self.next_slot, position: 0 }
+    }
+
+    /// Copies the next valid frames of the reader's session into `buf` and
+    /// returns the number of bytes filled, `0` once the whole area was read.
+    /// Logging may continue between calls; frames overwritten in the meantime
+    /// are missed.
+    pub fn read(&mut self, reader: &mut SessionReader, buf: &mut [u8]) -> Result<usize, LogError> {
+        let mut batch = [0u8; FRAME_SIZE * READ_BATCH];
+        let mut filled = 0;
+
+        while reader.position < self.slots && buf.len() - filled >= FRAME_SIZE {
+            let slot = (reader.start_slot + reader.position) % self.slots;
+            let count = (self.slots - slot).min(self.slots - reader.position).min(READ_BATCH as u32) as usize;
+            self.storage.read(slot * FRAME_SIZE as u32, &mut batch[..count * FRAME_SIZE])?;
+
+            for frame in batch[..count * FRAME_SIZE].chunks_exact(FRAME_SIZE) {
+                if buf.len() - filled < FRAME_SIZE {
+                    break;
+                }
+                reader.position += 1;
+                if frame_session(frame) == reader.session && decode_frame(frame.try_into().unwrap()).is_ok() {
+                    buf[filled..filled + FRAME_SIZE].copy_from_slice(frame);
+                    filled += FRAME_SIZE;
+                }
+            }
+        }
+        Ok(filled)
+    }
+
+    /// Deletes a past session by overwriting its frames with zeros, which
+    /// flash allows without erasing a sector.
+    pub fn delete_session(&mut self, session: u16) -> Result<(), LogError> {
+        if session == self.session {
+            return Err(LogError::SessionActive);
+        }
+
+        let mut slots = Vec::new();
+        self.for_each_frame(|slot, frame| {
+            if frame_session(frame) == session && decode_frame(frame).is_ok() {
+                slots.push(slot);
+            }
+        })?;
+        if slots.is_empty() {
+            return Err(LogError::SessionNotFound);
+        }
+
+        for slot in slots {
+            self.storage.write(slot * FRAME_SIZE as u32, &[0u8; FRAME_SIZE])?;
+        }
+        Ok(())
+    }
+
+    pub fn storage(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    /// Replaces the header written at the next launch. Sample intervals are
+    /// taken from the logger configuration.
+    pub fn set_header(&mut self, mut header: LogHeader) {
+        header.pad_interval_ms = self.config.pad_interval_ms;
+        header.flight_interval_ms = self.config.flight_interval_ms;
+        header.pre_launch_ms = self.config.pre_launch_ms;
+        self.header = header;
+    }
+
+    pub fn log(&mut self, record: LogRecord) -> Result<(), LogError> {
+        let flying = record.phase.is_flying();
+
+        if flying && !self.in_flight {
+            self.in_flight = true;
+            self.write_header()?;
+            while let Some((buffered, written)) = self.pre_launch.pop_front() {
+                if !written {
+                    self.write(buffered)?;
+                }
+            }
+        }
+        let interval = if flying { self.config.flight_interval_ms } else { self.config.pad_interval_ms };
+        let due = self.last_written_ms.map_or(true, |last| record.time_ms.saturating_sub(last) >= interval);
+
+        if !flying {
+            self.in_flight = false;
+            while let Some((oldest, _)) = self.pre_launch.front() {
+                if record.time_ms.saturating_sub(oldest.time_ms) < self.config.pre_launch_ms {
+                    break;
+                }
+                self.pre_launch.pop_front();
+            }
+            self.pre_launch.push_back((record.clone(), due));
+        }
+
+        if due {
+            self.write(record)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the latest pad sample if it was only buffered, so the log ends
+    /// with the state before a reboot.
+    pub fn flush(&mut self) -> Result<(), LogError> {
+        let record = match self.pre_launch.back_mut() {
+            Some((record, written)) if !*written => {
+                *written = true;
+                record.clone()
+            }
+            _ => return Ok(()),
+        };
+        self.write(record)
+    }
+
+    fn for_each_frame<F: FnMut(u32, &[u8; FRAME_SIZE])>(&mut self, mut f: F) -> Result<(), LogError> {
+        let mut batch = [0u8; FRAME_SIZE * READ_BATCH];
+        for first in (0..self.slots).step_by(READ_BATCH) {
+            let count = (self.slots - first).min(READ_BATCH as u32) as usize;
+            self.storage.read(first * FRAME_SIZE as u32, &mut batch[..count * FRAME_SIZE])?;
+            for (i, frame) in batch[..count * FRAME_SIZE].chunks_exact(FRAME_SIZE).enumerate() {
+                f(first + i as u32, frame.try_into().unwrap());
+            }
+        }
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> Result<(), LogError> {
+        for frame in self.header.encode(self.session, self.next_sequence) {
+            self.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, mut record: LogRecord) -> Result<(), LogError> {
+        record.sequence = self.next_sequence;
+        record.session = self.session;
+        self.write_frame(&record.encode())?;
+        self.last_written_ms = Some(record.time_ms);
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &[u8; FRAME_SIZE]) -> Result<(), LogError> {
+        let offset = self.next_slot * FRAME_SIZE as u32;
+        if offset % self.storage.sector_size() == 0 {
+            self.storage.erase_sector(offset)?;
+        }
+        self.storage.write(offset, frame)?;
+
+        self.next_slot = (self.next_slot + 1) % self.slots;
+        self.next_sequence += 1;
+        self.records_written += 1;
+        Ok(())
+    }
+}
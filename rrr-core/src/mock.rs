@@ -0,0 +1,479 @@
+//! In-memory implementations of the [`crate::hal`] traits for host tests and
+//! simulation. The mocks are handles to shared state: a clone kept by the test
+//! drives the inputs and inspects the outputs after the original was moved
+//! into the code under test.
+
+use std::sync::{Arc, Mutex};
+use rrr_api::{FirmwareSlots, RecoveryConfig, ServoCalibration, SlotInfo, SlotStatus, WifiCredentials, WifiNetwork};
+use sha2::{Digest, Sha256};
+use crate::altitude::STANDARD_PRESSURE;
+use crate::hal::*;
+use crate::logger::{LogError, LogStorage};
+use crate::ota::{ImageHeader, APP_DESC_MAGIC, IMAGE_HASH_SIZE, IMAGE_HEADER_SIZE, IMAGE_MAGIC};
+
+#[derive(Clone)]
+pub struct MockBarometer {
+    reading: Arc<Mutex<Result<BarometerReading, HalError>>>,
+    reinits: Arc<Mutex<u32>>,
+}
+
+impl Default for MockBarometer {
+    fn default() -> Self {
+        let reading = BarometerReading { pressure: STANDARD_PRESSURE, temperature: 20.0 };
+        Self { reading: Arc::new(Mutex::new(Ok(reading))), reinits: Arc::default() }
+    }
+}
+
+impl MockBarometer {
+    pub fn set(&self, pressure: f32, temperature: f32) {
+        *self.reading.lock().unwrap() = Ok(BarometerReading { pressure, temperature });
+    }
+
+    pub fn fail(&self, error: HalError) {
+        *self.reading.lock().unwrap() = Err(error);
+    }
+
+    /// Number of times the device was re-initialised.
+    pub fn reinits(&self) -> u32 {
+        *self.reinits.lock().unwrap()
+    }
+}
+
+impl Barometer for MockBarometer {
+    fn read(&mut self) -> Result<BarometerReading, HalError> {
+        self.reading.lock().unwrap().clone()
+    }
+
+    fn reinit(&mut self) -> Result<(), HalError> {
+        *self.reinits.lock().unwrap() += 1;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct MockFuelGauge {
+    reading: Arc<Mutex<Result<BatteryReading, HalError>>>,
+    reinits: Arc<Mutex<u32>>,
+}
+
+impl Default for MockFuelGauge {
+    fn default() -> Self {
+        let reading = BatteryReading { soc: 100.0, voltage: 4.1, charge_rate: 0.0 };
+        Self { reading: Arc::new(Mutex::new(Ok(reading))), reinits: Arc::default() }
+    }
+}
+
+impl MockFuelGauge {
+    pub fn set(&self, reading: BatteryReading) {
+        *self.reading.lock().unwrap() = Ok(reading);
+    }
+
+    pub fn fail(&self, error: HalError) {
+        *self.reading.lock().unwrap() = Err(error);
+    }
+
+    /// Number of times the device was re-initialised.
+    pub fn reinits(&self) -> u32 {
+        *self.reinits.lock().unwrap()
+    }
+}
+
+impl FuelGauge for MockFuelGauge {
+    fn read(&mut self) -> Result<BatteryReading, HalError> {
+        self.reading.lock().unwrap().clone()
+    }
+
+    fn reinit(&mut self) -> Result<(), HalError> {
+        *self.reinits.lock().unwrap() += 1;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct MockContinuity {
+    volts: Arc<Mutex<Vec<Result<f32, HalError>>>>,
+}
+
+impl MockContinuity {
+    /// All channels start without an igniter connected.
+    pub fn new(channels: u8) -> Self {
+        Self { volts: Arc::new(Mutex::new(vec![Ok(0.0); channels as usize])) }
+    }
+
+    pub fn set_volts(&self, channel: u8, volts: f32) {
+        self.volts.lock().unwrap()[channel as usize - 1] = Ok(volts);
+    }
+
+    pub fn fail(&self, channel: u8, error: HalError) {
+        self.volts.lock().unwrap()[channel as usize - 1] = Err(error);
+    }
+}
+
+impl ContinuityMonitor for MockContinuity {
+    fn channels(&self) -> u8 {
+        self.volts.lock().unwrap().len() as u8
+    }
+
+    fn read_volts(&mut self, channel: u8) -> Result<f32, HalError> {
+        let volts = self.volts.lock().unwrap();
+        (channel as usize).checked_sub(1)
+            .and_then(|i| volts.get(i))
+            .ok_or(HalError::InvalidChannel)?
+            .clone()
+    }
+}
+
+#[derive(Clone)]
+pub struct MockServoOutput {
+    max_duty: u32,
+    duty: Arc<Mutex<Option<u32>>>,
+}
+
+impl Default for MockServoOutput {
+    /// 14-bit resolution, like the board's LEDC timer.
+    fn default() -> Self {
+        Self { max_duty: (1 << 14) - 1, duty: Arc::default() }
+    }
+}
+
+impl MockServoOutput {
+    /// Last duty written, `None` before the first write.
+    pub fn duty(&self) -> Option<u32> {
+        *self.duty.lock().unwrap()
+    }
+}
+
+impl ServoOutput for MockServoOutput {
+    fn max_duty(&self) -> u32 {
+        self.max_duty
+    }
+
+    fn set_duty(&mut self, duty: u32) -> Result<(), HalError> {
+        *self.duty.lock().unwrap() = Some(duty);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockStatusLed {
+    color: Arc<Mutex<Option<(u8, u8, u8)>>>,
+}
+
+impl MockStatusLed {
+    pub fn color(&self) -> Option<(u8, u8, u8)> {
+        *self.color.lock().unwrap()
+    }
+}
+
+impl StatusLed for MockStatusLed {
+    fn set_rgb(&mut self, r: u8, g: u8, b: u8) -> Result<(), HalError> {
+        *self.color.lock().unwrap() = Some((r, g, b));
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockPyroOutput {
+    history: Arc<Mutex<Vec<bool>>>,
+    /// Makes every write fail, like a shorted or disconnected output stage.
+    pub broken: bool,
+}
+
+impl MockPyroOutput {
+    pub fn broken() -> Self {
+        Self { broken: true, ..Default::default() }
+    }
+
+    /// Every level written to the output, oldest first.
+    pub fn history(&self) -> Vec<bool> {
+        self.history.lock().unwrap().clone()
+    }
+
+    pub fn is_firing(&self) -> bool {
+        self.history.lock().unwrap().last().copied().unwrap_or(false)
+    }
+}
+
+impl PyroOutput for MockPyroOutput {
+    fn set_firing(&mut self, firing: bool) -> Result<(), HalError> {
+        if self.broken {
+            return Err(HalError::OutputFailed);
+        }
+        self.history.lock().unwrap().push(firing);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct StoredSettings {
+    pub wifi: Vec<WifiCredentials>,
+    pub recovery: Option<RecoveryConfig>,
+    pub servo_calibration: [Option<ServoCalibration>; 2],
+}
+
+#[derive(Clone, Default)]
+pub struct MemorySettings {
+    stored: Arc<Mutex<StoredSettings>>,
+    error: Arc<Mutex<Option<HalError>>>,
+}
+
+impl MemorySettings {
+    pub fn stored(&self) -> StoredSettings {
+        self.stored.lock().unwrap().clone()
+    }
+
+    /// Makes every read and write fail, like broken storage would.
+    pub fn fail(&self, error: HalError) {
+        *self.error.lock().unwrap() = Some(error);
+    }
+
+    fn check(&self) -> Result<(), HalError> {
+        self.error.lock().unwrap().clone().map_or(Ok(()), Err)
+    }
+}
+
+impl Settings for MemorySettings {
+    fn wifi_networks(&mut self) -> Result<Vec<WifiCredentials>, HalError> {
+        self.check()?;
+        Ok(self.stored.lock().unwrap().wifi.clone())
+    }
+
+    fn set_wifi_networks(&mut self, networks: &[WifiCredentials]) -> Result<(), HalError> {
+        self.check()?;
+        self.stored.lock().unwrap().wifi = networks.to_vec();
+        Ok(())
+    }
+
+    fn recovery_config(&mut self) -> Result<Option<RecoveryConfig>, HalError> {
+        self.check()?;
+        Ok(self.stored.lock().unwrap().recovery.clone())
+    }
+
+    fn set_recovery_config(&mut self, config: &RecoveryConfig) -> Result<(), HalError> {
+        self.check()?;
+        self.stored.lock().unwrap().recovery = Some(config.clone());
+        Ok(())
+    }
+
+    fn servo_calibration(&mut self, index: usize) -> Result<Option<ServoCalibration>, HalError> {
+        self.check()?;
+        let stored = self.stored.lock().unwrap();
+        Ok(stored.servo_calibration.get(index).ok_or(HalError::InvalidChannel)?.clone())
+    }
+
+    fn set_servo_calibration(&mut self, index: usize, calibration: &ServoCalibration) -> Result<(), HalError> {
+        self.check()?;
+        let mut stored = self.stored.lock().unwrap();
+        *stored.servo_calibration.get_mut(index).ok_or(HalError::InvalidChannel)? = Some(calibration.clone());
+        Ok(())
+    }
+
+    fn erase(&mut self) -> Result<(), HalError> {
+        *self.stored.lock().unwrap() = StoredSettings::default();
+        Ok(())
+    }
+}
+
+/// NOR flash emulation: writes can only clear bits, erasing sets a whole sector to `0xFF`.
+#[derive(Clone)]
+pub struct MemoryStorage {
+    pub data: Vec<u8>,
+    pub sector_size: u32,
+    pub erase_count: u32,
+}
+
+impl MemoryStorage {
+    pub fn new(sectors: usize, sector_size: u32) -> Self {
+        Self { data: vec![0xFF; sectors * sector_size as usize], sector_size, erase_count: 0 }
+    }
+}
+
+impl LogStorage for MemoryStorage {
+    fn capacity(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    fn sector_size(&self) -> u32 {
+        self.sector_size
+    }
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), LogError> {
+        let offset = offset as usize;
+        buf.copy_from_slice(self.data.get(offset..offset + buf.len()).ok_or(LogError::StorageFailure)?);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), LogError> {
+        let offset = offset as usize;
+        let target = self.data.get_mut(offset..offset + data.len()).ok_or(LogError::StorageFailure)?;
+        target.iter_mut().zip(data).for_each(|(t, d)| *t &= d);
+        Ok(())
+    }
+
+    fn erase_sector(&mut self, offset: u32) -> Result<(), LogError> {
+        let start = (offset / self.sector_size * self.sector_size) as usize;
+        self.data[start..start + self.sector_size as usize].fill(0xFF);
+        self.erase_count += 1;
+        Ok(())
+    }
+}
+
+/// Finds the networks it was given.
+#[derive(Clone)]
+pub struct MockWifiScanner {
+    networks: Arc<Mutex<Result<Vec<WifiNetwork>, HalError>>>,
+}
+
+impl Default for MockWifiScanner {
+    fn default() -> Self {
+        Self { networks: Arc::new(Mutex::new(Ok(Vec::new()))) }
+    }
+}
+
+impl MockWifiScanner {
+    pub fn set_networks(&self, networks: Vec<WifiNetwork>) {
+        *self.networks.lock().unwrap() = Ok(networks);
+    }
+
+    pub fn fail(&self, error: HalError) {
+        *self.networks.lock().unwrap() = Err(error);
+    }
+}
+
+impl WifiScanner for MockWifiScanner {
+    fn scan(&mut self) -> Result<Vec<WifiNetwork>, HalError> {
+        self.networks.lock().unwrap().clone()
+    }
+}
+
+struct SlotContent {
+    writing: Option<Vec<u8>>,
+    image: Option<Vec<u8>>,
+    reject: bool,
+    slots: FirmwareSlots,
+    rolled_back: bool,
+}
+
+/// App partitions in RAM. Images are written to `ota_1` while `ota_0` runs.
+#[derive(Clone)]
+pub struct MockFirmwareSlot {
+    capacity: u32,
+    content: Arc<Mutex<SlotContent>>,
+}
+
+impl MockFirmwareSlot {
+    /// Runs a valid image of version `version`.
+    pub fn new(capacity: u32, version: &str) -> Self {
+        let running = SlotInfo { label: "ota_0".into(), version: Some(version.into()), status: SlotStatus::Valid };
+        let content = SlotContent {
+            writing: None,
+            image: None,
+            reject: false,
+            slots: FirmwareSlots { running: Some(running), previous: None },
+            rolled_back: false,
+        };
+        Self { capacity, content: Arc::new(Mutex::new(content)) }
+    }
+
+    /// The last completed image.
+    pub fn image(&self) -> Option<Vec<u8>> {
+        self.content.lock().unwrap().image.clone()
+    }
+
+    /// Whether an image was begun and neither completed nor aborted.
+    pub fn is_writing(&self) -> bool {
+        self.content.lock().unwrap().writing.is_some()
+    }
+
+    /// Makes [`FirmwareSlot::complete`] reject the images.
+    pub fn reject(&self, reject: bool) {
+        self.content.lock().unwrap().reject = reject;
+    }
+
+    pub fn set_slots(&self, slots: FirmwareSlots) {
+        self.content.lock().unwrap().slots = slots;
+    }
+
+    /// Whether [`FirmwareSlot::roll_back`] was called; the mock keeps running.
+    pub fn rolled_back(&self) -> bool {
+        self.content.lock().unwrap().rolled_back
+    }
+}
+
+impl FirmwareSlot for MockFirmwareSlot {
+    fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    fn begin(&mut self, size: u32) -> Result<(), HalError> {
+        if size > self.capacity {
+            return Err(HalError::StorageFailed);
+        }
+        self.content.lock().unwrap().writing = Some(Vec::with_capacity(size as usize));
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), HalError> {
+        let mut content = self.content.lock().unwrap();
+        let writing = content.writing.as_mut().ok_or(HalError::OutputFailed)?;
+        if writing.len() + data.len() > self.capacity as usize {
+            return Err(HalError::OutputFailed);
+        }
+        writing.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn complete(&mut self) -> Result<(), HalError> {
+        let mut content = self.content.lock().unwrap();
+        let image = content.writing.take().ok_or(HalError::OutputFailed)?;
+        if content.reject {
+            return Err(HalError::InvalidImage);
+        }
+        let version = ImageHeader::parse(&image).ok().map(|header| header.version);
+        content.slots.previous = Some(SlotInfo { label: "ota_1".into(), version, status: SlotStatus::Unverified });
+        content.image = Some(image);
+        Ok(())
+    }
+
+    fn abort(&mut self) {
+        self.content.lock().unwrap().writing = None;
+    }
+
+    fn slots(&self) -> Result<FirmwareSlots, HalError> {
+        Ok(self.content.lock().unwrap().slots.clone())
+    }
+
+    fn mark_valid(&mut self) -> Result<(), HalError> {
+        let mut content = self.content.lock().unwrap();
+        let running = content.slots.running.as_mut().ok_or(HalError::StorageFailed)?;
+        running.status = SlotStatus::Valid;
+        Ok(())
+    }
+
+    fn roll_back(&mut self) -> Result<(), HalError> {
+        let mut content = self.content.lock().unwrap();
+        let running = content.slots.running.as_mut().ok_or(HalError::StorageFailed)?;
+        running.status = SlotStatus::Invalid;
+        content.rolled_back = true;
+        Ok(())
+    }
+}
+
+/// An app image of `size` bytes that passes the update's checks: an image
+/// header for `chip_id`, an app description with `version` and the SHA-256
+/// of the rest appended.
+pub fn firmware_image(version: &str, chip_id: u16, size: usize) -> Vec<u8> {
+    assert!(size >= IMAGE_HEADER_SIZE + IMAGE_HASH_SIZE && version.len() < 32);
+    let mut image: Vec<u8> = (0..size - IMAGE_HASH_SIZE).map(|i| (i % 251) as u8).collect();
+    image[..IMAGE_HEADER_SIZE].fill(0);
+    image[0] = IMAGE_MAGIC;
+    image[1] = 1;
+    image[12..14].copy_from_slice(&chip_id.to_le_bytes());
+    image[23] = 1;
+    image[32..36].copy_from_slice(&APP_DESC_MAGIC.to_le_bytes());
+    image[48..48 + version.len()].copy_from_slice(version.as_bytes());
+    image[80..84].copy_from_slice(b"rrr\0");
+    let hash = Sha256::digest(&image);
+    image.extend_from_slice(&hash);
+    image
+}
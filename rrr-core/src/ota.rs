@@ -0,0 +1,340 @@
+//! Firmware images uploaded to `POST /ota`, streamed into the update partition
+//! while the progress is published in [`State::ota`]. An image is checked as
+//! it arrives: its header before anything is written, its appended SHA-256 and
+//! its signature before it is made bootable. After the next boot
+//! [`FirmwareUpdater::confirm_boot`] keeps it or returns to the previous image,
+//! depending on the self-test.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use log::*;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use rrr_api::{ApiError, OtaState, OtaStatus, SlotStatus, State};
+use crate::hal::{FirmwareSlot, HalError};
+use crate::signing::{SignatureCheck, SignatureError, SignatureTrailer, VerifyingKey, TRAILER_SIZE};
+
+/// Bytes read from the request and written to the partition at a time.
+pub const OTA_CHUNK_SIZE: usize = 4096;
+
+/// First byte of an `esp_image_header_t`.
+pub const IMAGE_MAGIC: u8 = 0xE9;
+/// `magic_word` of the `esp_app_desc_t` following the first segment header.
+pub const APP_DESC_MAGIC: u32 = 0xABCD_5432;
+/// `chip_id` the ESP32-C3 images are built with.
+pub const ESP32C3_CHIP_ID: u16 = 5;
+/// Image header, first segment header and app description.
+pub const IMAGE_HEADER_SIZE: usize = 288;
+/// SHA-256 appended to the image by esptool.
+pub const IMAGE_HASH_SIZE: usize = 32;
+
+#[derive(Error, Clone, PartialEq, Debug)]
+pub enum OtaError {
+    #[error("Content-Length is required")]
+    LengthRequired,
+    #[error("Image is empty")]
+    Empty,
+    #[error("Image of {size} bytes does not fit the {capacity} byte partition")]
+    TooLarge { size: usize, capacity: u32 },
+    #[error("Upload ended after {received} of {expected} bytes")]
+    Interrupted { received: usize, expected: usize },
+    #[error("Upload failed: {0}")]
+    Connection(String),
+    #[error("Invalid image: {0}")]
+    InvalidImage(String),
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error("Updates are disabled, the firmware was built without an update key")]
+    NoUpdateKey,
+    #[error("Another update is in progress")]
+    Busy,
+    #[error("Updates are not allowed in flight")]
+    InFlight,
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl OtaError {
+    pub fn api_error(&self) -> ApiError {
+        match self {
+            OtaError::LengthRequired | OtaError::Empty | OtaError::TooLarge { .. } => ApiError::InvalidArgument,
+            OtaError::Interrupted { .. } | OtaError::Connection(_) => ApiError::InvalidArgument,
+            OtaError::InvalidImage(_) | OtaError::Hal(HalError::InvalidImage) => ApiError::InvalidArgument,
+            OtaError::Signature(_) | OtaError::NoUpdateKey => ApiError::Unauthorized,
+            OtaError::Busy => ApiError::Busy,
+            OtaError::InFlight => ApiError::InFlight,
+            OtaError::Hal(_) => ApiError::HardwareFault,
+        }
+    }
+}
+
+fn invalid(message: &str) -> OtaError {
+    OtaError::InvalidImage(message.into())
+}
+
+/// The parts of an ESP-IDF app image header an update checks.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ImageHeader {
+    pub chip_id: u16,
+    /// Whether esptool appended the SHA-256 of the image.
+    pub hash_appended: bool,
+    pub version: String,
+    pub project_name: String,
+}
+
+/// A NUL padded string of the app description.
+fn c_string(data: &[u8]) -> String {
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+impl ImageHeader {
+    /// Parses the first [`IMAGE_HEADER_SIZE`] bytes of an image.
+    pub fn parse(data: &[u8]) -> Result<Self, OtaError> {
+        if data.len() < IMAGE_HEADER_SIZE {
+            return Err(invalid("Image is too short"));
+        }
+        if data[0] != IMAGE_MAGIC {
+            return Err(invalid("Not an ESP-IDF app image"));
+        }
+        if u32::from_le_bytes([data[32], data[33], data[34], data[35]]) != APP_DESC_MAGIC {
+            return Err(invalid("Image has no app description"));
+        }
+        Ok(Self {
+            chip_id: u16::from_le_bytes([data[12], data[13]]),
+            hash_appended: data[23] == 1,
+            version: c_string(&data[48..80]),
+            project_name: c_string(&data[80..112]),
+        })
+    }
+}
+
+/// Checks a signed image while it streams in.
+struct ImageCheck {
+    /// Size of the image without the signature trailer.
+    size: usize,
+    chip_id: u16,
+    received: usize,
+    head: Vec<u8>,
+    header: Option<ImageHeader>,
+    hasher: Sha256,
+    /// The appended SHA-256, the last bytes of the image.
+    hash: Vec<u8>,
+    signature: SignatureCheck,
+    trailer: Vec<u8>,
+}
+
+impl ImageCheck {
+    /// `upload_size` includes the signature trailer.
+    fn new(upload_size: usize, chip_id: u16) -> Result<Self, OtaError> {
+        let size = upload_size.checked_sub(TRAILER_SIZE).ok_or(SignatureError::Unsigned)?;
+        if size < IMAGE_HEADER_SIZE + IMAGE_HASH_SIZE {
+            return Err(invalid("Image is too short"));
+        }
+        Ok(Self {
+            size,
+            chip_id,
+            received: 0,
+            head: Vec::with_capacity(IMAGE_HEADER_SIZE),
+            header: None,
+            hasher: Sha256::new(),
+            hash: Vec::with_capacity(IMAGE_HASH_SIZE),
+            signature: SignatureCheck::default(),
+            trailer: Vec::with_capacity(TRAILER_SIZE),
+        })
+    }
+
+    fn header(&self) -> Option<&ImageHeader> {
+        self.header.as_ref()
+    }
+
+    /// Checks the next `data` of the upload, the header as soon as it is
+    /// complete. Returns how many of its bytes belong to the image, the rest
+    /// is the signature trailer.
+    fn update(&mut self, data: &[u8]) -> Result<usize, OtaError> {
+        let image_len = self.size.saturating_sub(self.received).min(data.len());
+        let (data, trailer) = data.split_at(image_len);
+        self.trailer.extend_from_slice(trailer);
+        self.signature.update(data);
+        let hashed = (self.size - IMAGE_HASH_SIZE).saturating_sub(self.received).min(data.len());
+        self.hasher.update(&data[..hashed]);
+        self.hash.extend_from_slice(&data[hashed..]);
+        self.received += data.len();
+
+        if self.header.is_none() {
+            let missing = IMAGE_HEADER_SIZE - self.head.len();
+            self.head.extend_from_slice(&data[..missing.min(data.len())]);
+            if self.head.len() == IMAGE_HEADER_SIZE {
+                let header = ImageHeader::parse(&self.head)?;
+                if header.chip_id != self.chip_id {
+                    return Err(OtaError::InvalidImage(format!("Image is built for chip {}", header.chip_id)));
+                }
+                if !header.hash_appended {
+                    return Err(invalid("Image has no SHA-256 appended"));
+                }
+                self.header = Some(header);
+            }
+        }
+        Ok(image_len)
+    }
+
+    fn finish(self, key: &VerifyingKey) -> Result<ImageHeader, OtaError> {
+        let header = self.header.ok_or_else(|| invalid("Image is too short"))?;
+        // Without a trailer the end of the image is missing as well.
+        SignatureTrailer::parse(&self.trailer)?;
+        if self.hasher.finalize().as_slice() != self.hash.as_slice() {
+            return Err(invalid("SHA-256 does not match"));
+        }
+        self.signature.verify(&self.trailer, self.size, key)?;
+        Ok(header)
+    }
+}
+
+pub struct FirmwareUpdater<F: FirmwareSlot> {
+    slot: F,
+    state: Arc<Mutex<State>>,
+    /// Images built for another chip are refused.
+    chip_id: u16,
+    /// Key the images must be signed with, updates are refused without one.
+    key: Option<VerifyingKey>,
+}
+
+impl<F: FirmwareSlot> FirmwareUpdater<F> {
+    pub fn new(slot: F, state: Arc<Mutex<State>>, chip_id: u16, key: Option<VerifyingKey>) -> Self {
+        Self { slot, state, chip_id, key }
+    }
+
+    /// Writes a signed image of `size` bytes to the update partition. `read`
+    /// fills the buffer from the request body and returns 0 at its end. The
+    /// image boots after the next reset only if all of it was received and
+    /// checked.
+    pub fn update<E: fmt::Display>(
+        &mut self,
+        size: usize,
+        mut read: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<(), OtaError> {
+        let result = self.write_image(size, &mut read);
+        {
+            let mut state = self.state.lock().unwrap();
+            match &result {
+                Ok(()) => {
+                    info!("Firmware update of {} bytes complete", size);
+                    state.ota.status = OtaStatus::Complete;
+                }
+                // Nothing was touched, an update that is running keeps its progress.
+                Err(OtaError::Busy | OtaError::InFlight) => {}
+                Err(e) => {
+                    error!("Firmware update failed: {}", e);
+                    state.ota.status = OtaStatus::Failed;
+                    state.ota.error = Some(e.to_string());
+                }
+            }
+        }
+        if result.is_ok() {
+            self.refresh_slots();
+        }
+        result
+    }
+
+    fn write_image<E: fmt::Display>(
+        &mut self,
+        size: usize,
+        read: &mut impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<(), OtaError> {
+        if self.state.lock().unwrap().flight.phase.is_flying() {
+            return Err(OtaError::InFlight);
+        }
+        let capacity = self.slot.capacity();
+        if size == 0 {
+            return Err(OtaError::Empty);
+        }
+        let key = self.key.ok_or(OtaError::NoUpdateKey)?;
+        let mut check = ImageCheck::new(size, self.chip_id)?;
+        if check.size > capacity as usize {
+            return Err(OtaError::TooLarge { size: check.size, capacity });
+        }
+
+        info!("Receiving firmware update of {} bytes", size);
+        self.state.lock().unwrap().ota = OtaState {
+            status: OtaStatus::Receiving,
+            received: 0,
+            size: size as u32,
+            version: None,
+            error: None,
+        };
+        // The trailer is checked on the way, only the image reaches the partition.
+        self.slot.begin(check.size as u32)?;
+        let result = self.receive(size, &mut check, read)
+            .and_then(|()| check.finish(&key))
+            .and_then(|header| {
+                info!("Image of {} {} verified", header.project_name, header.version);
+                Ok(self.slot.complete()?)
+            });
+        if result.is_err() {
+            self.slot.abort();
+        }
+        result
+    }
+
+    fn receive<E: fmt::Display>(
+        &mut self,
+        size: usize,
+        check: &mut ImageCheck,
+        read: &mut impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<(), OtaError> {
+        let mut buffer = vec![0; OTA_CHUNK_SIZE];
+        let mut received = 0;
+        while received < size {
+            let len = OTA_CHUNK_SIZE.min(size - received);
+            let n = read(&mut buffer[..len]).map_err(|e| OtaError::Connection(e.to_string()))?;
+            if n == 0 {
+                return Err(OtaError::Interrupted { received, expected: size });
+            }
+            // Nothing past a bad header reaches the partition.
+            let image_len = check.update(&buffer[..n])?;
+            if image_len > 0 {
+                self.slot.write(&buffer[..image_len])?;
+            }
+            received += n;
+
+            let mut state = self.state.lock().unwrap();
+            state.ota.received = received as u32;
+            if state.ota.version.is_none() {
+                state.ota.version = check.header().map(|header| header.version.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Publishes the app partitions in [`State::firmware`].
+    pub fn refresh_slots(&self) {
+        match self.slot.slots() {
+            Ok(slots) => self.state.lock().unwrap().firmware = slots,
+            Err(e) => error!("Reading the firmware slots failed: {}", e),
+        }
+    }
+
+    /// Settles an image booted for the first time once the boot self-test
+    /// finished: keeps it if the firmware works, otherwise restarts into the
+    /// previous image. Readiness failures such as a low battery do not count.
+    /// Images already valid are left alone.
+    pub fn confirm_boot(&mut self) -> Result<(), HalError> {
+        let slots = self.slot.slots()?;
+        let result = match &slots.running {
+            Some(running) if running.status == SlotStatus::Unverified => {
+                let version = running.version.as_deref().unwrap_or("unknown");
+                if self.state.lock().unwrap().self_test.firmware_works() {
+                    info!("Self-test passed, keeping firmware {}", version);
+                    self.slot.mark_valid()
+                } else {
+                    let previous = slots.previous.as_ref().and_then(|slot| slot.version.as_deref());
+                    error!("Self-test failed on firmware {}, rolling back to {}", version, previous.unwrap_or("unknown"));
+                    self.slot.roll_back()
+                }
+            }
+            _ => Ok(()),
+        };
+        self.refresh_slots();
+        result
+    }
+}
@@ -0,0 +1,148 @@
+use rrr_api::{PyroChannelState, PyroState};
+pub use rrr_api::{MAX_FIRE_DURATION_MS, MIN_FIRE_DURATION_MS};
+use thiserror::Error;
+use crate::hal::{HalError, PyroOutput};
+
+/// Continuity voltage above which an igniter is considered connected.
+pub const CONTINUITY_THRESHOLD_VOLTS: f32 = 1.0;
+pub const DEFAULT_FIRE_DURATION_MS: u32 = 500;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PyroError {
+    #[error("Pyro channels are not armed")]
+    NotArmed,
+    #[error("Pyro channel does not exist")]
+    InvalidChannel,
+    #[error("Ignition pulse length is out of range")]
+    InvalidDuration,
+    #[error("Pyro output hardware failure")]
+    HardwareFault,
+}
+
+impl From<HalError> for PyroError {
+    fn from(_: HalError) -> Self {
+        PyroError::HardwareFault
+    }
+}
+
+struct PyroChannel<P> {
+    pin: P,
+    fire_until_ms: Option<u64>,
+    awaiting_continuity: bool,
+    state: PyroChannelState,
+}
+
+/// Arm/fire interlock for the pyro channels.
+///
+/// Channels are numbered from 1. A channel can only be fired while the
+/// controller is armed, the pulse length is bounded and the output is released
+/// by [`PyroController::poll`] once the pulse is over. The first continuity
+/// reading after the release is reported as `post_fire_continuity`.
+pub struct PyroController<P: PyroOutput> {
+    channels: Vec<PyroChannel<P>>,
+    armed: bool,
+}
+
+impl<P: PyroOutput> PyroController<P> {
+    /// Takes ownership of the outputs and drives all of them low.
+    pub fn new(pins: Vec<P>) -> Result<Self, PyroError> {
+        let mut channels = Vec::with_capacity(pins.len());
+        for mut pin in pins {
+            pin.set_firing(false)?;
+            channels.push(PyroChannel {
+                pin,
+                fire_until_ms: None,
+                awaiting_continuity: false,
+                state: PyroChannelState::default(),
+            });
+        }
+        Ok(Self { channels, armed: false })
+    }
+
+    pub fn channels(&self) -> u8 {
+        self.channels.len() as u8
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
+
+    pub fn arm(&mut self) {
+        self.armed = true;
+    }
+
+    /// Disarms the controller and immediately cuts any pulse in progress.
+    pub fn disarm(&mut self) -> Result<(), PyroError> {
+        self.armed = false;
+        let mut result = Ok(());
+        for channel in self.channels.iter_mut().filter(|c| c.fire_until_ms.is_some()) {
+            result = result.and(Self::release(channel));
+        }
+        result
+    }
+
+    pub fn fire(&mut self, channel: u8, duration_ms: u32, now_ms: u64) -> Result<(), PyroError> {
+        if !self.armed {
+            return Err(PyroError::NotArmed);
+        }
+        if !(MIN_FIRE_DURATION_MS..=MAX_FIRE_DURATION_MS).contains(&duration_ms) {
+            return Err(PyroError::InvalidDuration);
+        }
+        let channel = self.channel_mut(channel)?;
+
+        channel.pin.set_firing(true)?;
+        channel.fire_until_ms = Some(now_ms + duration_ms as u64);
+        channel.awaiting_continuity = false;
+        channel.state.fire = true;
+        channel.state.fire_time_ms = Some(now_ms);
+        channel.state.post_fire_continuity = None;
+        Ok(())
+    }
+
+    /// Releases every output whose ignition pulse has elapsed.
+    pub fn poll(&mut self, now_ms: u64) -> Result<(), PyroError> {
+        let mut result = Ok(());
+        for channel in self.channels.iter_mut() {
+            if matches!(channel.fire_until_ms, Some(until) if now_ms >= until) {
+                result = result.and(Self::release(channel));
+            }
+        }
+        result
+    }
+
+    /// Records a continuity measurement of the channel.
+    pub fn set_test_voltage(&mut self, channel: u8, volts: f32) -> Result<(), PyroError> {
+        let channel = self.channel_mut(channel)?;
+        channel.state.test_voltage = volts;
+        if channel.awaiting_continuity {
+            channel.awaiting_continuity = false;
+            channel.state.post_fire_continuity = Some(volts > CONTINUITY_THRESHOLD_VOLTS);
+        }
+        Ok(())
+    }
+
+    pub fn state(&self) -> PyroState {
+        let channel_state = |i: usize| {
+            self.channels.get(i).map(|c| c.state.clone()).unwrap_or_default()
+        };
+        PyroState {
+            armed: self.armed,
+            channel1: channel_state(0),
+            channel2: channel_state(1),
+        }
+    }
+
+    fn channel_mut(&mut self, channel: u8) -> Result<&mut PyroChannel<P>, PyroError> {
+        (channel as usize).checked_sub(1)
+            .and_then(|i| self.channels.get_mut(i))
+            .ok_or(PyroError::InvalidChannel)
+    }
+
+    fn release(channel: &mut PyroChannel<P>) -> Result<(), PyroError> {
+        channel.pin.set_firing(false)?;
+        channel.fire_until_ms = None;
+        channel.awaiting_continuity = true;
+        channel.state.fire = false;
+        Ok(())
+    }
+}
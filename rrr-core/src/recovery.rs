@@ -0,0 +1,74 @@
+use rrr_api::{FlightState, RecoveryConfig, RecoveryState};
+use crate::hal::PyroOutput;
+use crate::pyro::{PyroController, PyroError};
+
+/// Fires the drogue and main charges according to a [`RecoveryConfig`].
+///
+/// The drogue fires `drogue_delay_ms` after apogee, the main once the rocket
+/// has passed apogee and descended below `main_altitude` above ground. Each
+/// event also fires once its backup timer since launch runs out. An event is
+/// only marked as deployed after the pyro controller accepted the command, so
+/// a refused attempt (e.g. channels not armed) is retried on the next update.
+pub struct RecoverySequencer {
+    state: RecoveryState,
+}
+
+impl RecoverySequencer {
+    pub fn new(config: RecoveryConfig) -> Self {
+        Self { state: RecoveryState { config, ..Default::default() } }
+    }
+
+    pub fn state(&self) -> &RecoveryState {
+        &self.state
+    }
+
+    pub fn config(&self) -> &RecoveryConfig {
+        &self.state.config
+    }
+
+    pub fn set_config(&mut self, config: RecoveryConfig) {
+        self.state.config = config;
+    }
+
+    pub fn update<P: PyroOutput>(
+        &mut self,
+        now_ms: u64,
+        flight: &FlightState,
+        altitude_agl: f32,
+        pyro: &mut PyroController<P>,
+    ) -> Result<(), PyroError> {
+        let launch_ms = match flight.launch_time_ms {
+            Some(launch_ms) => launch_ms,
+            None => {
+                self.state.drogue_deploy_time_ms = None;
+                self.state.main_deploy_time_ms = None;
+                return Ok(());
+            }
+        };
+        let config = &self.state.config;
+        let backup_elapsed = |backup_ms: Option<u32>| {
+            backup_ms.is_some_and(|backup_ms| now_ms >= launch_ms + backup_ms as u64)
+        };
+
+        let drogue_due = flight.apogee_time_ms
+            .is_some_and(|apogee_ms| now_ms >= apogee_ms + config.drogue_delay_ms as u64)
+            || backup_elapsed(config.drogue_backup_ms);
+        let main_due = (flight.apogee_time_ms.is_some() && altitude_agl <= config.main_altitude)
+            || backup_elapsed(config.main_backup_ms);
+
+        let mut result = Ok(());
+        if let (Some(channel), true, None) = (config.drogue_channel, drogue_due, self.state.drogue_deploy_time_ms) {
+            match pyro.fire(channel, config.fire_duration_ms, now_ms) {
+                Ok(_) => self.state.drogue_deploy_time_ms = Some(now_ms),
+                Err(e) => result = Err(e),
+            }
+        }
+        if let (Some(channel), true, None) = (config.main_channel, main_due, self.state.main_deploy_time_ms) {
+            match pyro.fire(channel, config.fire_duration_ms, now_ms) {
+                Ok(_) => self.state.main_deploy_time_ms = Some(now_ms),
+                Err(e) => result = Err(e),
+            }
+        }
+        result
+    }
+}
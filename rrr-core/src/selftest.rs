@@ -0,0 +1,159 @@
+//! Boot-time checks of the peripherals and the preflight readiness derived
+//! from them. The firmware runs the checks before handing the devices to the
+//! sampling loops; readiness is then kept up to date with the sensor health.
+
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
+use log::*;
+use rrr_api::{Check, CheckResult, CheckStatus, SelfTestReport, Sensor, SensorStatus, SlotStatus, State};
+use crate::hal::*;
+use crate::logger::{FlightLogger, LogStorage};
+use crate::pyro::CONTINUITY_THRESHOLD_VOLTS;
+
+/// Below this state of charge, percent, the battery may not last the flight.
+pub const MIN_BATTERY_SOC: f32 = 30.0;
+pub const BATTERY_VOLTAGE_RANGE: RangeInclusive<f32> = 3.0..=4.4;
+/// Pascals, from the top of the BMP280's range to a deep low.
+pub const PRESSURE_RANGE: RangeInclusive<f32> = 30_000.0..=110_000.0;
+/// Degrees Celsius, the BMP280's operating range.
+pub const TEMPERATURE_RANGE: RangeInclusive<f32> = -40.0..=85.0;
+/// Shortest flight the log storage should hold at the flight rate. The
+/// board's 128 KiB `log` partition holds 82 s of 32-byte frames at 20 ms.
+pub const MIN_LOG_DURATION_MS: u64 = 60_000;
+
+#[derive(Default)]
+pub struct SelfTest {
+    checks: Vec<CheckResult>,
+}
+
+impl SelfTest {
+    pub fn checks(&self) -> &[CheckResult] {
+        &self.checks
+    }
+
+    pub fn pass(&mut self, check: Check) {
+        self.record(check, CheckStatus::Pass, None);
+    }
+
+    pub fn warn(&mut self, check: Check, message: String) {
+        self.record(check, CheckStatus::Warn, Some(message));
+    }
+
+    pub fn fail(&mut self, check: Check, message: String) {
+        self.record(check, CheckStatus::Fail, Some(message));
+    }
+
+    fn record(&mut self, check: Check, status: CheckStatus, message: Option<String>) {
+        match (status, &message) {
+            (CheckStatus::Pass, _) | (_, None) => info!("{:?} -- OK", check),
+            (CheckStatus::Warn, Some(message)) => warn!("{:?} -- WARN: {}", check, message),
+            (CheckStatus::Fail, Some(message)) => error!("{:?} -- FAIL: {}", check, message),
+        }
+        self.checks.push(CheckResult { check, status, message });
+    }
+
+    /// Passes unless `result` is an error.
+    pub fn check(&mut self, check: Check, result: Result<(), HalError>) {
+        self.check_result(check, result, |test, ()| test.pass(check));
+    }
+
+    /// Records a failure for `result`'s error, otherwise runs `check_value`.
+    fn check_result<T>(&mut self, check: Check, result: Result<T, HalError>, check_value: impl FnOnce(&mut Self, T)) {
+        match result {
+            Ok(value) => check_value(self, value),
+            Err(e) => self.fail(check, e.to_string()),
+        }
+    }
+
+    pub fn check_fuel_gauge<G: FuelGauge>(&mut self, gauge: &mut G) {
+        self.check_result(Check::FuelGauge, gauge.read(), |test, reading| {
+            if !BATTERY_VOLTAGE_RANGE.contains(&reading.voltage) || !(0.0..=100.0).contains(&reading.soc) {
+                test.fail(Check::FuelGauge, format!("Implausible reading: {:.2} V, {:.0} %", reading.voltage, reading.soc));
+            } else if reading.soc < MIN_BATTERY_SOC {
+                test.fail(Check::FuelGauge, format!("Battery at {:.0} %", reading.soc));
+            } else {
+                test.pass(Check::FuelGauge);
+            }
+        });
+    }
+
+    pub fn check_barometer<B: Barometer>(&mut self, barometer: &mut B) {
+        self.check_result(Check::Barometer, barometer.read(), |test, reading| {
+            if !PRESSURE_RANGE.contains(&reading.pressure) || !TEMPERATURE_RANGE.contains(&reading.temperature) {
+                test.fail(Check::Barometer, format!("Implausible reading: {:.0} Pa, {:.1} °C", reading.pressure, reading.temperature));
+            } else {
+                test.pass(Check::Barometer);
+            }
+        });
+    }
+
+    /// Missing igniters only warn, the board is also powered up without them.
+    pub fn check_continuity<C: ContinuityMonitor>(&mut self, monitor: &mut C) {
+        let mut open = Vec::new();
+        for channel in 1..=monitor.channels() {
+            match monitor.read_volts(channel) {
+                Ok(volts) if volts < CONTINUITY_THRESHOLD_VOLTS => open.push(channel.to_string()),
+                Ok(_) => {}
+                Err(e) => return self.fail(Check::Continuity, format!("Channel {}: {}", channel, e)),
+            }
+        }
+        if open.is_empty() {
+            self.pass(Check::Continuity);
+        } else {
+            self.warn(Check::Continuity, format!("No igniter on channel {}", open.join(", ")));
+        }
+    }
+
+    pub fn check_settings<N: Settings>(&mut self, settings: &mut N) {
+        let result = settings.wifi_networks().and_then(|_| settings.recovery_config()).map(|_| ());
+        self.check(Check::Nvs, result);
+    }
+
+    pub fn check_log<S: LogStorage>(&mut self, logger: Option<&Mutex<FlightLogger<S>>>) {
+        let Some(logger) = logger else {
+            return self.fail(Check::LogStorage, "No log storage".into());
+        };
+        let duration_ms = logger.lock().unwrap().flight_capacity_ms();
+        if duration_ms < MIN_LOG_DURATION_MS {
+            self.warn(Check::LogStorage, format!("Log storage holds only {} s of flight", duration_ms / 1000));
+        } else {
+            self.pass(Check::LogStorage);
+        }
+    }
+
+    /// A new image only warns, it is kept or rolled back once the self-test finished.
+    pub fn check_firmware<F: FirmwareSlot>(&mut self, slot: &F) {
+        self.check_result(Check::OtaPartition, slot.slots(), |test, slots| {
+            match slots.running.map(|running| running.status) {
+                Some(SlotStatus::Valid | SlotStatus::Factory) => test.pass(Check::OtaPartition),
+                Some(SlotStatus::Unverified) => test.warn(Check::OtaPartition, "New firmware is not confirmed yet".into()),
+                status => test.fail(Check::OtaPartition, format!("Running slot is {:?}", status.unwrap_or_default())),
+            }
+        });
+    }
+
+    pub fn finish(self, time_ms: u64) -> SelfTestReport {
+        SelfTestReport { time_ms: Some(time_ms), checks: self.checks, ready_to_fly: false, blocking: Vec::new() }
+    }
+}
+
+/// Derives `ready_to_fly` from the self-test and the current sensor health.
+pub fn update_readiness(state: &mut State) {
+    let report = &mut state.self_test;
+    report.blocking.clear();
+    if report.time_ms.is_none() {
+        report.blocking.push("Self-test has not run".into());
+    }
+    for result in report.checks.iter().filter(|result| result.status == CheckStatus::Fail) {
+        let message = result.message.as_deref().unwrap_or("failed");
+        report.blocking.push(format!("{:?}: {}", result.check, message));
+    }
+    for sensor in [Sensor::Barometer, Sensor::FuelGauge, Sensor::PyroContinuity] {
+        let health = state.health.sensor(sensor);
+        if health.status == SensorStatus::Failed {
+            let message = health.last_error.as_deref().unwrap_or("failed");
+            report.blocking.push(format!("{:?}: {}", sensor, message));
+        }
+    }
+    report.ready_to_fly = report.blocking.is_empty();
+}
@@ -0,0 +1,144 @@
+use rrr_api::{ServoCalibration, ServoState};
+pub use rrr_api::SERVO_PERIOD_US;
+use thiserror::Error;
+use crate::hal::{HalError, ServoOutput};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ServoError {
+    #[error("Servo channel does not exist")]
+    InvalidChannel,
+    #[error("Servo calibration is inconsistent")]
+    InvalidCalibration,
+    #[error("Servo output hardware failure")]
+    HardwareFault,
+}
+
+impl From<HalError> for ServoError {
+    fn from(_: HalError) -> Self {
+        ServoError::HardwareFault
+    }
+}
+
+pub fn check_calibration(calibration: &ServoCalibration) -> Result<(), ServoError> {
+    calibration.validate().map_err(|_| ServoError::InvalidCalibration)
+}
+
+/// Maps normalized positions to pulse widths for one calibrated servo.
+///
+/// Positions are clamped to the calibrated travel limits and the resulting
+/// pulse (including trim) never leaves `min_pulse_us..=max_pulse_us`, so the
+/// servo is never driven into its end stops.
+pub struct Servo {
+    calibration: ServoCalibration,
+    position: Option<f32>,
+}
+
+impl Servo {
+    pub fn new(calibration: ServoCalibration) -> Self {
+        Self { calibration, position: None }
+    }
+
+    pub fn calibration(&self) -> &ServoCalibration {
+        &self.calibration
+    }
+
+    pub fn set_calibration(&mut self, calibration: ServoCalibration) -> Result<(), ServoError> {
+        check_calibration(&calibration)?;
+        self.calibration = calibration;
+        self.position = self.position.map(|p| self.clamp(p));
+        Ok(())
+    }
+
+    pub fn position(&self) -> Option<f32> {
+        self.position
+    }
+
+    /// `None` switches the output off.
+    pub fn set_position(&mut self, position: Option<f32>) {
+        self.position = position.map(|p| self.clamp(p));
+    }
+
+    pub fn pulse_width_us(&self) -> Option<u32> {
+        let c = &self.calibration;
+        self.position.map(|position| {
+            let position = if c.reverse { -position } else { position };
+            let center = c.center_pulse_us as f32;
+            let pulse = if position >= 0.0 {
+                center + position * (c.max_pulse_us as f32 - center)
+            } else {
+                center + position * (center - c.min_pulse_us as f32)
+            };
+            (pulse + c.trim_us as f32)
+                .round()
+                .clamp(c.min_pulse_us as f32, c.max_pulse_us as f32) as u32
+        })
+    }
+
+    /// Duty value for a PWM channel with the given resolution; 0 when off.
+    pub fn duty(&self, max_duty: u32) -> u32 {
+        self.pulse_width_us()
+            .map_or(0, |pulse| (pulse as u64 * max_duty as u64 / SERVO_PERIOD_US as u64) as u32)
+    }
+
+    fn clamp(&self, position: f32) -> f32 {
+        position.clamp(self.calibration.min_position, self.calibration.max_position)
+    }
+}
+
+/// The board's two servo channels, numbered from 1. Every change is written
+/// to the outputs right away.
+pub struct ServoBank<S: ServoOutput> {
+    outputs: [S; 2],
+    servos: [Servo; 2],
+}
+
+impl<S: ServoOutput> ServoBank<S> {
+    /// Starts with both outputs switched off.
+    pub fn new(outputs: [S; 2], calibrations: [ServoCalibration; 2]) -> Result<Self, ServoError> {
+        let [calibration1, calibration2] = calibrations;
+        let mut bank = Self {
+            outputs,
+            servos: [Servo::new(calibration1), Servo::new(calibration2)],
+        };
+        bank.apply()?;
+        Ok(bank)
+    }
+
+    pub fn set_positions(&mut self, positions: [Option<f32>; 2]) -> Result<(), ServoError> {
+        for (servo, position) in self.servos.iter_mut().zip(positions) {
+            servo.set_position(position);
+        }
+        self.apply()
+    }
+
+    pub fn set_calibration(&mut self, channel: u8, calibration: ServoCalibration) -> Result<(), ServoError> {
+        let servo = (channel as usize).checked_sub(1)
+            .and_then(|i| self.servos.get_mut(i))
+            .ok_or(ServoError::InvalidChannel)?;
+        servo.set_calibration(calibration)?;
+        self.apply()
+    }
+
+    pub fn state(&self) -> ServoState {
+        let duty = |i: usize| {
+            let max_duty = self.outputs[i].max_duty();
+            self.servos[i].position().map(|_| self.servos[i].duty(max_duty) as f32 / max_duty as f32)
+        };
+        ServoState {
+            servo1_duty: duty(0),
+            servo2_duty: duty(1),
+            servo1_position: self.servos[0].position(),
+            servo2_position: self.servos[1].position(),
+            servo1_calibration: self.servos[0].calibration().clone(),
+            servo2_calibration: self.servos[1].calibration().clone(),
+        }
+    }
+
+    fn apply(&mut self) -> Result<(), ServoError> {
+        for (output, servo) in self.outputs.iter_mut().zip(&self.servos) {
+            let duty = servo.duty(output.max_duty());
+            output.set_duty(duty)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+//! Ed25519 signatures over firmware images. `rrr-sign` appends a
+//! [`SignatureTrailer`] to the image esptool built; the board checks it against
+//! the public key it was built with before the image is made bootable.
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use ed25519_dalek::{Signature, Signer, SIGNATURE_LENGTH};
+pub use ed25519_dalek::{SigningKey, VerifyingKey, PUBLIC_KEY_LENGTH, SECRET_KEY_LENGTH};
+
+/// First bytes of a [`SignatureTrailer`].
+pub const TRAILER_MAGIC: [u8; 8] = *b"RRRSIG01";
+/// Magic, image size, signing time and signature.
+pub const TRAILER_SIZE: usize = 8 + 4 + 8 + SIGNATURE_LENGTH;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SignatureError {
+    #[error("Image is not signed")]
+    Unsigned,
+    #[error("Signature is for an image of {signed} bytes, not {actual}")]
+    SizeMismatch { signed: u32, actual: usize },
+    #[error("Signature does not match the update key")]
+    Invalid,
+    #[error("Invalid public key")]
+    InvalidKey,
+}
+
+/// Appended to a signed image.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SignatureTrailer {
+    /// Size of the image before the trailer.
+    pub image_size: u32,
+    /// Unix time the image was signed, seconds.
+    pub signed_at: u64,
+    pub signature: [u8; SIGNATURE_LENGTH],
+}
+
+impl SignatureTrailer {
+    pub fn parse(data: &[u8]) -> Result<Self, SignatureError> {
+        if data.len() != TRAILER_SIZE || data[..8] != TRAILER_MAGIC {
+            return Err(SignatureError::Unsigned);
+        }
+        Ok(Self {
+            image_size: u32::from_le_bytes(data[8..12].try_into().unwrap()),
+            signed_at: u64::from_le_bytes(data[12..20].try_into().unwrap()),
+            signature: data[20..].try_into().unwrap(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(TRAILER_SIZE);
+        data.extend_from_slice(&TRAILER_MAGIC);
+        data.extend_from_slice(&self.image_size.to_le_bytes());
+        data.extend_from_slice(&self.signed_at.to_le_bytes());
+        data.extend_from_slice(&self.signature);
+        data
+    }
+}
+
+/// What is signed: the trailer's metadata and the SHA-256 of the image.
+fn signed_message(image_size: u32, signed_at: u64, digest: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(8 + 4 + 8 + digest.len());
+    message.extend_from_slice(&TRAILER_MAGIC);
+    message.extend_from_slice(&image_size.to_le_bytes());
+    message.extend_from_slice(&signed_at.to_le_bytes());
+    message.extend_from_slice(digest);
+    message
+}
+
+/// Returns `image` with a signature trailer appended.
+pub fn sign_image(image: &[u8], key: &SigningKey, signed_at: u64) -> Vec<u8> {
+    let image_size = image.len() as u32;
+    let message = signed_message(image_size, signed_at, &Sha256::digest(image));
+    let trailer = SignatureTrailer { image_size, signed_at, signature: key.sign(&message).to_bytes() };
+    let mut signed = image.to_vec();
+    signed.extend_from_slice(&trailer.to_bytes());
+    signed
+}
+
+/// Verifies a signed image held in memory, see [`SignatureCheck`] for streams.
+pub fn verify_image(signed: &[u8], key: &VerifyingKey) -> Result<SignatureTrailer, SignatureError> {
+    let image_size = signed.len().checked_sub(TRAILER_SIZE).ok_or(SignatureError::Unsigned)?;
+    let mut check = SignatureCheck::default();
+    check.update(&signed[..image_size]);
+    check.verify(&signed[image_size..], image_size, key)
+}
+
+/// Verifies a signed image fed to it piece by piece.
+#[derive(Default)]
+pub struct SignatureCheck {
+    hasher: Sha256,
+}
+
+impl SignatureCheck {
+    /// Hashes the next `data` of the image, without the trailer.
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    /// Checks `trailer` against the image hashed so far, `image_size` bytes.
+    pub fn verify(self, trailer: &[u8], image_size: usize, key: &VerifyingKey) -> Result<SignatureTrailer, SignatureError> {
+        let trailer = SignatureTrailer::parse(trailer)?;
+        if trailer.image_size as usize != image_size {
+            return Err(SignatureError::SizeMismatch { signed: trailer.image_size, actual: image_size });
+        }
+        let message = signed_message(trailer.image_size, trailer.signed_at, &self.hasher.finalize());
+        key.verify_strict(&message, &Signature::from_bytes(&trailer.signature))
+            .map_err(|_| SignatureError::Invalid)?;
+        Ok(trailer)
+    }
+}
+
+/// Parses a public key written as 64 hex digits, as `rrr-sign` prints it.
+pub fn parse_public_key(hex: &str) -> Result<VerifyingKey, SignatureError> {
+    let bytes = from_hex(hex.trim()).ok_or(SignatureError::InvalidKey)?;
+    let bytes: [u8; PUBLIC_KEY_LENGTH] = bytes.try_into().map_err(|_| SignatureError::InvalidKey)?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| SignatureError::InvalidKey)
+}
+
+pub fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
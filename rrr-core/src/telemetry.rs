@@ -0,0 +1,88 @@
+//! The `/ws` telemetry socket, shared by the board's server and the virtual
+//! board. A [`TelemetrySession`] tracks one connection; the servers feed it the
+//! client's frames and send whatever it returns in the session's encoding.
+//! Clients pick the encoding with the frames they send: JSON in text frames,
+//! postcard in binary ones.
+
+use std::sync::Mutex;
+use log::*;
+use rrr_api::{ClientMessage, Command, CommandResponse, Encoding, ServerMessage, State};
+use rrr_api::{MAX_TELEMETRY_PERIOD_MS, MIN_TELEMETRY_PERIOD_MS};
+use crate::avionics::CommandError;
+
+/// Largest client frame accepted, same as a `/command` body.
+pub const MAX_MESSAGE_SIZE: usize = crate::http::MAX_COMMAND_SIZE;
+
+#[derive(Default)]
+pub struct TelemetrySession {
+    encoding: Encoding,
+    period_ms: Option<u32>,
+    next_ms: u64,
+}
+
+impl TelemetrySession {
+    /// Push interval the client subscribed to, `None` before it subscribes.
+    pub fn period_ms(&self) -> Option<u32> {
+        self.period_ms
+    }
+
+    /// Encoding of the client's last frame, used for everything sent to it.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Handles a frame from the client and returns the reply, if any.
+    pub fn handle<F>(&mut self, frame: &[u8], encoding: Encoding, now_ms: u64, command_handler: F) -> Option<ServerMessage>
+        where F: FnOnce(&Command) -> Result<(), CommandError>
+    {
+        self.encoding = encoding;
+        let message = match encoding.decode::<ClientMessage>(frame) {
+            Ok(message) => message,
+            Err(e) => return Some(ServerMessage::Rejected { message: e.to_string() }),
+        };
+        match message {
+            ClientMessage::Subscribe { period_ms } => {
+                let period_ms = period_ms.clamp(MIN_TELEMETRY_PERIOD_MS, MAX_TELEMETRY_PERIOD_MS);
+                info!("Telemetry subscription every {} ms", period_ms);
+                self.period_ms = Some(period_ms);
+                self.next_ms = now_ms;
+                None
+            }
+            ClientMessage::Unsubscribe => {
+                self.period_ms = None;
+                None
+            }
+            ClientMessage::Command { id, command } => {
+                let response = match command_handler(&command) {
+                    Ok(()) => CommandResponse::Ok,
+                    Err(e) => {
+                        error!("Command failed: {}", e);
+                        CommandResponse::Failed { error: e.api_error(), message: e.to_string() }
+                    }
+                };
+                Some(ServerMessage::CommandResponse { id, response })
+            }
+        }
+    }
+
+    /// The state to push if one is due.
+    pub fn poll(&mut self, now_ms: u64, state: &Mutex<State>) -> Option<ServerMessage> {
+        let period_ms = self.period_ms?;
+        if now_ms < self.next_ms {
+            return None;
+        }
+        // Skips pushes missed while the transport was busy instead of bursting them.
+        self.next_ms = (self.next_ms + period_ms as u64).max(now_ms + 1);
+        Some(ServerMessage::State(Box::new(state.lock().unwrap().clone())))
+    }
+
+    /// Milliseconds until the next push is due, `None` when not subscribed.
+    pub fn wait_ms(&self, now_ms: u64) -> Option<u64> {
+        self.period_ms.map(|_| self.next_ms.saturating_sub(now_ms))
+    }
+}
+
+/// Reply to a frame larger than [`MAX_MESSAGE_SIZE`].
+pub fn message_too_large() -> ServerMessage {
+    ServerMessage::Rejected { message: "Message too large".into() }
+}
@@ -0,0 +1,58 @@
+//! The Wi-Fi networks the board joins. They are stored in priority order and
+//! tried one after the other at boot; the board opens its own access point
+//! only when none of them connects.
+
+use rrr_api::{WifiCredentials, WifiNetwork, MAX_WIFI_NETWORKS};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum WifiError {
+    #[error("At most {} networks can be stored", MAX_WIFI_NETWORKS)]
+    TooManyNetworks,
+    #[error("Network {0} is not stored")]
+    NotStored(String),
+}
+
+/// Stores `credentials` after the other networks, or replaces the password of
+/// the stored network with the same SSID, which keeps its priority.
+pub fn add_network(networks: &mut Vec<WifiCredentials>, credentials: WifiCredentials) -> Result<(), WifiError> {
+    if let Some(stored) = networks.iter_mut().find(|stored| stored.ssid == credentials.ssid) {
+        stored.password = credentials.password;
+        return Ok(());
+    }
+    if networks.len() >= MAX_WIFI_NETWORKS {
+        return Err(WifiError::TooManyNetworks);
+    }
+    networks.push(credentials);
+    Ok(())
+}
+
+pub fn remove_network(networks: &mut Vec<WifiCredentials>, ssid: &str) -> Result<(), WifiError> {
+    let index = position(networks, ssid)?;
+    networks.remove(index);
+    Ok(())
+}
+
+/// Moves the network to `priority`, or to the end of the list if it is shorter.
+pub fn move_network(networks: &mut Vec<WifiCredentials>, ssid: &str, priority: usize) -> Result<(), WifiError> {
+    let network = networks.remove(position(networks, ssid)?);
+    networks.insert(priority.min(networks.len()), network);
+    Ok(())
+}
+
+fn position(networks: &[WifiCredentials], ssid: &str) -> Result<usize, WifiError> {
+    networks.iter().position(|network| network.ssid == ssid).ok_or_else(|| WifiError::NotStored(ssid.into()))
+}
+
+/// SSIDs as reported in `State::wifi_networks`.
+pub fn ssids(networks: &[WifiCredentials]) -> Vec<String> {
+    networks.iter().map(|network| network.ssid.clone()).collect()
+}
+
+/// The order to try the stored networks in: those a scan found first, then
+/// the others, which may be hidden. Each part keeps the stored priority.
+pub fn connection_order(networks: &[WifiCredentials], found: &[WifiNetwork]) -> Vec<WifiCredentials> {
+    let (in_range, others): (Vec<_>, Vec<_>) = networks.iter().cloned()
+        .partition(|network| found.iter().any(|found| found.ssid == network.ssid));
+    in_range.into_iter().chain(others).collect()
+}
@@ -0,0 +1,99 @@
+use rrr_core::altitude::*;
+
+fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+    assert!((actual - expected).abs() <= tolerance, "{actual} is not within {tolerance} of {expected}");
+}
+
+#[test]
+fn matches_isa_table() {
+    assert_close(pressure_altitude(STANDARD_PRESSURE, STANDARD_PRESSURE), 0.0, 0.01);
+    assert_close(pressure_altitude(89_874.6, STANDARD_PRESSURE), 1_000.0, 1.0);
+    assert_close(pressure_altitude(79_495.2, STANDARD_PRESSURE), 2_000.0, 1.0);
+    assert_close(pressure_altitude(54_019.9, STANDARD_PRESSURE), 5_000.0, 2.0);
+}
+
+#[test]
+fn sea_level_pressure_inverts_pressure_altitude() {
+    let qnh = sea_level_pressure(95_000.0, 600.0);
+    assert_close(pressure_altitude(95_000.0, qnh), 600.0, 0.1);
+}
+
+#[test]
+fn altitude_pressure_inverts_pressure_altitude() {
+    assert_close(altitude_pressure(1_000.0, STANDARD_PRESSURE), 89_874.6, 5.0);
+    assert_close(pressure_altitude(altitude_pressure(734.0, 99_000.0), 99_000.0), 734.0, 0.1);
+}
+
+#[test]
+fn update_returns_standard_altitude() {
+    let mut altimeter = Altimeter::default();
+    altimeter.set_qnh(99_000.0);
+
+    assert_close(altimeter.update(89_874.6), 1_000.0, 1.0);
+}
+
+#[test]
+fn reading_at_converts_standard_altitude() {
+    let mut altimeter = Altimeter::default();
+    altimeter.set_qnh(102_000.0);
+    let standard = altimeter.update(94_000.0);
+
+    assert_eq!(altimeter.reading_at(standard).altitude_msl, altimeter.reading(94_000.0).altitude_msl);
+}
+
+#[test]
+fn unzeroed_altimeter_reports_msl_as_agl() {
+    let altimeter = Altimeter::default();
+    let reading = altimeter.reading(89_874.6);
+
+    assert_eq!(altimeter.ground_pressure(), None);
+    assert_close(reading.altitude_msl, 1_000.0, 1.0);
+    assert_close(reading.altitude_agl, reading.altitude_msl, 0.001);
+}
+
+#[test]
+fn zeroing_averages_samples() {
+    let mut altimeter = Altimeter::default();
+    altimeter.zero(4);
+    for pressure in [95_000.0, 95_010.0, 94_990.0] {
+        altimeter.update(pressure);
+        assert!(altimeter.is_zeroing());
+    }
+    altimeter.update(95_000.0);
+    let reading = altimeter.reading(95_000.0);
+
+    assert!(!altimeter.is_zeroing());
+    assert_eq!(altimeter.ground_pressure(), Some(95_000.0));
+    assert_close(reading.altitude_agl, 0.0, 0.01);
+    assert_close(altimeter.reading(93_900.0).altitude_agl, 98.0, 2.0);
+}
+
+#[test]
+fn qnh_shifts_msl_but_not_agl() {
+    let mut altimeter = Altimeter::default();
+    altimeter.zero(1);
+    altimeter.update(95_000.0);
+    let standard = altimeter.reading(94_000.0);
+
+    altimeter.set_qnh(102_000.0);
+    let corrected = altimeter.reading(94_000.0);
+
+    assert_close(corrected.altitude_msl - standard.altitude_msl, 56.0, 2.0);
+    assert_close(corrected.altitude_agl, standard.altitude_agl, 0.5);
+}
+
+#[test]
+fn field_elevation_sets_ground_msl() {
+    let mut altimeter = Altimeter::default();
+    altimeter.set_field_elevation(350.0);
+    altimeter.zero(1);
+    altimeter.update(96_500.0);
+    let reading = altimeter.reading(96_500.0);
+
+    assert_close(reading.altitude_msl, 350.0, 0.1);
+    assert_close(altimeter.ground_altitude_msl(), 350.0, 0.1);
+    assert_close(reading.altitude_agl, 0.0, 0.01);
+
+    altimeter.set_qnh(STANDARD_PRESSURE);
+    assert_eq!(altimeter.qnh(), STANDARD_PRESSURE);
+}
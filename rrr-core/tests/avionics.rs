@@ -0,0 +1,293 @@
+use rrr_api::{ApiError, Command, FlightPhase, RecoveryConfig, ServoCalibration, WifiCredentials};
+use rrr_api::{RESET_CONFIRMATION, RESET_NVS_CONFIRMATION};
+use rrr_core::avionics::*;
+use rrr_core::hal::{BatteryReading, HalError};
+use rrr_core::mock::*;
+
+type TestAvionics = Avionics<MockPyroOutput, MockServoOutput, MockStatusLed, MemorySettings>;
+
+struct Board {
+    pyro: [MockPyroOutput; 2],
+    servos: [MockServoOutput; 2],
+    led: MockStatusLed,
+    settings: MemorySettings,
+}
+
+fn board() -> Board {
+    Board {
+        pyro: Default::default(),
+        servos: Default::default(),
+        led: Default::default(),
+        settings: Default::default(),
+    }
+}
+
+fn avionics(board: &Board) -> TestAvionics {
+    Avionics::new(board.pyro.to_vec(), board.servos.clone(), board.led.clone(), board.settings.clone()).unwrap()
+}
+
+#[test]
+fn outputs_start_safe() {
+    let board = board();
+    let avionics = avionics(&board);
+
+    assert!(board.pyro.iter().all(|pin| pin.history() == vec![false]));
+    assert!(board.servos.iter().all(|servo| servo.duty().is_some()));
+    assert!(!avionics.state().lock().unwrap().pyro.armed);
+}
+
+#[test]
+fn stored_configuration_is_loaded() {
+    let board = board();
+    let mut settings = board.settings.clone();
+    let config = RecoveryConfig { drogue_channel: Some(2), ..Default::default() };
+    let calibration = ServoCalibration { reverse: true, ..Default::default() };
+    rrr_core::hal::Settings::set_recovery_config(&mut settings, &config).unwrap();
+    rrr_core::hal::Settings::set_servo_calibration(&mut settings, 1, &calibration).unwrap();
+
+    let avionics = avionics(&board);
+    let state = avionics.state().lock().unwrap().clone();
+    assert_eq!(state.recovery.config, config);
+    assert_eq!(state.servo.servo2_calibration, calibration);
+}
+
+#[test]
+fn unreadable_settings_fall_back_to_defaults() {
+    let board = board();
+    let mut settings = board.settings.clone();
+    let config = RecoveryConfig { drogue_channel: Some(2), ..Default::default() };
+    rrr_core::hal::Settings::set_recovery_config(&mut settings, &config).unwrap();
+    board.settings.fail(HalError::StorageFailed);
+
+    let avionics = avionics(&board);
+    let state = avionics.state().lock().unwrap().clone();
+    assert_eq!(state.recovery.config, RecoveryConfig::default());
+    assert_eq!(state.servo.servo2_calibration, ServoCalibration::default());
+    assert!(state.wifi_networks.is_empty());
+    assert!(avionics.wifi_credentials().is_empty());
+}
+
+#[test]
+fn barometer_samples_update_state() {
+    let board = board();
+    let avionics = avionics(&board);
+    let mut barometer = MockBarometer::default();
+    barometer.set(97_000.0, 15.5);
+
+    let mut sampler = avionics.barometer_sampler();
+    for i in 0..10 {
+        sampler.sample(&mut barometer, i * BAROMETER_PERIOD_MS).unwrap();
+    }
+
+    let state = avionics.state().lock().unwrap().clone();
+    assert_eq!(state.barometer.pressure, 97_000.0);
+    assert_eq!(state.barometer.temperature, 15.5);
+    assert!(state.barometer.altitude_msl > 300.0);
+
+    barometer.fail(HalError::NoResponse);
+    assert_eq!(sampler.sample(&mut barometer, 1000), Err(HalError::NoResponse));
+}
+
+#[test]
+fn battery_and_continuity_are_sampled() {
+    let board = board();
+    let avionics = avionics(&board);
+    let mut gauge = MockFuelGauge::default();
+    gauge.set(BatteryReading { soc: 55.0, voltage: 3.8, charge_rate: -2.0 });
+    let mut continuity = MockContinuity::new(2);
+    continuity.set_volts(1, 3.2);
+
+    avionics.sample_battery(&mut gauge).unwrap();
+    avionics.sample_continuity(&mut continuity).unwrap();
+
+    let state = avionics.state().lock().unwrap().clone();
+    assert_eq!(state.battery.soc, 55.0);
+    assert_eq!(state.battery.voltage, 3.8);
+    assert_eq!(state.pyro.channel1.test_voltage, 3.2);
+    assert_eq!(state.pyro.channel2.test_voltage, 0.0);
+
+    continuity.fail(2, HalError::InvalidReading);
+    continuity.set_volts(1, 0.1);
+    assert_eq!(avionics.sample_continuity(&mut continuity), Err(HalError::InvalidReading));
+    assert_eq!(avionics.state().lock().unwrap().pyro.channel1.test_voltage, 0.1);
+}
+
+#[test]
+fn fire_command_pulses_output_until_polled() {
+    let board = board();
+    let avionics = avionics(&board);
+    let fire = Command::FirePyro { channel: 1, duration_ms: 100 };
+
+    assert!(avionics.handle_command(&fire).is_err());
+    assert!(!board.pyro[0].is_firing());
+
+    avionics.handle_command(&Command::ArmPyro).unwrap();
+    avionics.handle_command(&fire).unwrap();
+    assert!(board.pyro[0].is_firing());
+    assert!(avionics.state().lock().unwrap().pyro.channel1.fire);
+
+    avionics.poll_pyro(avionics.now_ms() + 100);
+    assert!(!board.pyro[0].is_firing());
+    assert!(!avionics.state().lock().unwrap().pyro.channel1.fire);
+}
+
+#[test]
+fn settings_commands_are_persisted() {
+    let board = board();
+    let avionics = avionics(&board);
+    let config = RecoveryConfig { main_channel: Some(1), ..Default::default() };
+    let calibration = ServoCalibration { trim_us: 25, ..Default::default() };
+
+    avionics.handle_command(&Command::AddWifiNetwork { ssid: "field".into(), password: "rocketry".into() }).unwrap();
+    avionics.handle_command(&Command::SetRecoveryConfig { config: config.clone() }).unwrap();
+    avionics.handle_command(&Command::SetServoCalibration { channel: 2, calibration: calibration.clone() }).unwrap();
+
+    let stored = board.settings.stored();
+    assert_eq!(stored.wifi, [WifiCredentials { ssid: "field".into(), password: "rocketry".into() }]);
+    assert_eq!(stored.recovery, Some(config.clone()));
+    assert_eq!(stored.servo_calibration, [None, Some(calibration.clone())]);
+
+    let state = avionics.state().lock().unwrap().clone();
+    assert_eq!(state.recovery.config, config);
+    assert_eq!(state.servo.servo2_calibration, calibration);
+
+    // A calibration that was not saved is not applied either.
+    avionics.handle_command(&Command::SetServoPosition { position_1: None, position_2: Some(0.5) }).unwrap();
+    board.settings.fail(HalError::StorageFailed);
+    let duty = board.servos[1].duty();
+    let unsaved = ServoCalibration { trim_us: -40, ..Default::default() };
+    assert!(avionics.handle_command(&Command::SetServoCalibration { channel: 2, calibration: unsaved }).is_err());
+    assert_eq!(avionics.state().lock().unwrap().servo.servo2_calibration, calibration);
+    assert_eq!(board.servos[1].duty(), duty);
+}
+
+#[test]
+fn wifi_networks_are_stored_in_order() {
+    let board = board();
+    let avionics = avionics(&board);
+    let add = |ssid: &str| Command::AddWifiNetwork { ssid: ssid.into(), password: "rocketry".into() };
+    for ssid in ["field", "hotspot", "club"] {
+        avionics.handle_command(&add(ssid)).unwrap();
+    }
+    avionics.handle_command(&Command::MoveWifiNetwork { ssid: "club".into(), priority: 0 }).unwrap();
+    avionics.handle_command(&Command::RemoveWifiNetwork { ssid: "field".into() }).unwrap();
+    assert_eq!(avionics.state().lock().unwrap().wifi_networks, ["club", "hotspot"]);
+
+    let error = avionics.handle_command(&Command::RemoveWifiNetwork { ssid: "field".into() }).unwrap_err();
+    assert_eq!(error.api_error(), ApiError::InvalidArgument);
+    assert_eq!(error.to_string(), "Network field is not stored");
+
+    // Read back at the next boot.
+    let stored: Vec<String> = board.settings.stored().wifi.into_iter().map(|network| network.ssid).collect();
+    assert_eq!(stored, ["club", "hotspot"]);
+    assert_eq!(rrr_core::wifi::ssids(&avionics.wifi_credentials()), ["club", "hotspot"]);
+    assert_eq!(self::avionics(&board).state().lock().unwrap().wifi_networks, ["club", "hotspot"]);
+}
+
+#[test]
+fn reset_commands_need_confirmation_and_ground() {
+    let board = board();
+    let avionics = avionics(&board);
+    let reset = Command::Reset { confirm: RESET_CONFIRMATION.into() };
+    let error = avionics.handle_command(&Command::Reset { confirm: "yes".into() }).unwrap_err();
+    assert_eq!(error.api_error(), ApiError::InvalidArgument);
+    assert_eq!(avionics.reset_request(), None);
+
+    avionics.state().lock().unwrap().flight.phase = FlightPhase::Descent;
+    assert_eq!(avionics.handle_command(&reset).unwrap_err().api_error(), ApiError::InFlight);
+    assert_eq!(avionics.reset_request(), None);
+
+    avionics.state().lock().unwrap().flight.phase = FlightPhase::Landed;
+    let waiter = avionics.clone();
+    let waiting = std::thread::spawn(move || waiter.wait_for_reset());
+    avionics.handle_command(&reset).unwrap();
+    assert_eq!(waiting.join().unwrap(), ResetRequest::Restart);
+}
+
+#[test]
+fn reset_nvs_restores_defaults() {
+    let board = board();
+    let avionics = avionics(&board);
+    let config = RecoveryConfig { main_channel: Some(1), ..Default::default() };
+    let calibration = ServoCalibration { trim_us: 25, ..Default::default() };
+    avionics.handle_command(&Command::AddWifiNetwork { ssid: "field".into(), password: "rocketry".into() }).unwrap();
+    avionics.handle_command(&Command::SetRecoveryConfig { config }).unwrap();
+    avionics.handle_command(&Command::SetServoCalibration { channel: 1, calibration }).unwrap();
+
+    avionics.handle_command(&Command::ResetNvs { confirm: RESET_NVS_CONFIRMATION.into() }).unwrap();
+    let stored = board.settings.stored();
+    assert!(stored.wifi.is_empty() && stored.recovery.is_none());
+    assert_eq!(stored.servo_calibration, [None, None]);
+    let state = avionics.state().lock().unwrap().clone();
+    assert_eq!(state.recovery.config, RecoveryConfig::default());
+    assert_eq!(state.servo.servo1_calibration, ServoCalibration::default());
+    assert!(state.wifi_networks.is_empty());
+    assert_eq!(avionics.reset_request(), Some(ResetRequest::FactoryReset));
+}
+
+#[test]
+fn servo_and_led_commands_drive_outputs() {
+    let board = board();
+    let avionics = avionics(&board);
+    avionics.handle_command(&Command::SetServoPosition { position_1: Some(0.0), position_2: None }).unwrap();
+    let center = board.servos[0].duty();
+
+    avionics.handle_command(&Command::SetServoPosition { position_1: Some(1.0), position_2: None }).unwrap();
+    assert!(board.servos[0].duty() > center);
+    assert_eq!(avionics.state().lock().unwrap().servo.servo1_position, Some(1.0));
+
+    avionics.handle_command(&Command::SetPwmDutyCycle { duty_1: Some(0.0), duty_2: None }).unwrap();
+    assert!(board.servos[0].duty() < center);
+
+    avionics.handle_command(&Command::SetLedColor { r: 1, g: 2, b: 3 }).unwrap();
+    assert_eq!(board.led.color(), Some((1, 2, 3)));
+}
+
+#[test]
+fn command_errors_map_to_api_errors() {
+    let board = board();
+    let avionics = avionics(&board);
+    let error = |command: Command| avionics.handle_command(&command).unwrap_err().api_error();
+
+    assert_eq!(error(Command::FirePyro { channel: 1, duration_ms: 100 }), ApiError::NotArmed);
+    avionics.handle_command(&Command::ArmPyro).unwrap();
+    assert_eq!(error(Command::FirePyro { channel: 3, duration_ms: 100 }), ApiError::InvalidArgument);
+    let calibration = ServoCalibration { min_pulse_us: 1600, ..Default::default() };
+    assert_eq!(error(Command::SetServoCalibration { channel: 1, calibration }), ApiError::InvalidArgument);
+}
+
+#[test]
+fn sections_are_stamped_and_go_stale() {
+    let board = board();
+    let avionics = avionics(&board);
+    let mut gauge = MockFuelGauge::default();
+    let mut continuity = MockContinuity::new(2);
+    let samples = || avionics.state().lock().unwrap().samples.clone();
+
+    avionics.sample_battery(&mut gauge).unwrap();
+    avionics.sample_battery(&mut gauge).unwrap();
+    avionics.sample_continuity(&mut continuity).unwrap();
+    assert_eq!(samples().battery.sequence, 2);
+    assert_eq!(samples().pyro.sequence, 1);
+    assert_eq!(samples().barometer.time_ms, None);
+
+    // Failed reads and pyro commands leave the continuity stamp alone.
+    continuity.fail(1, HalError::NoResponse);
+    assert!(avionics.sample_continuity(&mut continuity).is_err());
+    avionics.handle_command(&Command::ArmPyro).unwrap();
+    assert_eq!(samples().pyro.sequence, 1);
+    avionics.handle_command(&Command::SetServoPosition { position_1: Some(0.5), position_2: None }).unwrap();
+    assert_eq!(samples().servo.sequence, 1);
+
+    let deadline = samples().battery.time_ms.unwrap() + BATTERY_PERIOD_MS * STALE_AFTER_PERIODS;
+    avionics.check_staleness(deadline);
+    assert!(!samples().battery.stale);
+    assert!(samples().barometer.stale);
+    assert!(!samples().servo.stale);
+
+    avionics.check_staleness(deadline + 1);
+    assert!(samples().battery.stale);
+    assert_eq!(avionics.state().lock().unwrap().time_ms, deadline + 1);
+    avionics.sample_battery(&mut gauge).unwrap();
+    assert!(!samples().battery.stale);
+}
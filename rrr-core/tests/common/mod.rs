@@ -0,0 +1,92 @@
+//! Synthetic flight traces with known ground truth.
+
+#![allow(dead_code)]
+
+pub const SAMPLE_PERIOD_MS: u64 = 20;
+pub const GROUND_ALTITUDE: f32 = 250.0;
+pub const PAD_TIME_MS: u64 = 10_000;
+pub const BURN_TIME_MS: u64 = 1_500;
+pub const THRUST_ACCELERATION: f32 = 60.0;
+pub const DESCENT_VELOCITY: f32 = -15.0;
+pub const GRAVITY: f32 = 9.81;
+
+#[derive(Clone, Copy, Debug)]
+pub struct TruthSample {
+    pub time_ms: u64,
+    pub altitude: f32,
+    pub velocity: f32,
+    pub acceleration: f32,
+}
+
+/// Deterministic pseudo random noise so traces are reproducible without extra dependencies.
+pub struct Noise(u64);
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Uniformly distributed value in `-amplitude..amplitude`.
+    pub fn next(&mut self, amplitude: f32) -> f32 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let unit = (self.0 >> 40) as f32 / (1u64 << 24) as f32;
+        (unit * 2.0 - 1.0) * amplitude
+    }
+}
+
+/// Pad wait, constant-thrust boost, ballistic coast, constant-rate descent and
+/// some time lying on the ground.
+pub fn flight_profile() -> Vec<TruthSample> {
+    let dt = SAMPLE_PERIOD_MS as f32 / 1000.0;
+    let mut samples = Vec::new();
+    let mut altitude = 0.0f32;
+    let mut velocity = 0.0f32;
+    let mut landed_at: Option<u64> = None;
+    let mut time_ms = 0;
+
+    loop {
+        let acceleration = if time_ms < PAD_TIME_MS {
+            0.0
+        } else if time_ms < PAD_TIME_MS + BURN_TIME_MS {
+            THRUST_ACCELERATION - GRAVITY
+        } else if velocity > DESCENT_VELOCITY {
+            -GRAVITY
+        } else {
+            0.0
+        };
+
+        samples.push(TruthSample { time_ms, altitude: GROUND_ALTITUDE + altitude, velocity, acceleration });
+
+        if landed_at.is_none() {
+            velocity += acceleration * dt;
+            if velocity < DESCENT_VELOCITY { velocity = DESCENT_VELOCITY; }
+            altitude += velocity * dt;
+            if time_ms > PAD_TIME_MS && altitude <= 0.0 {
+                altitude = 0.0;
+                velocity = 0.0;
+                landed_at = Some(time_ms);
+            }
+        }
+
+        if let Some(landed_at) = landed_at {
+            if time_ms > landed_at + 20_000 { break; }
+        }
+        time_ms += SAMPLE_PERIOD_MS;
+    }
+
+    samples
+}
+
+pub fn apogee_time_ms(trace: &[TruthSample]) -> u64 {
+    trace.iter()
+        .max_by(|a, b| a.altitude.total_cmp(&b.altitude))
+        .unwrap()
+        .time_ms
+}
+
+pub fn landing_time_ms(trace: &[TruthSample]) -> u64 {
+    trace.iter()
+        .find(|s| s.time_ms > PAD_TIME_MS && s.altitude <= GROUND_ALTITUDE)
+        .unwrap()
+        .time_ms
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::*;
+use rrr_core::estimator::{AltitudeEstimator, Estimate, EstimatorConfig};
+
+fn run(config: EstimatorConfig, trace: &[TruthSample], noise_amplitude: f32) -> Vec<Estimate> {
+    let mut noise = Noise::new(7);
+    let mut estimator = AltitudeEstimator::new(config);
+    trace.iter()
+        .map(|s| estimator.update(s.time_ms, s.altitude + noise.next(noise_amplitude)))
+        .collect()
+}
+
+fn rms(values: impl Iterator<Item=f32>) -> f32 {
+    let (sum, count) = values.fold((0.0f32, 0), |(sum, count), v| (sum + v * v, count + 1));
+    (sum / count as f32).sqrt()
+}
+
+fn in_flight(trace: &[TruthSample]) -> impl Iterator<Item=usize> + '_ {
+    let landing = landing_time_ms(trace);
+    (0..trace.len()).filter(move |&i| trace[i].time_ms > PAD_TIME_MS && trace[i].time_ms < landing)
+}
+
+#[test]
+fn converges_on_noise_free_trace() {
+    let trace = flight_profile();
+    let estimates = run(EstimatorConfig::default(), &trace, 0.0);
+
+    let pad_end = trace.iter().position(|s| s.time_ms == PAD_TIME_MS).unwrap();
+    assert!((estimates[pad_end].altitude - GROUND_ALTITUDE).abs() < 0.01);
+    assert!(estimates[pad_end].vertical_velocity.abs() < 0.01);
+}
+
+#[test]
+fn tracks_altitude_and_velocity_in_flight() {
+    let trace = flight_profile();
+    let estimates = run(EstimatorConfig::default(), &trace, 0.5);
+
+    let altitude_error = rms(in_flight(&trace).map(|i| estimates[i].altitude - trace[i].altitude));
+    let velocity_error = rms(in_flight(&trace).map(|i| estimates[i].vertical_velocity - trace[i].velocity));
+
+    assert!(altitude_error < 0.5, "altitude rms error {altitude_error}");
+    assert!(velocity_error < 3.0, "velocity rms error {velocity_error}");
+}
+
+#[test]
+fn follows_boost_without_lag() {
+    let trace = flight_profile();
+    let estimates = run(EstimatorConfig::default(), &trace, 0.5);
+
+    let burnout = trace.iter().position(|s| s.time_ms == PAD_TIME_MS + BURN_TIME_MS).unwrap();
+    assert!((estimates[burnout].altitude - trace[burnout].altitude).abs() < 1.0);
+    assert!((estimates[burnout].vertical_velocity - trace[burnout].velocity).abs() < 5.0);
+}
+
+#[test]
+fn estimates_coast_deceleration() {
+    let trace = flight_profile();
+    let estimates = run(EstimatorConfig::default(), &trace, 0.5);
+
+    let coast_start = PAD_TIME_MS + BURN_TIME_MS + 1_000;
+    let coast: Vec<_> = (0..trace.len())
+        .filter(|&i| trace[i].time_ms > coast_start && trace[i].time_ms < coast_start + 4_000)
+        .collect();
+    let mean = coast.iter().map(|&i| estimates[i].vertical_acceleration).sum::<f32>() / coast.len() as f32;
+    assert!((mean + GRAVITY).abs() < 1.0, "mean coast acceleration {mean}");
+}
+
+#[test]
+fn lower_process_noise_smooths_more() {
+    let trace: Vec<_> = flight_profile().into_iter()
+        .take_while(|s| s.time_ms < PAD_TIME_MS)
+        .collect();
+    let smooth = run(EstimatorConfig { process_noise: 1.0, ..Default::default() }, &trace, 1.0);
+    let agile = run(EstimatorConfig { process_noise: 5000.0, ..Default::default() }, &trace, 1.0);
+
+    let settled = trace.len() / 2;
+    let smooth_error = rms(smooth[settled..].iter().map(|e| e.altitude - GROUND_ALTITUDE));
+    let agile_error = rms(agile[settled..].iter().map(|e| e.altitude - GROUND_ALTITUDE));
+    assert!(smooth_error < agile_error, "{smooth_error} vs {agile_error}");
+}
+
+#[test]
+fn reset_reinitialises_on_next_sample() {
+    let mut estimator = AltitudeEstimator::new(EstimatorConfig::default());
+    estimator.update(0, 100.0);
+    estimator.update(20, 100.0);
+    estimator.reset();
+
+    let estimate = estimator.update(40, 300.0);
+    assert_eq!(estimate, Estimate { altitude: 300.0, vertical_velocity: 0.0, vertical_acceleration: 0.0 });
+}
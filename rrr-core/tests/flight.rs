@@ -0,0 +1,89 @@
+mod common;
+
+use common::*;
+use rrr_api::FlightPhase;
+use rrr_core::estimator::{AltitudeEstimator, EstimatorConfig};
+use rrr_core::flight::{FlightConfig, FlightStateMachine};
+
+fn run(trace: &[TruthSample], noise_amplitude: f32) -> (FlightStateMachine, Vec<FlightPhase>) {
+    let mut noise = Noise::new(42);
+    let mut estimator = AltitudeEstimator::new(EstimatorConfig::default());
+    let mut machine = FlightStateMachine::new(FlightConfig::default());
+    let mut phases = Vec::new();
+    for sample in trace {
+        let estimate = estimator.update(sample.time_ms, sample.altitude + noise.next(noise_amplitude));
+        if let Some(phase) = machine.update(sample.time_ms, &estimate) {
+            phases.push(phase);
+        }
+    }
+    (machine, phases)
+}
+
+#[test]
+fn detects_every_phase_in_order() {
+    let trace = flight_profile();
+    let (_, phases) = run(&trace, 0.5);
+
+    assert_eq!(phases, vec![
+        FlightPhase::Armed,
+        FlightPhase::Boost,
+        FlightPhase::Coast,
+        FlightPhase::Apogee,
+        FlightPhase::Descent,
+        FlightPhase::Landed,
+    ]);
+}
+
+#[test]
+fn transition_times_match_ground_truth() {
+    let trace = flight_profile();
+    let (machine, _) = run(&trace, 0.5);
+    let state = machine.state();
+
+    let launch = state.launch_time_ms.unwrap();
+    assert!(launch > PAD_TIME_MS && launch < PAD_TIME_MS + 1_000, "launch at {launch}");
+
+    let burnout = state.burnout_time_ms.unwrap();
+    let true_burnout = PAD_TIME_MS + BURN_TIME_MS;
+    assert!(burnout >= true_burnout && burnout < true_burnout + 1_500, "burnout at {burnout}");
+
+    let apogee = state.apogee_time_ms.unwrap();
+    let true_apogee = apogee_time_ms(&trace);
+    assert!(apogee.abs_diff(true_apogee) < 1_500, "apogee at {apogee}, expected {true_apogee}");
+
+    let landing = state.landing_time_ms.unwrap();
+    let true_landing = landing_time_ms(&trace);
+    assert!(landing > true_landing && landing < true_landing + 10_000, "landing at {landing}");
+}
+
+#[test]
+fn reports_ground_and_max_altitude() {
+    let trace = flight_profile();
+    let (machine, _) = run(&trace, 0.5);
+    let state = machine.state();
+
+    let true_max = trace.iter().map(|s| s.altitude).fold(f32::MIN, f32::max) - GROUND_ALTITUDE;
+    assert!((state.ground_altitude - GROUND_ALTITUDE).abs() < 1.0);
+    assert!((state.max_altitude - true_max).abs() < 3.0, "max {} vs {}", state.max_altitude, true_max);
+}
+
+#[test]
+fn pad_noise_does_not_trigger_launch() {
+    let trace: Vec<_> = flight_profile().into_iter()
+        .take_while(|s| s.time_ms < PAD_TIME_MS)
+        .collect();
+    let (machine, phases) = run(&trace, 3.0);
+
+    assert_eq!(phases, vec![FlightPhase::Armed]);
+    assert_eq!(machine.phase(), FlightPhase::Armed);
+}
+
+#[test]
+fn reset_returns_to_idle() {
+    let trace = flight_profile();
+    let (mut machine, _) = run(&trace, 0.5);
+    machine.reset();
+
+    assert_eq!(machine.phase(), FlightPhase::Idle);
+    assert_eq!(machine.state().launch_time_ms, None);
+}
@@ -0,0 +1,87 @@
+use rrr_api::{Sensor, SensorStatus};
+use rrr_core::hal::{Barometer, HalError};
+use rrr_core::health::*;
+use rrr_core::mock::MockBarometer;
+
+fn read(barometer: &mut MockBarometer) -> Result<(), HalError> {
+    barometer.read().map(|_| ())
+}
+
+#[test]
+fn errors_degrade_then_fail_the_sensor() {
+    let mut barometer = MockBarometer::default();
+    let mut supervisor = SensorSupervisor::new(Sensor::Barometer);
+    assert!(supervisor.run(&mut barometer, 0, MockBarometer::reinit, read) == Some(Ok(())));
+    assert_eq!(supervisor.health().status, SensorStatus::Ok);
+
+    barometer.fail(HalError::NoResponse);
+    for i in 1..FAILED_AFTER_ERRORS {
+        assert!(supervisor.run(&mut barometer, i as u64, MockBarometer::reinit, read).unwrap().is_err());
+        assert_eq!(supervisor.health().status, SensorStatus::Degraded);
+    }
+    supervisor.run(&mut barometer, 10, MockBarometer::reinit, read);
+    let health = supervisor.health();
+    assert_eq!(health.status, SensorStatus::Failed);
+    assert_eq!(health.consecutive_errors, FAILED_AFTER_ERRORS);
+    assert_eq!(health.last_error.as_deref(), Some("Device did not respond"));
+    assert_eq!(health.last_error_ms, Some(10));
+    assert_eq!(barometer.reinits(), 0);
+}
+
+#[test]
+fn failed_sensor_is_reinitialised_with_backoff() {
+    let mut barometer = MockBarometer::default();
+    let mut supervisor = SensorSupervisor::new(Sensor::Barometer);
+    barometer.fail(HalError::NoResponse);
+    for _ in 0..FAILED_AFTER_ERRORS {
+        supervisor.run(&mut barometer, 0, MockBarometer::reinit, read);
+    }
+
+    // Backs off, doubling the delay after every failed re-initialisation.
+    assert!(supervisor.run(&mut barometer, INITIAL_BACKOFF_MS - 1, MockBarometer::reinit, read).is_none());
+    assert!(supervisor.run(&mut barometer, INITIAL_BACKOFF_MS, MockBarometer::reinit, read).is_some());
+    assert_eq!(barometer.reinits(), 1);
+    let next_ms = INITIAL_BACKOFF_MS * 3;
+    assert!(supervisor.run(&mut barometer, next_ms - 1, MockBarometer::reinit, read).is_none());
+    assert!(supervisor.run(&mut barometer, next_ms, MockBarometer::reinit, read).is_some());
+    assert_eq!(barometer.reinits(), 2);
+    assert_eq!(supervisor.health().reinits, 2);
+
+    barometer.set(100_000.0, 20.0);
+    let recovered_ms = next_ms + INITIAL_BACKOFF_MS * 4;
+    assert!(supervisor.run(&mut barometer, recovered_ms, MockBarometer::reinit, read) == Some(Ok(())));
+    let health = supervisor.health();
+    assert_eq!(health.status, SensorStatus::Ok);
+    assert_eq!(health.consecutive_errors, 0);
+    assert_eq!(health.total_errors, FAILED_AFTER_ERRORS + 2);
+    assert_eq!(barometer.reinits(), 3);
+
+    // The backoff starts over after a recovery.
+    barometer.fail(HalError::InvalidReading);
+    for _ in 0..FAILED_AFTER_ERRORS {
+        supervisor.run(&mut barometer, recovered_ms, MockBarometer::reinit, read);
+    }
+    assert!(supervisor.run(&mut barometer, recovered_ms + INITIAL_BACKOFF_MS, MockBarometer::reinit, read).is_some());
+}
+
+#[test]
+fn backoff_is_capped() {
+    let mut barometer = MockBarometer::default();
+    let mut supervisor = SensorSupervisor::new(Sensor::Barometer);
+    barometer.fail(HalError::NoResponse);
+    let mut now_ms = 0;
+    for _ in 0..FAILED_AFTER_ERRORS {
+        supervisor.run(&mut barometer, now_ms, MockBarometer::reinit, read);
+    }
+    for _ in 0..20 {
+        while supervisor.run(&mut barometer, now_ms, MockBarometer::reinit, read).is_none() {
+            now_ms += 1;
+        }
+    }
+    let reinit_ms = now_ms;
+    now_ms += 1;
+    while supervisor.run(&mut barometer, now_ms, MockBarometer::reinit, read).is_none() {
+        now_ms += 1;
+    }
+    assert_eq!(now_ms - reinit_ms, MAX_BACKOFF_MS);
+}
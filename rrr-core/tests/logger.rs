@@ -0,0 +1,214 @@
+use rrr_api::FlightPhase;
+use rrr_core::logger::*;
+use rrr_core::mock::MemoryStorage;
+use rrr_log::{LogHeader, LogRecord};
+
+fn stored_records(storage: &MemoryStorage) -> Vec<LogRecord> {
+    let mut records: Vec<_> = rrr_log::decode(&storage.data).into_iter().flat_map(|s| s.records).collect();
+    records.sort_by_key(|r| r.sequence);
+    records
+}
+
+fn header() -> LogHeader {
+    LogHeader { firmware_version: "0.0.1".into(), qnh: 101_325.0, ..Default::default() }
+}
+
+fn logger(sectors: usize) -> FlightLogger<MemoryStorage> {
+    FlightLogger::new(MemoryStorage::new(sectors, 4096), LoggerConfig::default(), header()).unwrap()
+}
+
+fn sample(time_ms: u32, phase: FlightPhase) -> LogRecord {
+    LogRecord { time_ms, phase, altitude_agl: time_ms as f32 / 100.0, ..Default::default() }
+}
+
+#[test]
+fn pad_is_logged_at_low_rate() {
+    let mut logger = logger(4);
+    for t in (0..10_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Armed)).unwrap();
+    }
+
+    let records = stored_records(logger.storage());
+    assert_eq!(records.len(), 10);
+    assert!(records.windows(2).all(|w| w[1].time_ms - w[0].time_ms == 1000));
+}
+
+#[test]
+fn flush_writes_the_latest_pad_sample() {
+    let mut logger = logger(4);
+    for t in (0..1_500).step_by(20) {
+        logger.log(sample(t, FlightPhase::Idle)).unwrap();
+    }
+    logger.flush().unwrap();
+    logger.flush().unwrap();
+
+    let records = stored_records(logger.storage());
+    let times: Vec<_> = records.iter().map(|r| r.time_ms).collect();
+    assert_eq!(times, [0, 1000, 1480]);
+}
+
+#[test]
+fn flight_keeps_pre_launch_history_at_full_rate() {
+    let mut logger = logger(8);
+    for t in (0..10_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Armed)).unwrap();
+    }
+    for t in (10_000..12_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Boost)).unwrap();
+    }
+    assert!(logger.state().recording_fast);
+
+    let mut records = stored_records(logger.storage());
+    assert!(records.windows(2).all(|w| w[1].sequence > w[0].sequence));
+
+    records.sort_by_key(|r| r.time_ms);
+    let fast: Vec<_> = records.iter().filter(|r| r.time_ms >= 7_000).collect();
+    assert_eq!(fast.len(), 250);
+    assert!(fast.windows(2).all(|w| w[1].time_ms - w[0].time_ms == 20));
+    assert_eq!(records.iter().filter(|r| r.time_ms < 7_000).count(), 7);
+}
+
+#[test]
+fn landing_returns_to_low_rate() {
+    let mut logger = logger(8);
+    for t in (0..1_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Descent)).unwrap();
+    }
+    for t in (1_000..10_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Landed)).unwrap();
+    }
+
+    assert!(!logger.state().recording_fast);
+    let landed = stored_records(logger.storage()).into_iter().filter(|r| r.phase == FlightPhase::Landed).count();
+    assert_eq!(landed, 9);
+}
+
+#[test]
+fn wraps_around_and_overwrites_oldest() {
+    let mut logger = logger(2);
+    let capacity = logger.state().capacity_records;
+    for i in 0..capacity * 3 {
+        logger.log(sample(i * 20, FlightPhase::Boost)).unwrap();
+    }
+
+    let records = stored_records(logger.storage());
+    let header_frames = header().encode(0, 0).len() as u32;
+    let last = records.last().unwrap().sequence;
+    assert_eq!(last, capacity * 3 + 2 * header_frames - 1);
+    assert!(records.len() as u32 > capacity / 2);
+    assert!(records.windows(2).all(|w| w[1].sequence > w[0].sequence));
+}
+
+#[test]
+fn resumes_after_restart_with_new_session() {
+    let mut logger = logger(4);
+    for t in (0..1_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Boost)).unwrap();
+    }
+    let storage = logger.storage().clone();
+
+    let mut logger = FlightLogger::new(storage, LoggerConfig::default(), header()).unwrap();
+    assert_eq!(logger.state().session, 1);
+    for t in (0..1_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Boost)).unwrap();
+    }
+
+    let records = stored_records(logger.storage());
+    assert_eq!(records.len(), 100);
+    assert!(records.windows(2).all(|w| w[1].sequence > w[0].sequence));
+    assert_eq!(records.iter().filter(|r| r.session == 1).count(), 50);
+}
+
+#[test]
+fn header_is_written_at_startup_and_launch() {
+    let mut logger = logger(4);
+    for t in (0..2_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Armed)).unwrap();
+    }
+    logger.set_header(LogHeader { qnh: 99_000.0, ..header() });
+    for t in (2_000..3_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Boost)).unwrap();
+    }
+
+    let sessions = rrr_log::decode(&logger.storage().data);
+    assert_eq!(sessions.len(), 1);
+    let header = sessions[0].header.clone().unwrap();
+    assert_eq!(header.qnh, 99_000.0);
+    assert_eq!(header.firmware_version, "0.0.1");
+    assert_eq!(header.flight_interval_ms, LoggerConfig::default().flight_interval_ms);
+    assert_eq!(sessions[0].corrupted_frames, 0);
+}
+
+fn two_sessions() -> FlightLogger<MemoryStorage> {
+    let mut logger = logger(8);
+    for t in (0..1_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Boost)).unwrap();
+    }
+    let storage = logger.storage().clone();
+
+    let mut logger = FlightLogger::new(storage, LoggerConfig::default(), header()).unwrap();
+    for t in (0..5_000).step_by(20) {
+        logger.log(sample(t, FlightPhase::Armed)).unwrap();
+    }
+    logger
+}
+
+#[test]
+fn lists_sessions_oldest_first() {
+    let mut logger = two_sessions();
+    let sessions = logger.sessions().unwrap();
+
+    assert_eq!(sessions.iter().map(|s| s.id).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(sessions[0].records, 50);
+    assert_eq!(sessions[0].duration_ms, 980);
+    assert!((sessions[0].max_altitude - 9.8).abs() < 1e-3);
+    assert!(!sessions[0].active);
+    assert_eq!(sessions[1].records, 5);
+    assert!(sessions[1].active);
+    assert!(sessions[0].size > 50 * rrr_log::FRAME_SIZE as u32);
+}
+
+#[test]
+fn reads_session_in_small_chunks() {
+    let mut logger = two_sessions();
+    let size = logger.sessions().unwrap()[0].size as usize;
+
+    let mut reader = logger.reader(0);
+    let mut data = Vec::new();
+    let mut buf = [0u8; 100];
+    loop {
+        let n = logger.read(&mut reader, &mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+
+    assert_eq!(data.len(), size);
+    let sessions = rrr_log::decode(&data);
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].records.len(), 50);
+    assert!(sessions[0].header.is_some());
+}
+
+#[test]
+fn deletes_past_sessions_only() {
+    let mut logger = two_sessions();
+    assert_eq!(logger.delete_session(1), Err(LogError::SessionActive));
+    assert_eq!(logger.delete_session(9), Err(LogError::SessionNotFound));
+
+    logger.delete_session(0).unwrap();
+    let sessions = logger.sessions().unwrap();
+    assert_eq!(sessions.iter().map(|s| s.id).collect::<Vec<_>>(), vec![1]);
+    assert_eq!(logger.delete_session(0), Err(LogError::SessionNotFound));
+
+    let storage = logger.storage().clone();
+    let logger = FlightLogger::new(storage, LoggerConfig::default(), header()).unwrap();
+    assert_eq!(logger.state().session, 2);
+}
+
+#[test]
+fn rejects_tiny_storage() {
+    let result = FlightLogger::new(MemoryStorage::new(0, 4096), LoggerConfig::default(), header());
+    assert!(matches!(result, Err(LogError::StorageTooSmall)));
+}
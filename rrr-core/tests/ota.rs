@@ -0,0 +1,240 @@
+use std::sync::{Arc, Mutex};
+use rrr_api::{ApiError, Check, CheckResult, CheckStatus, CommandResponse, FirmwareSlots, FlightPhase, OtaStatus, SlotInfo, SlotStatus, State};
+use rrr_core::http::post_ota;
+use rrr_core::hal::BatteryReading;
+use rrr_core::mock::{firmware_image, MemoryStorage, MockBarometer, MockFirmwareSlot, MockFuelGauge};
+use rrr_core::ota::*;
+use rrr_core::selftest::SelfTest;
+use rrr_core::signing::{sign_image, SignatureError, SigningKey, TRAILER_SIZE};
+
+fn updater(capacity: u32) -> (FirmwareUpdater<MockFirmwareSlot>, MockFirmwareSlot, Arc<Mutex<State>>) {
+    let slot = MockFirmwareSlot::new(capacity, "1.0.0");
+    let state = Arc::new(Mutex::new(State::default()));
+    let updater = FirmwareUpdater::new(slot.clone(), state.clone(), ESP32C3_CHIP_ID, Some(key().verifying_key()));
+    (updater, slot, state)
+}
+
+fn key() -> SigningKey {
+    SigningKey::from_bytes(&[7; 32])
+}
+
+/// A signed image of `size` bytes in total.
+fn image(size: usize) -> Vec<u8> {
+    sign(&firmware_image("1.1.0", ESP32C3_CHIP_ID, size - TRAILER_SIZE))
+}
+
+fn sign(image: &[u8]) -> Vec<u8> {
+    sign_image(image, &key(), 0)
+}
+
+/// Reads `body` in pieces of at most `piece` bytes.
+fn reader(body: &[u8], piece: usize) -> impl FnMut(&mut [u8]) -> Result<usize, String> + '_ {
+    let mut offset = 0;
+    move |buffer| {
+        let n = buffer.len().min(piece).min(body.len() - offset);
+        buffer[..n].copy_from_slice(&body[offset..offset + n]);
+        offset += n;
+        Ok(n)
+    }
+}
+
+fn running(status: SlotStatus) -> FirmwareSlots {
+    FirmwareSlots {
+        running: Some(SlotInfo { label: "ota_1".into(), version: Some("1.1.0".into()), status }),
+        previous: Some(SlotInfo { label: "ota_0".into(), version: Some("1.0.0".into()), status: SlotStatus::Valid }),
+    }
+}
+
+#[test]
+fn image_is_written_in_chunks() {
+    let (mut updater, slot, state) = updater(100_000);
+    let image = image(10_000);
+    // Pieces smaller than the header, which is then checked across reads.
+    updater.update(image.len(), reader(&image, 100)).unwrap();
+    assert_eq!(slot.image().as_deref(), Some(&image[..10_000 - TRAILER_SIZE]));
+    let state = state.lock().unwrap().clone();
+    assert_eq!(state.ota.status, OtaStatus::Complete);
+    assert_eq!((state.ota.received, state.ota.size), (10_000, 10_000));
+    assert_eq!(state.ota.version.as_deref(), Some("1.1.0"));
+
+    let previous = state.firmware.previous.unwrap();
+    assert_eq!((previous.version.as_deref(), previous.status), (Some("1.1.0"), SlotStatus::Unverified));
+    assert_eq!(state.firmware.running.unwrap().version.as_deref(), Some("1.0.0"));
+}
+
+#[test]
+fn header_is_parsed() {
+    let header = ImageHeader::parse(&image(1000)).unwrap();
+    assert_eq!(header.version, "1.1.0");
+    assert_eq!(header.project_name, "rrr");
+    assert_eq!(header.chip_id, ESP32C3_CHIP_ID);
+    assert!(header.hash_appended);
+    assert!(ImageHeader::parse(&[0; IMAGE_HEADER_SIZE]).is_err());
+}
+
+#[test]
+fn short_upload_is_aborted() {
+    let (mut updater, slot, state) = updater(100_000);
+    let image = image(8000);
+    let error = updater.update(8000, reader(&image[..5000], OTA_CHUNK_SIZE)).unwrap_err();
+    assert_eq!(error, OtaError::Interrupted { received: 5000, expected: 8000 });
+    assert_eq!(slot.image(), None);
+    assert!(!slot.is_writing());
+    let ota = state.lock().unwrap().ota.clone();
+    assert_eq!(ota.status, OtaStatus::Failed);
+    assert_eq!(ota.received, 5000);
+    assert_eq!(ota.error.as_deref(), Some("Upload ended after 5000 of 8000 bytes"));
+
+    let error = updater.update(1000, |_: &mut [u8]| Err("connection reset")).unwrap_err();
+    assert_eq!(error, OtaError::Connection("connection reset".into()));
+    assert!(!slot.is_writing());
+}
+
+#[test]
+fn corrupt_images_are_refused() {
+    let (mut updater, slot, _) = updater(100_000);
+    let mut corrupt = image(5000);
+    corrupt[3000] ^= 1;
+    assert_eq!(updater.update(5000, reader(&corrupt, 700)), Err(OtaError::InvalidImage("SHA-256 does not match".into())));
+
+    let other_chip = sign(&firmware_image("1.1.0", 0, 5000));
+    assert!(matches!(updater.update(other_chip.len(), reader(&other_chip, 700)), Err(OtaError::InvalidImage(_))));
+    let mut no_hash = firmware_image("1.1.0", ESP32C3_CHIP_ID, 5000);
+    no_hash[23] = 0;
+    let no_hash = sign(&no_hash);
+    assert!(matches!(updater.update(no_hash.len(), reader(&no_hash, 700)), Err(OtaError::InvalidImage(_))));
+    let not_an_image = sign(&[0xE9; 5000]);
+    assert!(matches!(updater.update(not_an_image.len(), reader(&not_an_image, 700)), Err(OtaError::InvalidImage(_))));
+    assert_eq!(slot.image(), None);
+    assert!(!slot.is_writing());
+}
+
+#[test]
+fn unsigned_images_are_refused() {
+    let (mut updater, slot, _) = updater(100_000);
+    let unsigned = firmware_image("1.1.0", ESP32C3_CHIP_ID, 5000);
+    assert_eq!(updater.update(5000, reader(&unsigned, 700)), Err(OtaError::Signature(SignatureError::Unsigned)));
+
+    let other_key = SigningKey::from_bytes(&[8; 32]);
+    let foreign = sign_image(&unsigned, &other_key, 0);
+    let error = updater.update(foreign.len(), reader(&foreign, 700)).unwrap_err();
+    assert_eq!(error, OtaError::Signature(SignatureError::Invalid));
+    assert_eq!(error.api_error(), ApiError::Unauthorized);
+    assert_eq!(slot.image(), None);
+    assert!(!slot.is_writing());
+
+    // Without a key no image is accepted.
+    let state = Arc::new(Mutex::new(State::default()));
+    let mut updater = FirmwareUpdater::new(slot.clone(), state, ESP32C3_CHIP_ID, None);
+    let signed = image(5000);
+    assert_eq!(updater.update(5000, reader(&signed, 700)), Err(OtaError::NoUpdateKey));
+}
+
+#[test]
+fn oversized_and_rejected_images_fail() {
+    let (mut updater, slot, _) = updater(1000);
+    let image = image(1001 + TRAILER_SIZE);
+    assert_eq!(updater.update(image.len(), reader(&image, 100)), Err(OtaError::TooLarge { size: 1001, capacity: 1000 }));
+    assert_eq!(updater.update(0, reader(&image, 100)), Err(OtaError::Empty));
+    assert!(matches!(updater.update(100, reader(&image, 100)), Err(OtaError::InvalidImage(_))));
+
+    slot.reject(true);
+    let image = self::image(1000 + TRAILER_SIZE);
+    let error = updater.update(image.len(), reader(&image, 100)).unwrap_err();
+    assert_eq!(error.api_error(), ApiError::InvalidArgument);
+    assert_eq!(slot.image(), None);
+}
+
+#[test]
+fn image_filling_the_slot_fits() {
+    let (mut updater, slot, _) = updater(1000);
+    let image = image(1000 + TRAILER_SIZE);
+    updater.update(image.len(), reader(&image, 100)).unwrap();
+    assert_eq!(slot.image().map(|image| image.len()), Some(1000));
+}
+
+#[test]
+fn no_update_in_flight() {
+    let (mut updater, slot, state) = updater(1000);
+    state.lock().unwrap().flight.phase = FlightPhase::Coast;
+    let image = image(500);
+    assert_eq!(updater.update(500, reader(&image, 100)), Err(OtaError::InFlight));
+    assert_eq!(slot.image(), None);
+    assert_eq!(state.lock().unwrap().ota.status, OtaStatus::Idle);
+}
+
+#[test]
+fn new_image_is_kept_after_self_test() {
+    let (mut updater, slot, state) = updater(1000);
+    slot.set_slots(running(SlotStatus::Unverified));
+    let mut test = SelfTest::default();
+    test.check_firmware(&slot);
+    assert_eq!(test.checks()[0].status, CheckStatus::Warn);
+    state.lock().unwrap().self_test = test.finish(100);
+
+    updater.confirm_boot().unwrap();
+    assert!(!slot.rolled_back());
+    let firmware = state.lock().unwrap().firmware.clone();
+    assert_eq!(firmware.running.unwrap().status, SlotStatus::Valid);
+    assert_eq!(firmware.previous.unwrap().version.as_deref(), Some("1.0.0"));
+}
+
+#[test]
+fn new_image_is_rolled_back_after_failed_self_test() {
+    let (mut updater, slot, state) = updater(1000);
+    slot.set_slots(running(SlotStatus::Unverified));
+    let mut test = SelfTest::default();
+    test.fail(Check::Barometer, "Device did not respond".into());
+    state.lock().unwrap().self_test = test.finish(100);
+
+    updater.confirm_boot().unwrap();
+    assert!(slot.rolled_back());
+
+    // A valid image stays whatever the self-test says.
+    let (mut updater, slot, state) = self::updater(1000);
+    state.lock().unwrap().self_test = SelfTest::default().finish(100);
+    state.lock().unwrap().self_test.checks.push(CheckResult {
+        check: Check::Led,
+        status: CheckStatus::Fail,
+        message: None,
+    });
+    updater.confirm_boot().unwrap();
+    assert!(!slot.rolled_back());
+    assert_eq!(state.lock().unwrap().firmware.running.as_ref().unwrap().status, SlotStatus::Valid);
+}
+
+#[test]
+fn low_battery_does_not_roll_back() {
+    let (mut updater, slot, state) = updater(1000);
+    slot.set_slots(running(SlotStatus::Unverified));
+    let gauge = MockFuelGauge::default();
+    gauge.set(BatteryReading { soc: 12.0, voltage: 3.5, charge_rate: 0.0 });
+    let mut test = SelfTest::default();
+    test.check_fuel_gauge(&mut gauge.clone());
+    test.check_log::<MemoryStorage>(None);
+    test.check_barometer(&mut MockBarometer::default());
+    state.lock().unwrap().self_test = test.finish(100);
+
+    updater.confirm_boot().unwrap();
+    assert!(!slot.rolled_back());
+    assert_eq!(state.lock().unwrap().firmware.running.as_ref().unwrap().status, SlotStatus::Valid);
+}
+
+#[test]
+fn post_ota_statuses() {
+    let (updater, _, _) = updater(1000);
+    let updater = Mutex::new(updater);
+    let image = image(600);
+    assert_eq!(post_ota(&updater, Some(600), reader(&image, 100)).status, 200);
+    assert_eq!(post_ota(&updater, None, reader(&image, 100)).status, 411);
+    assert_eq!(post_ota(&updater, Some(2000), reader(&image, 100)).status, 413);
+    let unsigned = firmware_image("1.1.0", ESP32C3_CHIP_ID, 600);
+    assert_eq!(post_ota(&updater, Some(600), reader(&unsigned, 100)).status, 403);
+    assert_eq!(post_ota(&updater, Some(700), reader(&image, 100)).status, 400);
+
+    let _upload = updater.lock().unwrap();
+    let response = post_ota(&updater, Some(600), reader(&image, 100));
+    assert_eq!(response.status, 409);
+    let body: CommandResponse = serde_json::from_slice(&response.body).unwrap();
+    assert!(matches!(body, CommandResponse::Failed { error: ApiError::Busy, .. }));
+}
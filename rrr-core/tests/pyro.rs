@@ -0,0 +1,94 @@
+use rrr_core::mock::MockPyroOutput as MockPin;
+use rrr_core::pyro::*;
+
+fn controller() -> (PyroController<MockPin>, MockPin, MockPin) {
+    let (pin1, pin2) = (MockPin::default(), MockPin::default());
+    let controller = PyroController::new(vec![pin1.clone(), pin2.clone()]).unwrap();
+    (controller, pin1, pin2)
+}
+
+#[test]
+fn outputs_start_low_and_disarmed() {
+    let (controller, pin1, pin2) = controller();
+
+    assert_eq!(pin1.history(), vec![false]);
+    assert_eq!(pin2.history(), vec![false]);
+    assert!(!controller.state().armed);
+}
+
+#[test]
+fn refuses_to_fire_when_disarmed() {
+    let (mut controller, pin1, _) = controller();
+
+    assert_eq!(controller.fire(1, DEFAULT_FIRE_DURATION_MS, 0), Err(PyroError::NotArmed));
+    assert!(!pin1.is_firing());
+    assert!(!controller.state().channel1.fire);
+}
+
+#[test]
+fn rejects_unknown_channel_and_bad_duration() {
+    let (mut controller, _, _) = controller();
+    controller.arm();
+
+    assert_eq!(controller.fire(0, DEFAULT_FIRE_DURATION_MS, 0), Err(PyroError::InvalidChannel));
+    assert_eq!(controller.fire(3, DEFAULT_FIRE_DURATION_MS, 0), Err(PyroError::InvalidChannel));
+    assert_eq!(controller.fire(1, MIN_FIRE_DURATION_MS - 1, 0), Err(PyroError::InvalidDuration));
+    assert_eq!(controller.fire(1, MAX_FIRE_DURATION_MS + 1, 0), Err(PyroError::InvalidDuration));
+}
+
+#[test]
+fn pulse_is_released_after_duration() {
+    let (mut controller, pin1, pin2) = controller();
+    controller.arm();
+    controller.fire(1, 500, 1_000).unwrap();
+
+    assert!(pin1.is_firing());
+    assert!(!pin2.is_firing());
+    assert_eq!(controller.state().channel1.fire_time_ms, Some(1_000));
+
+    controller.poll(1_499).unwrap();
+    assert!(pin1.is_firing());
+    assert!(controller.state().channel1.fire);
+
+    controller.poll(1_500).unwrap();
+    assert!(!pin1.is_firing());
+    assert!(!controller.state().channel1.fire);
+    assert!(controller.is_armed());
+}
+
+#[test]
+fn disarm_cuts_pulse_in_progress() {
+    let (mut controller, pin1, _) = controller();
+    controller.arm();
+    controller.fire(1, 1_000, 0).unwrap();
+    controller.disarm().unwrap();
+
+    assert!(!pin1.is_firing());
+    assert!(!controller.state().armed);
+    assert_eq!(controller.fire(1, 1_000, 10), Err(PyroError::NotArmed));
+}
+
+#[test]
+fn reports_continuity_after_fire() {
+    let (mut controller, _, _) = controller();
+    controller.set_test_voltage(1, 3.0).unwrap();
+    controller.arm();
+    controller.fire(1, 100, 0).unwrap();
+
+    controller.set_test_voltage(1, 3.0).unwrap();
+    assert_eq!(controller.state().channel1.post_fire_continuity, None);
+
+    controller.poll(100).unwrap();
+    controller.set_test_voltage(1, 0.1).unwrap();
+    assert_eq!(controller.state().channel1.post_fire_continuity, Some(false));
+
+    controller.set_test_voltage(1, 3.0).unwrap();
+    assert_eq!(controller.state().channel1.post_fire_continuity, Some(false));
+    assert_eq!(controller.state().channel1.test_voltage, 3.0);
+}
+
+#[test]
+fn hardware_fault_is_reported() {
+    let pin = MockPin::broken();
+    assert!(matches!(PyroController::new(vec![pin]), Err(PyroError::HardwareFault)));
+}
@@ -0,0 +1,120 @@
+use rrr_api::{FlightPhase, FlightState, RecoveryConfig};
+use rrr_core::mock::MockPyroOutput as MockPin;
+use rrr_core::pyro::{PyroController, PyroError};
+use rrr_core::recovery::RecoverySequencer;
+
+const LAUNCH_MS: u64 = 10_000;
+const APOGEE_MS: u64 = 20_000;
+
+fn setup(config: RecoveryConfig) -> (RecoverySequencer, PyroController<MockPin>, MockPin, MockPin) {
+    let (drogue, main) = (MockPin::default(), MockPin::default());
+    let mut pyro = PyroController::new(vec![drogue.clone(), main.clone()]).unwrap();
+    pyro.arm();
+    (RecoverySequencer::new(config), pyro, drogue, main)
+}
+
+fn dual_deploy() -> RecoveryConfig {
+    RecoveryConfig {
+        drogue_channel: Some(1),
+        drogue_delay_ms: 1_000,
+        main_channel: Some(2),
+        main_altitude: 150.0,
+        ..Default::default()
+    }
+}
+
+fn coasting() -> FlightState {
+    FlightState { phase: FlightPhase::Coast, launch_time_ms: Some(LAUNCH_MS), ..Default::default() }
+}
+
+fn descending() -> FlightState {
+    FlightState { phase: FlightPhase::Descent, apogee_time_ms: Some(APOGEE_MS), ..coasting() }
+}
+
+#[test]
+fn nothing_fires_before_launch() {
+    let config = RecoveryConfig { drogue_backup_ms: Some(0), main_backup_ms: Some(0), ..dual_deploy() };
+    let (mut recovery, mut pyro, drogue, main) = setup(config);
+    recovery.update(100_000, &FlightState::default(), 0.0, &mut pyro).unwrap();
+
+    assert!(!drogue.is_firing());
+    assert!(!main.is_firing());
+}
+
+#[test]
+fn main_altitude_is_ignored_on_the_way_up() {
+    let (mut recovery, mut pyro, _, main) = setup(dual_deploy());
+    recovery.update(LAUNCH_MS + 500, &coasting(), 50.0, &mut pyro).unwrap();
+
+    assert!(!main.is_firing());
+}
+
+#[test]
+fn drogue_fires_after_apogee_delay() {
+    let (mut recovery, mut pyro, drogue, main) = setup(dual_deploy());
+    recovery.update(APOGEE_MS + 999, &descending(), 500.0, &mut pyro).unwrap();
+    assert!(!drogue.is_firing());
+
+    recovery.update(APOGEE_MS + 1_000, &descending(), 490.0, &mut pyro).unwrap();
+    assert!(drogue.is_firing());
+    assert!(!main.is_firing());
+    assert_eq!(recovery.state().drogue_deploy_time_ms, Some(APOGEE_MS + 1_000));
+}
+
+#[test]
+fn main_fires_at_configured_altitude() {
+    let (mut recovery, mut pyro, _, main) = setup(dual_deploy());
+    recovery.update(APOGEE_MS + 5_000, &descending(), 151.0, &mut pyro).unwrap();
+    assert!(!main.is_firing());
+
+    recovery.update(APOGEE_MS + 5_020, &descending(), 149.5, &mut pyro).unwrap();
+    assert!(main.is_firing());
+    assert_eq!(recovery.state().main_deploy_time_ms, Some(APOGEE_MS + 5_020));
+}
+
+#[test]
+fn backup_timers_fire_without_apogee() {
+    let config = RecoveryConfig { drogue_backup_ms: Some(12_000), main_backup_ms: Some(30_000), ..dual_deploy() };
+    let (mut recovery, mut pyro, drogue, main) = setup(config);
+
+    recovery.update(LAUNCH_MS + 12_000, &coasting(), 800.0, &mut pyro).unwrap();
+    assert!(drogue.is_firing());
+    assert!(!main.is_firing());
+
+    recovery.update(LAUNCH_MS + 30_000, &coasting(), 800.0, &mut pyro).unwrap();
+    assert!(main.is_firing());
+}
+
+#[test]
+fn each_event_fires_once() {
+    let (mut recovery, mut pyro, drogue, _) = setup(dual_deploy());
+    recovery.update(APOGEE_MS + 1_000, &descending(), 400.0, &mut pyro).unwrap();
+    pyro.poll(APOGEE_MS + 2_000).unwrap();
+    recovery.update(APOGEE_MS + 3_000, &descending(), 380.0, &mut pyro).unwrap();
+
+    assert_eq!(drogue.history(), vec![false, true, false]);
+}
+
+#[test]
+fn refused_fire_is_retried() {
+    let (mut recovery, mut pyro, drogue, _) = setup(dual_deploy());
+    pyro.disarm().unwrap();
+
+    let result = recovery.update(APOGEE_MS + 1_000, &descending(), 400.0, &mut pyro);
+    assert_eq!(result, Err(PyroError::NotArmed));
+    assert_eq!(recovery.state().drogue_deploy_time_ms, None);
+
+    pyro.arm();
+    recovery.update(APOGEE_MS + 1_020, &descending(), 400.0, &mut pyro).unwrap();
+    assert!(drogue.is_firing());
+}
+
+#[test]
+fn disabled_events_never_fire() {
+    let config = RecoveryConfig { drogue_channel: None, main_channel: None, ..dual_deploy() };
+    let (mut recovery, mut pyro, drogue, main) = setup(config);
+    recovery.update(APOGEE_MS + 60_000, &descending(), 0.0, &mut pyro).unwrap();
+
+    assert!(!drogue.is_firing());
+    assert!(!main.is_firing());
+}
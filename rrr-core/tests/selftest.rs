@@ -0,0 +1,99 @@
+use std::sync::Mutex;
+use rrr_api::{Check, CheckStatus, SensorStatus, State};
+use rrr_core::hal::{BatteryReading, HalError};
+use rrr_core::logger::{FlightLogger, LoggerConfig};
+use rrr_core::mock::*;
+use rrr_core::selftest::*;
+
+fn status(test: &SelfTest, check: Check) -> CheckStatus {
+    test.checks().iter().find(|result| result.check == check).unwrap().status
+}
+
+#[test]
+fn healthy_board_is_ready() {
+    let mut test = SelfTest::default();
+    test.check_fuel_gauge(&mut MockFuelGauge::default());
+    test.check_barometer(&mut MockBarometer::default());
+    let continuity = MockContinuity::new(1);
+    continuity.set_volts(1, 3.3);
+    test.check_continuity(&mut continuity.clone());
+    test.check_settings(&mut MemorySettings::default());
+    test.check(Check::Led, Ok(()));
+    assert!(test.checks().iter().all(|result| result.status == CheckStatus::Pass));
+
+    let mut state = State::default();
+    update_readiness(&mut state);
+    assert!(!state.self_test.ready_to_fly);
+    assert_eq!(state.self_test.blocking, ["Self-test has not run"]);
+
+    state.self_test = test.finish(1200);
+    update_readiness(&mut state);
+    assert!(state.self_test.ready_to_fly);
+    assert!(state.self_test.blocking.is_empty());
+}
+
+#[test]
+fn implausible_readings_fail() {
+    let mut test = SelfTest::default();
+    let barometer = MockBarometer::default();
+    barometer.set(0.0, 20.0);
+    test.check_barometer(&mut barometer.clone());
+    assert_eq!(status(&test, Check::Barometer), CheckStatus::Fail);
+
+    let gauge = MockFuelGauge::default();
+    gauge.set(BatteryReading { soc: 12.0, voltage: 3.5, charge_rate: 0.0 });
+    test.check_fuel_gauge(&mut gauge.clone());
+    assert_eq!(status(&test, Check::FuelGauge), CheckStatus::Fail);
+
+    let mut state = State { self_test: test.finish(0), ..Default::default() };
+    update_readiness(&mut state);
+    assert!(!state.self_test.ready_to_fly);
+    assert_eq!(state.self_test.blocking.len(), 2);
+    assert_eq!(state.self_test.blocking[1], "FuelGauge: Battery at 12 %");
+}
+
+#[test]
+fn missing_igniters_and_small_log_only_warn() {
+    let mut test = SelfTest::default();
+    test.check_continuity(&mut MockContinuity::new(2));
+    let logger = FlightLogger::new(MemoryStorage::new(4, 4096), LoggerConfig::default(), Default::default()).unwrap();
+    test.check_log(Some(&Mutex::new(logger)));
+    assert_eq!(test.checks()[0].message.as_deref(), Some("No igniter on channel 1, 2"));
+    assert!(test.checks().iter().all(|result| result.status == CheckStatus::Warn));
+
+    let mut state = State { self_test: test.finish(0), ..Default::default() };
+    update_readiness(&mut state);
+    assert!(state.self_test.ready_to_fly);
+
+    // The size of the board's log partition.
+    let mut test = SelfTest::default();
+    let logger = FlightLogger::new(MemoryStorage::new(32, 4096), LoggerConfig::default(), Default::default()).unwrap();
+    test.check_log(Some(&Mutex::new(logger)));
+    assert_eq!(test.checks()[0].status, CheckStatus::Pass);
+}
+
+#[test]
+fn device_errors_fail() {
+    let mut test = SelfTest::default();
+    let continuity = MockContinuity::new(2);
+    continuity.fail(2, HalError::NoResponse);
+    test.check_continuity(&mut continuity.clone());
+    test.check(Check::Led, Err(HalError::OutputFailed));
+    test.check_log::<MemoryStorage>(None);
+    assert!(test.checks().iter().all(|result| result.status == CheckStatus::Fail));
+    assert_eq!(test.checks()[0].message.as_deref(), Some("Channel 2: Device did not respond"));
+}
+
+#[test]
+fn failed_sensor_blocks_after_self_test() {
+    let mut state = State { self_test: SelfTest::default().finish(0), ..Default::default() };
+    state.health.barometer.status = SensorStatus::Failed;
+    state.health.barometer.last_error = Some("Device did not respond".into());
+    update_readiness(&mut state);
+    assert!(!state.self_test.ready_to_fly);
+    assert_eq!(state.self_test.blocking, ["Barometer: Device did not respond"]);
+
+    state.health.barometer.status = SensorStatus::Ok;
+    update_readiness(&mut state);
+    assert!(state.self_test.ready_to_fly);
+}
@@ -0,0 +1,94 @@
+use rrr_api::ServoCalibration;
+use rrr_core::servo::*;
+
+fn servo(calibration: ServoCalibration, position: f32) -> Servo {
+    let mut servo = Servo::new(calibration);
+    servo.set_position(Some(position));
+    servo
+}
+
+#[test]
+fn default_calibration_spans_one_to_two_ms() {
+    let calibration = ServoCalibration::default();
+
+    assert_eq!(servo(calibration.clone(), -1.0).pulse_width_us(), Some(1000));
+    assert_eq!(servo(calibration.clone(), 0.0).pulse_width_us(), Some(1500));
+    assert_eq!(servo(calibration, 1.0).pulse_width_us(), Some(2000));
+}
+
+#[test]
+fn off_servo_has_zero_duty() {
+    let servo = Servo::new(ServoCalibration::default());
+
+    assert_eq!(servo.pulse_width_us(), None);
+    assert_eq!(servo.duty(16383), 0);
+}
+
+#[test]
+fn duty_matches_pulse_width() {
+    let max_duty = (1 << 14) - 1;
+
+    assert_eq!(servo(ServoCalibration::default(), -1.0).duty(max_duty), max_duty / 20);
+    assert_eq!(servo(ServoCalibration::default(), 1.0).duty(max_duty), max_duty / 10);
+}
+
+#[test]
+fn asymmetric_center_is_respected() {
+    let calibration = ServoCalibration { min_pulse_us: 900, center_pulse_us: 1400, max_pulse_us: 2100, ..Default::default() };
+
+    assert_eq!(servo(calibration.clone(), -0.5).pulse_width_us(), Some(1150));
+    assert_eq!(servo(calibration, 0.5).pulse_width_us(), Some(1750));
+}
+
+#[test]
+fn reverse_mirrors_around_center() {
+    let calibration = ServoCalibration { reverse: true, ..Default::default() };
+
+    assert_eq!(servo(calibration.clone(), 1.0).pulse_width_us(), Some(1000));
+    assert_eq!(servo(calibration, -0.5).pulse_width_us(), Some(1750));
+}
+
+#[test]
+fn trim_never_exceeds_pulse_limits() {
+    let calibration = ServoCalibration { trim_us: 50, ..Default::default() };
+
+    assert_eq!(servo(calibration.clone(), 0.0).pulse_width_us(), Some(1550));
+    assert_eq!(servo(calibration, 1.0).pulse_width_us(), Some(2000));
+}
+
+#[test]
+fn travel_limits_clamp_position() {
+    let calibration = ServoCalibration { min_position: -0.5, max_position: 0.25, ..Default::default() };
+    let low = servo(calibration.clone(), -1.0);
+    let high = servo(calibration, 1.0);
+
+    assert_eq!(low.position(), Some(-0.5));
+    assert_eq!(low.pulse_width_us(), Some(1250));
+    assert_eq!(high.position(), Some(0.25));
+    assert_eq!(high.pulse_width_us(), Some(1625));
+}
+
+#[test]
+fn new_calibration_reclamps_current_position() {
+    let mut servo = servo(ServoCalibration::default(), 1.0);
+    servo.set_calibration(ServoCalibration { max_position: 0.5, ..Default::default() }).unwrap();
+
+    assert_eq!(servo.position(), Some(0.5));
+}
+
+#[test]
+fn inconsistent_calibration_is_rejected() {
+    let invalid = [
+        ServoCalibration { min_pulse_us: 1600, ..Default::default() },
+        ServoCalibration { max_pulse_us: 1400, ..Default::default() },
+        ServoCalibration { max_pulse_us: 25_000, ..Default::default() },
+        ServoCalibration { min_position: -1.5, ..Default::default() },
+        ServoCalibration { min_position: 0.5, max_position: 0.5, ..Default::default() },
+    ];
+
+    for calibration in invalid {
+        let mut servo = Servo::new(ServoCalibration::default());
+        assert_eq!(servo.set_calibration(calibration.clone()), Err(ServoError::InvalidCalibration), "{:?}", calibration);
+        assert_eq!(servo.calibration(), &ServoCalibration::default());
+    }
+}
@@ -0,0 +1,58 @@
+use rrr_core::signing::*;
+
+fn key() -> SigningKey {
+    SigningKey::from_bytes(&[7; SECRET_KEY_LENGTH])
+}
+
+#[test]
+fn signed_image_verifies() {
+    let image: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+    let signed = sign_image(&image, &key(), 1_700_000_000);
+    assert_eq!(signed.len(), image.len() + TRAILER_SIZE);
+    assert_eq!(&signed[..image.len()], image);
+
+    let trailer = verify_image(&signed, &key().verifying_key()).unwrap();
+    assert_eq!((trailer.image_size, trailer.signed_at), (5000, 1_700_000_000));
+
+    // Streamed in pieces.
+    let mut check = SignatureCheck::default();
+    for piece in image.chunks(777) {
+        check.update(piece);
+    }
+    assert_eq!(check.verify(&signed[image.len()..], image.len(), &key().verifying_key()), Ok(trailer));
+}
+
+#[test]
+fn tampering_is_detected() {
+    let image = vec![1; 1000];
+    let signed = sign_image(&image, &key(), 0);
+
+    let mut modified = signed.clone();
+    modified[10] ^= 1;
+    assert_eq!(verify_image(&modified, &key().verifying_key()), Err(SignatureError::Invalid));
+
+    // The metadata is signed too.
+    let mut modified = signed.clone();
+    modified[image.len() + 12] ^= 1;
+    assert_eq!(verify_image(&modified, &key().verifying_key()), Err(SignatureError::Invalid));
+
+    let other = SigningKey::from_bytes(&[8; SECRET_KEY_LENGTH]);
+    assert_eq!(verify_image(&signed, &other.verifying_key()), Err(SignatureError::Invalid));
+
+    assert_eq!(verify_image(&image, &key().verifying_key()), Err(SignatureError::Unsigned));
+    let mut longer = image.clone();
+    longer.push(0);
+    longer.extend_from_slice(&signed[image.len()..]);
+    assert_eq!(verify_image(&longer, &key().verifying_key()), Err(SignatureError::SizeMismatch { signed: 1000, actual: 1001 }));
+}
+
+#[test]
+fn public_key_hex_round_trips() {
+    let public = key().verifying_key();
+    let hex = to_hex(public.as_bytes());
+    assert_eq!(hex.len(), 2 * PUBLIC_KEY_LENGTH);
+    assert_eq!(parse_public_key(&format!("{}\n", hex)), Ok(public));
+    assert_eq!(parse_public_key(&hex[2..]), Err(SignatureError::InvalidKey));
+    assert_eq!(parse_public_key("zz"), Err(SignatureError::InvalidKey));
+    assert_eq!(from_hex("0aff"), Some(vec![0x0a, 0xff]));
+}
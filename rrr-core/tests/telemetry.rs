@@ -0,0 +1,68 @@
+use std::sync::Mutex;
+use rrr_api::*;
+use rrr_core::avionics::CommandError;
+use rrr_core::pyro::PyroError;
+use rrr_core::telemetry::TelemetrySession;
+
+fn frame(message: &ClientMessage) -> Vec<u8> {
+    serde_json::to_vec(message).unwrap()
+}
+
+fn unused(_: &Command) -> Result<(), CommandError> {
+    panic!("no command expected")
+}
+
+#[test]
+fn pushes_state_at_subscribed_rate() {
+    let state = Mutex::new(State::default());
+    let mut session = TelemetrySession::default();
+    assert!(session.poll(0, &state).is_none());
+    assert_eq!(session.wait_ms(0), None);
+
+    assert!(session.handle(&frame(&ClientMessage::Subscribe { period_ms: 100 }), Encoding::Json, 1000, unused).is_none());
+    assert_eq!(session.period_ms(), Some(100));
+    assert!(matches!(session.poll(1000, &state), Some(ServerMessage::State(_))));
+    assert!(session.poll(1050, &state).is_none());
+    assert_eq!(session.wait_ms(1050), Some(50));
+    assert!(session.poll(1100, &state).is_some());
+
+    // A late poll does not make up for the missed pushes.
+    assert!(session.poll(1500, &state).is_some());
+    assert!(session.poll(1500, &state).is_none());
+    assert!(session.poll(1600, &state).is_some());
+
+    session.handle(&frame(&ClientMessage::Unsubscribe), Encoding::Json, 1700, unused);
+    assert!(session.poll(5000, &state).is_none());
+}
+
+#[test]
+fn subscription_period_is_clamped() {
+    let mut session = TelemetrySession::default();
+    session.handle(&frame(&ClientMessage::Subscribe { period_ms: 1 }), Encoding::Json, 0, unused);
+    assert_eq!(session.period_ms(), Some(MIN_TELEMETRY_PERIOD_MS));
+    session.handle(&frame(&ClientMessage::Subscribe { period_ms: u32::MAX }), Encoding::Json, 0, unused);
+    assert_eq!(session.period_ms(), Some(MAX_TELEMETRY_PERIOD_MS));
+}
+
+#[test]
+fn commands_are_answered_with_their_id() {
+    let mut session = TelemetrySession::default();
+    let arm = ClientMessage::Command { id: 7, command: Command::ArmPyro };
+    let reply = session.handle(&frame(&arm), Encoding::Json, 0, |c| {
+        assert!(matches!(c, Command::ArmPyro));
+        Ok(())
+    });
+    assert!(reply == Some(ServerMessage::CommandResponse { id: 7, response: CommandResponse::Ok }));
+
+    let fire = ClientMessage::Command { id: 8, command: Command::FirePyro { channel: 1, duration_ms: 100 } };
+    let reply = session.handle(&frame(&fire), Encoding::Json, 0, |_| Err(CommandError::Pyro(PyroError::NotArmed)));
+    match reply {
+        Some(ServerMessage::CommandResponse { id: 8, response: CommandResponse::Failed { error, .. } }) => {
+            assert_eq!(error, ApiError::NotArmed)
+        }
+        _ => panic!("expected a failed command response"),
+    }
+
+    let reply = session.handle(b"{\"Subscribe\":{}}", Encoding::Json, 0, unused);
+    assert!(matches!(reply, Some(ServerMessage::Rejected { .. })));
+}
@@ -0,0 +1,70 @@
+use std::sync::Mutex;
+use rrr_api::{FlightPhase, State, WifiAuth, WifiCredentials, WifiNetwork, MAX_WIFI_NETWORKS};
+use rrr_core::hal::HalError;
+use rrr_core::http::get_wifi_scan;
+use rrr_core::mock::MockWifiScanner;
+use rrr_core::wifi::*;
+
+fn network(ssid: &str, rssi: i8, channel: u8) -> WifiNetwork {
+    WifiNetwork { ssid: ssid.into(), rssi, channel, auth: WifiAuth::Wpa2 }
+}
+
+#[test]
+fn scan_lists_strongest_first() {
+    let scanner = MockWifiScanner::default();
+    scanner.set_networks(vec![
+        network("field", -80, 1),
+        network("", -40, 6),
+        network("hotspot", -55, 11),
+        network("field", -60, 6),
+    ]);
+    let response = get_wifi_scan(&Mutex::new(scanner), &Mutex::new(State::default()), None);
+    assert_eq!(response.status, 200);
+    let networks: Vec<WifiNetwork> = serde_json::from_slice(&response.body).unwrap();
+    assert_eq!(networks, vec![network("hotspot", -55, 11), network("field", -60, 6)]);
+}
+
+#[test]
+fn scan_failures() {
+    let scanner = Mutex::new(MockWifiScanner::default());
+    scanner.lock().unwrap().fail(HalError::NoResponse);
+    assert_eq!(get_wifi_scan(&scanner, &Mutex::new(State::default()), None).status, 500);
+
+    let state = Mutex::new(State::default());
+    state.lock().unwrap().flight.phase = FlightPhase::Boost;
+    scanner.lock().unwrap().set_networks(vec![network("field", -60, 6)]);
+    assert_eq!(get_wifi_scan(&scanner, &state, None).status, 409);
+}
+
+fn credentials(ssid: &str) -> WifiCredentials {
+    WifiCredentials { ssid: ssid.into(), password: "rocketry".into() }
+}
+
+#[test]
+fn network_list() {
+    let mut networks = vec![credentials("field"), credentials("hotspot")];
+    add_network(&mut networks, WifiCredentials { ssid: "field".into(), password: "new password".into() }).unwrap();
+    assert_eq!(networks[0].password, "new password");
+    add_network(&mut networks, credentials("club")).unwrap();
+    assert_eq!(ssids(&networks), ["field", "hotspot", "club"]);
+
+    move_network(&mut networks, "field", 7).unwrap();
+    assert_eq!(ssids(&networks), ["hotspot", "club", "field"]);
+    move_network(&mut networks, "club", 0).unwrap();
+    assert_eq!(ssids(&networks), ["club", "hotspot", "field"]);
+    assert_eq!(move_network(&mut networks, "home", 0), Err(WifiError::NotStored("home".into())));
+    remove_network(&mut networks, "hotspot").unwrap();
+    assert_eq!(ssids(&networks), ["club", "field"]);
+
+    let mut full: Vec<_> = (0..MAX_WIFI_NETWORKS).map(|i| credentials(&i.to_string())).collect();
+    assert_eq!(add_network(&mut full, credentials("field")), Err(WifiError::TooManyNetworks));
+    add_network(&mut full, credentials("0")).unwrap();
+}
+
+#[test]
+fn networks_in_range_are_tried_first() {
+    let networks = [credentials("field"), credentials("hidden"), credentials("hotspot"), credentials("home")];
+    let found = [network("hotspot", -50, 1), network("field", -80, 6), network("other", -40, 11)];
+    assert_eq!(ssids(&connection_order(&networks, &found)), ["field", "hotspot", "hidden", "home"]);
+    assert_eq!(connection_order(&networks, &[]), networks);
+}
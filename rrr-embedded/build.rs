@@ -1,6 +1,7 @@
 use std::fs::{create_dir_all, File, read_dir, remove_dir_all, DirEntry};
 use std::io::{BufReader, copy};
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use trunk_build_time::cmd::build;
 use trunk_build_time::config;
 use embuild::{
@@ -10,9 +11,19 @@ use flate2::Compression;
 use tokio;
 use flate2::write::GzEncoder;
 
+/// Exposes the commit being built as `RRR_GIT_HASH`, left unset outside a checkout.
+fn git_hash() {
+    let output = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output().ok();
+    if let Some(output) = output.filter(|o| o.status.success()) {
+        println!("cargo:rustc-env=RRR_GIT_HASH={}", String::from_utf8_lossy(&output.stdout).trim());
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     println!("cargo:rerun-if-changed=../");
+    println!("cargo:rerun-if-env-changed=RRR_OTA_PUBLIC_KEY");
+    git_hash();
 
     let mut cfg = config::ConfigOptsBuild::default();
     cfg.release = true;
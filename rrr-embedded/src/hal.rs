@@ -0,0 +1,192 @@
+//! ESP-IDF implementations of the [`rrr_core::hal`] traits.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use bmp280_ehal::BMP280;
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use esp_idf_hal::adc::{AdcChannelDriver, AdcDriver, Atten11dB, ADC1};
+use esp_idf_hal::gpio::{Gpio1, Output, OutputPin, PinDriver};
+use esp_idf_hal::ledc::LedcDriver;
+use max170xx::Max17048;
+use rrr_core::hal::*;
+
+pub struct EspPyroOutput<'d, T: OutputPin> {
+    pin: PinDriver<'d, T, Output>,
+}
+
+impl<'d, T: OutputPin> EspPyroOutput<'d, T> {
+    pub fn new(pin: PinDriver<'d, T, Output>) -> Self {
+        Self { pin }
+    }
+}
+
+impl<'d, T: OutputPin> PyroOutput for EspPyroOutput<'d, T> {
+    fn set_firing(&mut self, firing: bool) -> Result<(), HalError> {
+        let result = if firing { self.pin.set_high() } else { self.pin.set_low() };
+        result.map_err(|_| HalError::OutputFailed)
+    }
+}
+
+/// I2C handle that never fails a transfer, for drivers that unwrap bus
+/// errors: failures are latched for the owner to check and reads come back
+/// zeroed.
+pub struct LatchedI2c<I2C> {
+    i2c: I2C,
+    failed: Arc<AtomicBool>,
+}
+
+impl<I2C> LatchedI2c<I2C> {
+    pub fn new(i2c: I2C, failed: Arc<AtomicBool>) -> Self {
+        Self { i2c, failed }
+    }
+}
+
+impl<I2C: Write> Write for LatchedI2c<I2C> {
+    type Error = I2C::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        if self.i2c.write(address, bytes).is_err() {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+impl<I2C: WriteRead> WriteRead for LatchedI2c<I2C> {
+    type Error = I2C::Error;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+        if self.i2c.write_read(address, bytes, buffer).is_err() {
+            buffer.fill(0);
+            self.failed.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+/// BMP280 on a shared bus. `connect` hands out a new bus handle each time the
+/// sensor is (re-)initialised.
+pub struct Bmp280Barometer<I2C, F> {
+    connect: F,
+    bmp280: Option<BMP280<LatchedI2c<I2C>>>,
+    failed: Arc<AtomicBool>,
+}
+
+impl<I2C: Write + WriteRead, F: FnMut() -> I2C> Bmp280Barometer<I2C, F> {
+    /// Initialises the sensor. Failures show in the self-test's read and the
+    /// sampling loop retries later.
+    pub fn new(connect: F) -> Self {
+        let mut barometer = Self { connect, bmp280: None, failed: Arc::default() };
+        barometer.reinit().ok();
+        barometer
+    }
+
+    fn check_bus(&self) -> Result<(), HalError> {
+        if self.failed.swap(false, Ordering::Relaxed) {
+            return Err(HalError::NoResponse);
+        }
+        Ok(())
+    }
+}
+
+impl<I2C: Write + WriteRead, F: FnMut() -> I2C> Barometer for Bmp280Barometer<I2C, F> {
+    fn read(&mut self) -> Result<BarometerReading, HalError> {
+        let bmp280 = self.bmp280.as_mut().ok_or(HalError::NoResponse)?;
+        let temperature = bmp280.temp() as f32;
+        let pressure = bmp280.pressure_one_shot() as f32;
+        self.check_bus()?;
+        if !pressure.is_finite() || pressure <= 0.0 {
+            return Err(HalError::InvalidReading);
+        }
+        Ok(BarometerReading { pressure, temperature })
+    }
+
+    fn reinit(&mut self) -> Result<(), HalError> {
+        self.bmp280 = None;
+        self.failed.store(false, Ordering::Relaxed);
+        let i2c = LatchedI2c::new((self.connect)(), self.failed.clone());
+        let bmp280 = BMP280::new(i2c).map_err(|_| HalError::NoResponse)?;
+        self.check_bus()?;
+        self.bmp280 = Some(bmp280);
+        Ok(())
+    }
+}
+
+/// MAX17048 on a shared bus, see [`Bmp280Barometer`] for `connect`.
+pub struct Max17048Gauge<I2C, F> {
+    connect: F,
+    max17048: Max17048<I2C>,
+}
+
+impl<I2C, E, F> Max17048Gauge<I2C, F>
+    where I2C: Write<Error = E> + WriteRead<Error = E>, F: FnMut() -> I2C
+{
+    pub fn new(mut connect: F) -> Self {
+        let max17048 = Max17048::new(connect());
+        Self { connect, max17048 }
+    }
+}
+
+impl<I2C, E, F> FuelGauge for Max17048Gauge<I2C, F>
+    where I2C: Write<Error = E> + WriteRead<Error = E>, F: FnMut() -> I2C
+{
+    fn read(&mut self) -> Result<BatteryReading, HalError> {
+        Ok(BatteryReading {
+            soc: self.max17048.soc().map_err(|_| HalError::NoResponse)?,
+            voltage: self.max17048.voltage().map_err(|_| HalError::NoResponse)?,
+            charge_rate: self.max17048.charge_rate().map_err(|_| HalError::NoResponse)?,
+        })
+    }
+
+    fn reinit(&mut self) -> Result<(), HalError> {
+        self.max17048 = Max17048::new((self.connect)());
+        self.max17048.version().map_err(|_| HalError::NoResponse)?;
+        Ok(())
+    }
+}
+
+/// Igniter voltage of pyro channel 1, measured on GPIO1.
+pub struct AdcContinuity<'d> {
+    adc: AdcDriver<'d, ADC1>,
+    channel: AdcChannelDriver<'d, Gpio1, Atten11dB<ADC1>>,
+}
+
+impl<'d> AdcContinuity<'d> {
+    pub fn new(adc: AdcDriver<'d, ADC1>, channel: AdcChannelDriver<'d, Gpio1, Atten11dB<ADC1>>) -> Self {
+        Self { adc, channel }
+    }
+}
+
+impl<'d> ContinuityMonitor for AdcContinuity<'d> {
+    fn channels(&self) -> u8 {
+        1
+    }
+
+    fn read_volts(&mut self, channel: u8) -> Result<f32, HalError> {
+        if channel != 1 {
+            return Err(HalError::InvalidChannel);
+        }
+        let millivolts = self.adc.read(&mut self.channel).map_err(|_| HalError::NoResponse)?;
+        Ok(millivolts as f32 / 1000f32)
+    }
+}
+
+pub struct LedcServoOutput<'d> {
+    driver: LedcDriver<'d>,
+}
+
+impl<'d> LedcServoOutput<'d> {
+    pub fn new(driver: LedcDriver<'d>) -> Self {
+        Self { driver }
+    }
+}
+
+impl<'d> ServoOutput for LedcServoOutput<'d> {
+    fn max_duty(&self) -> u32 {
+        self.driver.get_max_duty()
+    }
+
+    fn set_duty(&mut self, duty: u32) -> Result<(), HalError> {
+        self.driver.set_duty(duty).map_err(|_| HalError::OutputFailed)
+    }
+}
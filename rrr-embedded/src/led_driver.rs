@@ -1,5 +1,6 @@
 use ws2812_esp32_rmt_driver::*;
 use ws2812_esp32_rmt_driver::driver::color::*;
+use rrr_core::hal::{HalError, StatusLed};
 
 
 pub struct LedDriver {
@@ -23,4 +24,9 @@ impl LedDriver {
     pub fn off(&mut self) -> Result<(), Ws2812Esp32RmtDriverError> {
         self.ws2812.write(LedPixelColorGrb24::new_with_rgb(0, 0, 0).as_ref())
     }
-}
\ No newline at end of file
+}
+impl StatusLed for LedDriver {
+    fn set_rgb(&mut self, r: u8, g: u8, b: u8) -> Result<(), HalError> {
+        LedDriver::set_rgb(self, r, g, b).map_err(|_| HalError::OutputFailed)
+    }
+}
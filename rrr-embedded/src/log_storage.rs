@@ -0,0 +1,60 @@
+use std::ffi::c_void;
+use esp_idf_sys::*;
+use rrr_core::logger::{LogError, LogStorage};
+
+const LOG_PARTITION_LABEL: &[u8] = b"log\0";
+const LOG_PARTITION_SUBTYPE: esp_partition_subtype_t = 0x40;
+const FLASH_SECTOR_SIZE: u32 = 4096;
+
+/// The `log` data partition from `partitions.csv`.
+pub struct LogPartition {
+    partition: *const esp_partition_t,
+}
+
+// The partition table entry is static and the esp_partition_* functions are thread safe.
+unsafe impl Send for LogPartition {}
+
+impl LogPartition {
+    pub fn take() -> Result<Self, LogError> {
+        let partition = unsafe {
+            esp_partition_find_first(
+                esp_partition_type_t_ESP_PARTITION_TYPE_DATA,
+                LOG_PARTITION_SUBTYPE,
+                LOG_PARTITION_LABEL.as_ptr() as *const _,
+            )
+        };
+        if partition.is_null() {
+            Err(LogError::StorageFailure)
+        } else {
+            Ok(Self { partition })
+        }
+    }
+}
+
+impl LogStorage for LogPartition {
+    fn capacity(&self) -> u32 {
+        unsafe { (*self.partition).size }
+    }
+
+    fn sector_size(&self) -> u32 {
+        FLASH_SECTOR_SIZE
+    }
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), LogError> {
+        esp!(unsafe {
+            esp_partition_read(self.partition, offset as usize, buf.as_mut_ptr() as *mut c_void, buf.len())
+        }).map_err(|_| LogError::StorageFailure)
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), LogError> {
+        esp!(unsafe {
+            esp_partition_write(self.partition, offset as usize, data.as_ptr() as *const c_void, data.len())
+        }).map_err(|_| LogError::StorageFailure)
+    }
+
+    fn erase_sector(&mut self, offset: u32) -> Result<(), LogError> {
+        esp!(unsafe {
+            esp_partition_erase_range(self.partition, offset as usize, FLASH_SECTOR_SIZE as usize)
+        }).map_err(|_| LogError::StorageFailure)
+    }
+}
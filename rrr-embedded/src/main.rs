@@ -1,21 +1,23 @@
+mod hal;
 mod led_driver;
+mod log_storage;
 mod ota;
 mod wifi;
 mod server;
 mod nvs;
 
+use crate::hal::{AdcContinuity, Bmp280Barometer, EspPyroOutput, LedcServoOutput, Max17048Gauge};
 use crate::led_driver::LedDriver;
+use crate::log_storage::LogPartition;
 use crate::ota::OtaDriver;
 
 use rrr_api as api;
 
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::thread;
 use log::*;
 use anyhow::Result;
-use embedded_svc::http::server::Connection;
-use embedded_svc::io::Read;
 use embedded_svc::wifi::*;
 use esp_idf_hal::adc::{ADC1, AdcChannelDriver, Atten11dB};
 use esp_idf_hal::adc::config::Resolution;
@@ -29,21 +31,26 @@ use esp_idf_hal::i2c::I2cDriver;
 use esp_idf_hal::ledc;
 use esp_idf_hal::ledc::{LedcDriver, LedcTimerDriver};
 use esp_idf_hal::ledc::config::TimerConfig;
-use esp_idf_sys::esp_intr_disable;
-use max170xx::Max17048;
 use rrr_api::WifiCredentials;
-use crate::api::{Command, WifiConnectionConfiguration, WifiConnectionType};
+use rrr_core::avionics::{Avionics, RESET_DELAY_MS};
+use rrr_core::hal::StatusLed;
+use rrr_core::logger::{FlightLogger, LoggerConfig};
+use rrr_core::ota::{FirmwareUpdater, ESP32C3_CHIP_ID};
+use rrr_core::selftest::SelfTest;
+use rrr_core::signing::{parse_public_key, VerifyingKey};
+use rrr_log::LogHeader;
+use crate::api::{Check, Command, WifiConnectionType};
 use crate::server::Server;
 use crate::wifi::WiFi;
 
-const BMP_280_FILTER_GAIN: f32 = 0.05f32;
+/// Clock readings before 2023-01-01 mean the time was never set since boot.
+const CLOCK_VALID_AFTER: u64 = 1_672_531_200;
+const BOARD_NAME: &str = "esp32-c3";
 
 fn main() -> Result<()> {
     esp_idf_sys::link_patches();
     esp_idf_svc::log::EspLogger::initialize_default();
 
-    let state = Arc::new(Mutex::new(api::State::default()));
-
     let peripherals = Peripherals::take().unwrap();
     let sysloop = EspSystemEventLoop::take()?;
 
@@ -57,186 +64,120 @@ fn main() -> Result<()> {
     let shared_i2c = shared_bus::new_std!(I2cDriver = i2c).unwrap();
 
 
-    let _pyro = esp_idf_hal::gpio::PinDriver::output(peripherals.pins.gpio6)?;
+    let pyro = EspPyroOutput::new(esp_idf_hal::gpio::PinDriver::output(peripherals.pins.gpio6)?);
 
+    let nvs = nvs::Nvs::new()?;
 
-    let timer_driver =
+    let timer_driver = Arc::new(
         LedcTimerDriver::new(
             peripherals.ledc.timer0,
             &TimerConfig::default()
                 .frequency(50.Hz().into())
                 .resolution(ledc::Resolution::Bits14),
-        )?;
-
-    let mut pwm_driver_1 = LedcDriver::new(peripherals.ledc.channel0, &timer_driver , peripherals.pins.gpio4)?;
-    let mut pwm_driver_2 = LedcDriver::new(peripherals.ledc.channel1, &timer_driver, peripherals.pins.gpio5)?;
-
-    let mut pwm = Arc::new(Mutex::new((pwm_driver_1, pwm_driver_2)));
+        )?);
 
+    let servos = [
+        LedcServoOutput::new(LedcDriver::new(peripherals.ledc.channel0, timer_driver.clone(), peripherals.pins.gpio4)?),
+        LedcServoOutput::new(LedcDriver::new(peripherals.ledc.channel1, timer_driver, peripherals.pins.gpio5)?),
+    ];
 
-    let mut max17048 = Max17048::new(shared_i2c.acquire_i2c());
+    //Drivers
+    let mut self_test = SelfTest::default();
+    let mut led_driver = LedDriver::new(9, 0)?;
+    self_test.check(Check::Led, StatusLed::set_rgb(&mut led_driver, 20, 0, 0));
 
-    max17048.version().unwrap();
-    info!("SOC: {:.2}", max17048.soc().unwrap());
-    info!("MAX -- OK");
+    let avionics = Avionics::new(vec![pyro], servos, led_driver, nvs)?;
+    let state = avionics.state();
+    let reset_reason = reset_reason();
+    info!("Reset reason: {:?}", reset_reason);
+    state.lock().unwrap().reset_reason = reset_reason;
+    self_test.check_settings(&mut *avionics.settings().lock().unwrap());
 
-    let max17048 = Arc::new(Mutex::new(max17048));
 
     esp_idf_hal::task::thread::ThreadSpawnConfiguration {
         name: Some(b"max-thread\0"),
         ..Default::default()
-    }.set().unwrap();
-
-    let max1 = max17048.clone();
-    let max2 = max17048.clone();
-
-    let state1 = state.clone();
-    let state2 = state.clone();
-
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_millis(1000));
-            let mut state = state1.lock().unwrap();
-            let mut max = max1.lock().unwrap();
-            state.battery.soc = max.soc().unwrap();
-            state.battery.voltage = max.voltage().unwrap();
-            state.battery.charge_rate = max.charge_rate().unwrap();
-        }
-    });
+    }.set()?;
 
-    let bmp280 = bmp280_ehal::BMP280::new(shared_i2c.acquire_i2c())?;
-    let bmp280 = Arc::new(Mutex::new(bmp280));
-
-    let state_ = state.clone();
-
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_millis(20));
-            let mut bmp280 = bmp280.lock().unwrap();
-            let temperature: f32  = bmp280.temp() as f32;
-            let p0 = 101325f32;
-            let pressure: f32 = bmp280.pressure_one_shot() as f32;
-            //-44330f32 * (1f32 - f32::powf  (pressure / 101325f32).po powf(1f32/5.255f32));
-            let altitude: f32 = -8435.775 * (pressure / p0 - 1f32);
-            let mut state = state_.lock().unwrap();
-            let new_altitude = state.barometer.altitude +
-                (altitude - state.barometer.altitude) * BMP_280_FILTER_GAIN;
-            state.barometer.temperature = temperature;
-            state.barometer.altitude = new_altitude;
-        }
-    });
+    // Sensors that fail here are retried and re-initialised by their sampling loops.
+    let mut max17048 = Max17048Gauge::new(move || shared_i2c.acquire_i2c());
+    self_test.check_fuel_gauge(&mut max17048);
+    avionics.spawn_fuel_gauge(max17048);
 
+    let mut bmp280 = Bmp280Barometer::new(move || shared_i2c.acquire_i2c());
+    self_test.check_barometer(&mut bmp280);
+    avionics.spawn_barometer(bmp280);
 
 
     let mut adc_driver_config = esp_idf_hal::adc::AdcConfig::default();
     adc_driver_config.resolution = Resolution::Resolution12Bit;
     adc_driver_config.calibration = true;
 
-    let mut adc_driver = esp_idf_hal::adc::AdcDriver::new(peripherals.adc1, &esp_idf_hal::adc::AdcConfig::default())?;
-    let mut adc_channel_driver: AdcChannelDriver<'_, Gpio1, Atten11dB<ADC1>> = esp_idf_hal::adc::AdcChannelDriver::new(peripherals.pins.gpio1)?;
-
-    adc_driver.read(&mut adc_channel_driver)?;
-
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_millis(1000));
-            let adc_reading = adc_driver.read(&mut adc_channel_driver).unwrap();
-            let voltage = adc_reading as f32;
-            let mut state = state2.lock().unwrap();
-            state.pyro.channel1.test_voltage = voltage / 1000f32;
-        }
+    let adc_driver = esp_idf_hal::adc::AdcDriver::new(peripherals.adc1, &esp_idf_hal::adc::AdcConfig::default())?;
+    let adc_channel_driver: AdcChannelDriver<'_, Gpio1, Atten11dB<ADC1>> = esp_idf_hal::adc::AdcChannelDriver::new(peripherals.pins.gpio1)?;
+
+    let mut continuity = AdcContinuity::new(adc_driver, adc_channel_driver);
+    self_test.check_continuity(&mut continuity);
+    avionics.spawn_continuity(continuity);
+    avionics.spawn_pyro_poll();
+    avionics.spawn_staleness_check();
+
+    let start_time = SystemTime::now().duration_since(UNIX_EPOCH).ok()
+        .map(|d| d.as_secs())
+        .filter(|secs| *secs > CLOCK_VALID_AFTER);
+    let header = rrr_core::avionics::log_header(&state.lock().unwrap(), &LogHeader {
+        firmware_version: env!("CARGO_PKG_VERSION").to_string(),
+        start_time,
+        ..Default::default()
     });
+    let logger = match LogPartition::take().and_then(|partition| FlightLogger::new(partition, LoggerConfig::default(), header.clone())) {
+        Ok(logger) => {
+            info!("Logger -- OK, session {}", logger.state().session);
+            Some(Arc::new(Mutex::new(logger)))
+        }
+        Err(e) => {
+            error!("Logger -- FAIL: {}", e);
+            None
+        }
+    };
 
+    self_test.check_log(logger.as_deref());
+    if let Some(logger) = logger.clone() {
+        avionics.spawn_logger(logger, header);
+    }
 
-    //Drivers
-    let mut led_driver = LedDriver::new(9, 0)?;
-    info!("LED -- OK");
-    led_driver.set_rgb(20, 0, 0)?;
-
-    let mut nvs = nvs::Nvs::new()?;
 
-    let nvs_arc0 = Arc::new(Mutex::new(nvs));
-    let nvs_arc1 = nvs_arc0.clone();
 
 
-    let wifi_configuration = match nvs_arc0.lock().unwrap().get_wifi_connection()? {
-        None => {
-            WifiConnectionConfiguration {
-                connection_type: WifiConnectionType::StartAccessPoint,
-                credentials: WifiCredentials {
-                    ssid: String::from("RRR-wifi-0"),
-                    password: String::from("12345678"),
-                },
-            }
-        }
-        Some(creds) => {
-            WifiConnectionConfiguration {
-                connection_type: WifiConnectionType::ConnectToExternal,
-                credentials: WifiCredentials {
-                    ssid: creds.ssid,
-                    password: creds.password,
-                },
-            }
-        }
+    let networks = avionics.wifi_credentials();
+    info!("wifi networks {:?}", rrr_core::wifi::ssids(&networks));
+    let access_point = WifiCredentials {
+        ssid: String::from("RRR-wifi-0"),
+        password: String::from("12345678"),
     };
 
-    info!("wifi config {:?}", wifi_configuration);
-
-    #[allow(unused_variables)]
-        let wifi = WiFi::new(wifi_configuration, peripherals.modem, sysloop.clone(), state.clone())?;
+    let wifi = WiFi::new(&networks, access_point, peripherals.modem, sysloop.clone(), state.clone())?;
 
     match state.lock().unwrap().wifi_state.connection_type {
-         WifiConnectionType::ConnectToExternal => led_driver.set_rgb(0, 20, 0)?,
-        _ => led_driver.set_rgb(10, 10, 0)?,
+         WifiConnectionType::ConnectToExternal => avionics.set_status_led(0, 20, 0)?,
+        _ => avionics.set_status_led(10, 10, 0)?,
     }
 
-    #[allow(unused_variables)]
-        let mut ota_driver = OtaDriver::new()?;
-
-
-    let ld = Arc::new(Mutex::new(led_driver));
-
-    let state_ = state.clone();
-
-    let command_handler = move |c: &Command| -> Result<()> {
-        match c {
-            Command::Reset => {}
-            Command::SetWifi { ssid, password } => {
-                let creds = WifiCredentials { ssid: ssid.clone(), password: password.clone() };
-                nvs_arc1.lock().unwrap().set_wifi_connection(creds).unwrap();
-                nvs_arc1.lock().unwrap().get_wifi_connection().unwrap();
-            }
-            Command::SetLedColor { r, g, b } =>
-                { ld.lock().unwrap().set_rgb(r.clone(), g.clone(), b.clone())? }
-            Command::SetPwmDutyCycle {duty_1, duty_2} =>
-                {
-                    info!("setting pwm");
-                    fn duty_opt_to_servo_duty(d: &Option<f32>, max_duty: u32) -> u32 {
-                        match d {
-                            None => {0}
-                            Some(i) => {(max_duty as f32  * (10f32 * (i + 1f32)) / 200f32) as u32}
-                        }
-                    }
-
-                    let mut pwm = pwm.lock().unwrap();
-                    let d = duty_opt_to_servo_duty(duty_1, pwm.0.get_max_duty());
-                    pwm.0.set_duty(d)?;
-                    info!("d1: {}", d);
-                    let d = duty_opt_to_servo_duty(duty_2, pwm.1.get_max_duty());
-                    pwm.1.set_duty(d)?;
-                    info!("d2: {}", d);
-                    let mut state = state_.lock().unwrap();
-                    state.servo.servo1_duty = *duty_1;
-                    state.servo.servo2_duty = *duty_2;
-                }
-
-            _ => {}
-        }
-        Ok(())
-    };
+    let ota_driver = OtaDriver::new()?;
+    self_test.check_firmware(&ota_driver);
+    avionics.finish_self_test(self_test);
+    // A new image that failed the self-test restarts into the previous one here.
+    let mut ota = FirmwareUpdater::new(ota_driver, state.clone(), ESP32C3_CHIP_ID, ota_key());
+    if let Err(e) = ota.confirm_boot() {
+        error!("Confirming the firmware failed: {}", e);
+    }
+
+
+    let info = avionics.board_info(BOARD_NAME, env!("CARGO_PKG_VERSION"), option_env!("RRR_GIT_HASH"));
+    let avionics_ = avionics.clone();
+    let command_handler = move |c: &Command| avionics_.handle_command(c);
 
-    #[allow(unused_variables)]
-        let server = Server::new(state, command_handler)?;
+    let server = Server::new(info, state, logger.clone(), ota, wifi, command_handler)?;
 
     info!("HTTP server -- OK");
     info!("mDNS -- OK");
@@ -246,10 +187,55 @@ fn main() -> Result<()> {
     mdns.set_instance_name("RRR web server")?;
     mdns.add_service(None, "_http", "_tcp", 80, &[("board", "{esp32}")])?;
 
-    loop {
-        thread::sleep(Duration::from_millis(1000));
-    }
+    let request = avionics.wait_for_reset();
+    info!("{:?} requested, shutting down", request);
+    thread::sleep(Duration::from_millis(RESET_DELAY_MS));
+    drop(mdns);
+    drop(server);
+    // The logger stays locked so that no write is cut short by the restart.
+    let _logger = logger.as_ref().map(|logger| {
+        let mut logger = logger.lock().unwrap();
+        if let Err(e) = logger.flush() {
+            error!("Log flush failed: {}", e);
+        }
+        logger
+    });
+    esp_idf_hal::reset::restart();
 
     #[allow(unreachable_code)]
     Ok(())
-}
\ No newline at end of file
+}
+
+fn reset_reason() -> api::ResetReason {
+    use esp_idf_sys::*;
+
+    #[allow(non_upper_case_globals)]
+    match unsafe { esp_reset_reason() } {
+        esp_reset_reason_t_ESP_RST_POWERON => api::ResetReason::PowerOn,
+        esp_reset_reason_t_ESP_RST_EXT => api::ResetReason::External,
+        esp_reset_reason_t_ESP_RST_SW => api::ResetReason::Software,
+        esp_reset_reason_t_ESP_RST_PANIC => api::ResetReason::Panic,
+        esp_reset_reason_t_ESP_RST_INT_WDT | esp_reset_reason_t_ESP_RST_TASK_WDT | esp_reset_reason_t_ESP_RST_WDT => {
+            api::ResetReason::Watchdog
+        }
+        esp_reset_reason_t_ESP_RST_BROWNOUT => api::ResetReason::Brownout,
+        esp_reset_reason_t_ESP_RST_DEEPSLEEP => api::ResetReason::DeepSleep,
+        _ => api::ResetReason::Unknown,
+    }
+}
+
+/// Key firmware updates must be signed with, set as `RRR_OTA_PUBLIC_KEY` when
+/// building. Without one `POST /ota` refuses every image.
+fn ota_key() -> Option<VerifyingKey> {
+    match option_env!("RRR_OTA_PUBLIC_KEY").map(parse_public_key) {
+        Some(Ok(key)) => Some(key),
+        Some(Err(e)) => {
+            error!("RRR_OTA_PUBLIC_KEY: {}", e);
+            None
+        }
+        None => {
+            warn!("Built without RRR_OTA_PUBLIC_KEY, firmware updates are disabled");
+            None
+        }
+    }
+}
@@ -2,16 +2,26 @@ use anyhow::Result;
 use esp_idf_svc::nvs::{EspDefaultNvs, EspDefaultNvsPartition, EspNvs, NvsDefault, NvsPartitionId};
 use log::info;
 use rrr_api::*;
+use rrr_core::hal::{HalError, Settings};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 pub struct Nvs {
     espnvs: EspNvs<NvsDefault>,
 }
 
+const WIFI_NETWORKS_NAME: &str = "wifi_list";
+const WIFI_NETWORKS_LENGTH_NAME: &str = "wifi_list_l";
+/// The single network stored by older firmware, used until the list is written.
 const WIFI_SSID_NAME: &str = "wifi_ssid";
 const WIFI_SSID_LENGTH_NAME: &str = "wifi_ssid_l";
 const WIFI_PASSWORD_NAME: &str = "wifi_pass";
 const WIFI_PASSWORD_LENGTH_NAME: &str = "wifi_pass_l";
 const WIFI_SET_NAME: &str = "wifi_set";
+const RECOVERY_CONFIG_NAME: &str = "recovery";
+const RECOVERY_CONFIG_LENGTH_NAME: &str = "recovery_l";
+const SERVO_CALIBRATION_NAMES: [&str; 2] = ["servo_cal_1", "servo_cal_2"];
+const SERVO_CALIBRATION_LENGTH_NAMES: [&str; 2] = ["servo_cal_1_l", "servo_cal_2_l"];
 
 
 impl Nvs {
@@ -21,19 +31,18 @@ impl Nvs {
         Ok(Self { espnvs: nvs })
     }
 
-    pub fn set_wifi_connection(&mut self, wifi: WifiCredentials) -> Result<()> {
-        self.espnvs.set_u8(WIFI_SET_NAME, 0)?;
-        self.espnvs.set_blob(WIFI_SSID_NAME, wifi.ssid.as_bytes())?;
-        let ssid_length = wifi.password.len() as u8;
-        self.espnvs.set_u8(WIFI_SSID_LENGTH_NAME, wifi.ssid.len() as u8)?;
-        self.espnvs.set_blob(WIFI_PASSWORD_NAME, wifi.password.as_bytes())?;
-        let pass_length = wifi.password.len() as u8;
-        self.espnvs.set_u8(WIFI_PASSWORD_LENGTH_NAME, wifi.password.len() as u8)?;
-        self.espnvs.set_u8(WIFI_SET_NAME, 1)?;
-        Ok(())
+    pub fn set_wifi_networks(&mut self, networks: &[WifiCredentials]) -> Result<()> {
+        self.set_json(WIFI_NETWORKS_NAME, WIFI_NETWORKS_LENGTH_NAME, &networks)
     }
 
-    pub fn get_wifi_connection(&mut self) -> Result<Option<WifiCredentials>> {
+    pub fn get_wifi_networks(&mut self) -> Result<Vec<WifiCredentials>> {
+        match self.get_json(WIFI_NETWORKS_NAME, WIFI_NETWORKS_LENGTH_NAME)? {
+            Some(networks) => Ok(networks),
+            None => Ok(self.get_legacy_wifi_connection()?.into_iter().collect()),
+        }
+    }
+
+    fn get_legacy_wifi_connection(&mut self) -> Result<Option<WifiCredentials>> {
         match self.espnvs.get_u8(WIFI_SET_NAME)? {
             None => { Ok(None) }
             Some(0) => { Ok(None) }
@@ -59,13 +68,92 @@ impl Nvs {
         }
     }
 
+    pub fn set_recovery_config(&mut self, config: &RecoveryConfig) -> Result<()> {
+        self.set_json(RECOVERY_CONFIG_NAME, RECOVERY_CONFIG_LENGTH_NAME, config)
+    }
+
+    pub fn get_recovery_config(&mut self) -> Result<Option<RecoveryConfig>> {
+        self.get_json(RECOVERY_CONFIG_NAME, RECOVERY_CONFIG_LENGTH_NAME)
+    }
+
+    /// `channel` is the zero-based servo output index.
+    pub fn set_servo_calibration(&mut self, channel: usize, calibration: &ServoCalibration) -> Result<()> {
+        self.set_json(SERVO_CALIBRATION_NAMES[channel], SERVO_CALIBRATION_LENGTH_NAMES[channel], calibration)
+    }
+
+    pub fn get_servo_calibration(&mut self, channel: usize) -> Result<Option<ServoCalibration>> {
+        self.get_json(SERVO_CALIBRATION_NAMES[channel], SERVO_CALIBRATION_LENGTH_NAMES[channel])
+    }
+
+    fn set_json<T: Serialize>(&mut self, name: &str, length_name: &str, value: &T) -> Result<()> {
+        let data = serde_json::to_vec(value)?;
+        self.espnvs.set_blob(name, data.as_slice())?;
+        self.espnvs.set_u16(length_name, data.len() as u16)?;
+        Ok(())
+    }
+
+    fn get_json<T: DeserializeOwned>(&mut self, name: &str, length_name: &str) -> Result<Option<T>> {
+        match self.espnvs.get_u16(length_name)? {
+            None => { Ok(None) }
+            Some(length) => {
+                let mut data = vec![0u8; length as usize];
+                self.espnvs.get_blob(name, data.as_mut_slice())?;
+                Ok(Some(serde_json::from_slice(data.as_slice())?))
+            }
+        }
+    }
+
     pub fn wipe_data(&mut self) -> Result<()> {
+        self.espnvs.remove(WIFI_NETWORKS_LENGTH_NAME)?;
+        self.espnvs.remove(WIFI_NETWORKS_NAME)?;
         self.espnvs.remove(WIFI_SET_NAME)?;
         self.espnvs.remove(WIFI_SSID_NAME)?;
         self.espnvs.remove(WIFI_SSID_LENGTH_NAME)?;
         self.espnvs.remove(WIFI_PASSWORD_NAME)?;
         self.espnvs.remove(WIFI_PASSWORD_LENGTH_NAME)?;
+        self.espnvs.remove(RECOVERY_CONFIG_LENGTH_NAME)?;
+        self.espnvs.remove(RECOVERY_CONFIG_NAME)?;
+        for (name, length_name) in SERVO_CALIBRATION_NAMES.iter().zip(SERVO_CALIBRATION_LENGTH_NAMES.iter()) {
+            self.espnvs.remove(length_name)?;
+            self.espnvs.remove(name)?;
+        }
 
         Ok(())
     }
 }
+
+impl Settings for Nvs {
+    fn wifi_networks(&mut self) -> Result<Vec<WifiCredentials>, HalError> {
+        self.get_wifi_networks().map_err(|_| HalError::StorageFailed)
+    }
+
+    fn set_wifi_networks(&mut self, networks: &[WifiCredentials]) -> Result<(), HalError> {
+        Nvs::set_wifi_networks(self, networks).map_err(|_| HalError::StorageFailed)
+    }
+
+    fn recovery_config(&mut self) -> Result<Option<RecoveryConfig>, HalError> {
+        self.get_recovery_config().map_err(|_| HalError::StorageFailed)
+    }
+
+    fn set_recovery_config(&mut self, config: &RecoveryConfig) -> Result<(), HalError> {
+        Nvs::set_recovery_config(self, config).map_err(|_| HalError::StorageFailed)
+    }
+
+    fn servo_calibration(&mut self, index: usize) -> Result<Option<ServoCalibration>, HalError> {
+        if index >= SERVO_CALIBRATION_NAMES.len() {
+            return Err(HalError::InvalidChannel);
+        }
+        self.get_servo_calibration(index).map_err(|_| HalError::StorageFailed)
+    }
+
+    fn set_servo_calibration(&mut self, index: usize, calibration: &ServoCalibration) -> Result<(), HalError> {
+        if index >= SERVO_CALIBRATION_NAMES.len() {
+            return Err(HalError::InvalidChannel);
+        }
+        Nvs::set_servo_calibration(self, index, calibration).map_err(|_| HalError::StorageFailed)
+    }
+
+    fn erase(&mut self) -> Result<(), HalError> {
+        self.wipe_data().map_err(|_| HalError::StorageFailed)
+    }
+}
@@ -1,67 +1,105 @@
-use thiserror::Error;
-use embedded_svc::io::Write;
-use esp_idf_hal::reset::restart;
-use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
-use embedded_svc::http::client::Client;
+use std::ptr;
+use embedded_svc::ota::{Ota, Slot, SlotState};
 use esp_idf_svc::ota::EspOta;
-use esp_idf_sys::EspError;
-use log::{error, info};
-use crate::ota::OtaError::UrlUnavailable;
+use esp_idf_sys::*;
+use log::error;
+use rrr_api::{FirmwareSlots, SlotInfo, SlotStatus};
+use rrr_core::hal::{FirmwareSlot, HalError};
 
 
+/// Writes uploaded images to the app partition that is not running, which the
+/// bootloader then starts after the next reset.
 pub struct OtaDriver {
     ota: EspOta,
+    /// Update begun with `esp_ota_begin` and not yet ended.
+    update: Option<esp_ota_handle_t>,
 }
 
-#[derive(Error, Debug)]
-pub enum OtaError {
-    #[error("Url is unavailable")]
-    UrlUnavailable,
-    #[error("Firmware download was interrupted")]
-    ConnectionInterrupted,
-    #[error("ESP-IDF stack error")]
-    EspBackendFailure,
-    #[error("Firmware does not fit the partition")]
-    FileTooBig
+fn update_partition() -> Result<*const esp_partition_t, HalError> {
+    let partition = unsafe { esp_ota_get_next_update_partition(ptr::null()) };
+    if partition.is_null() {
+        error!("No OTA update partition");
+        return Err(HalError::StorageFailed);
+    }
+    Ok(partition)
+}
+
+fn storage_failed(e: EspError) -> HalError {
+    error!("OTA failed: {}", e);
+    HalError::StorageFailed
+}
+
+fn slot_info(slot: Slot) -> SlotInfo {
+    SlotInfo {
+        label: slot.label.as_str().into(),
+        version: slot.firmware.map(|firmware| firmware.version.as_str().into()),
+        status: match slot.state {
+            SlotState::Factory => SlotStatus::Factory,
+            SlotState::Valid => SlotStatus::Valid,
+            SlotState::Unverified => SlotStatus::Unverified,
+            SlotState::Invalid => SlotStatus::Invalid,
+            _ => SlotStatus::Unknown,
+        },
+    }
 }
 
 
 impl OtaDriver {
     pub fn new() -> Result<Self, EspError> {
         let ota = EspOta::new();
-        ota.map(|ota| { Self { ota } })
+        ota.map(|ota| { Self { ota, update: None } })
     }
+}
 
-    //TODO: handle size too big issue
-    pub fn run(&mut self, url: &str) -> Result<usize, OtaError>{
-
-        use OtaError::*;
+impl FirmwareSlot for OtaDriver {
+    fn capacity(&self) -> u32 {
+        update_partition().map_or(0, |partition| unsafe { (*partition).size })
+    }
 
-        let mut upd = self.ota.initiate_update().map_err(|_| EspBackendFailure)?;
+    fn begin(&mut self, size: u32) -> Result<(), HalError> {
+        self.abort();
+        let partition = update_partition()?;
+        let mut handle = 0;
+        // Erases only the sectors the image needs.
+        esp!(unsafe { esp_ota_begin(partition, size as usize, &mut handle) }).map_err(storage_failed)?;
+        self.update = Some(handle);
+        Ok(())
+    }
 
-        let cfg = Configuration::default();
-        let conn = EspHttpConnection::new(&cfg).map_err(|_| EspBackendFailure)?;
-        let mut client = Client::<EspHttpConnection>::wrap(conn);
-        let mut resp = client
-            .get(url).map_err(|_| UrlUnavailable)?
-            .submit().map_err(|_| UrlUnavailable)?;
+    fn write(&mut self, data: &[u8]) -> Result<(), HalError> {
+        let handle = self.update.ok_or(HalError::StorageFailed)?;
+        esp!(unsafe { esp_ota_write(handle, data.as_ptr() as *const _, data.len()) }).map_err(storage_failed)
+    }
 
-        let mut data_transferred = 0;
+    fn complete(&mut self) -> Result<(), HalError> {
+        let handle = self.update.take().ok_or(HalError::StorageFailed)?;
+        match unsafe { esp_ota_end(handle) } as u32 {
+            ESP_OK => {}
+            ESP_ERR_OTA_VALIDATE_FAILED | ESP_ERR_INVALID_SIZE => return Err(HalError::InvalidImage),
+            code => return Err(storage_failed(EspError::from(code as esp_err_t).unwrap())),
+        }
+        esp!(unsafe { esp_ota_set_boot_partition(update_partition()?) }).map_err(storage_failed)
+    }
 
-        loop {
-            let mut buf = [0u8; 100];
-            let size = resp.read(buf.as_mut_slice()).unwrap();
-            data_transferred = data_transferred + size;
-            upd.write_all(&buf[0..size]).unwrap();
-            if size < 100 {break}
+    fn abort(&mut self) {
+        if let Some(handle) = self.update.take() {
+            unsafe { esp_ota_abort(handle) };
         }
+    }
 
-        upd.complete().map_err(|_| EspBackendFailure)?;
+    fn slots(&self) -> Result<FirmwareSlots, HalError> {
+        let running = self.ota.get_running_slot().map_err(storage_failed)?;
+        // With rollback enabled the update partition is the one booted before.
+        let previous = self.ota.get_update_slot().map_err(storage_failed)?;
+        Ok(FirmwareSlots { running: Some(slot_info(running)), previous: Some(slot_info(previous)) })
+    }
 
-        Ok(data_transferred)
+    fn mark_valid(&mut self) -> Result<(), HalError> {
+        self.ota.mark_running_slot_valid().map_err(storage_failed)
     }
 
-    pub fn restart(self) {
-        restart();
+    fn roll_back(&mut self) -> Result<(), HalError> {
+        // Only returns if there is no image to go back to.
+        Err(storage_failed(self.ota.mark_running_slot_invalid_and_reboot()))
     }
-}
\ No newline at end of file
+}
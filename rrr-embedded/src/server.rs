@@ -1,93 +1,263 @@
 use crate::api;
-use std::io;
-use std::io::ErrorKind;
+use crate::log_storage::LogPartition;
+use crate::ota::OtaDriver;
+use crate::wifi::WiFi;
 
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
 use anyhow::Result;
-use embedded_svc::http::server::{Connection, Request};
-use esp_idf_svc::http::server::EspHttpServer;
+use log::*;
+use embedded_svc::http::Headers;
+use embedded_svc::http::server::Request;
+use embedded_svc::ws::FrameType;
+use esp_idf_svc::errors::EspIOError;
+use esp_idf_svc::http::server::{EspHttpConnection, EspHttpServer};
+use esp_idf_svc::http::server::ws::EspHttpWsDetachedSender;
 use esp_idf_sys::EspError;
 use include_dir::{Dir, include_dir};
+use rrr_api::{ApiError, Encoding};
+use rrr_core::avionics::CommandError;
+use rrr_core::http::{self, CORS_HEADER, LOG_CHUNK_SIZE, MAX_COMMAND_SIZE};
+use rrr_core::logger::FlightLogger;
+use rrr_core::ota::FirmwareUpdater;
+use rrr_core::telemetry::{self, TelemetrySession, MAX_MESSAGE_SIZE};
 
 
 static DIST: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/../rrr-frontend/dist-gz/");
 
+fn send(req: Request<&mut EspHttpConnection>, response: http::Response) -> Result<(), EspIOError> {
+    use embedded_svc::io::Write;
+
+    let mut headers = vec![CORS_HEADER];
+    if let Some(content_type) = response.content_type {
+        headers.push(("Content-Type", content_type));
+    }
+    req.into_response(response.status, response.message, &headers)?.write_all(&response.body)?;
+    Ok(())
+}
+
+/// How often an unsubscribed session checks for a subscription.
+const TELEMETRY_IDLE_MS: u64 = 100;
+
+fn frame_type(encoding: Encoding) -> FrameType {
+    if encoding.is_text() {
+        FrameType::Text(false)
+    } else {
+        FrameType::Binary(false)
+    }
+}
+
+/// Pushes states to one telemetry client until its session is dropped on close.
+fn push_telemetry(session: Weak<Mutex<TelemetrySession>>, mut sender: EspHttpWsDetachedSender, state: Arc<Mutex<api::State>>, started: Instant) {
+    use embedded_svc::ws::Sender;
+
+    loop {
+        let Some(session) = session.upgrade() else { return };
+        let now_ms = started.elapsed().as_millis() as u64;
+        let (message, encoding) = {
+            let mut session = session.lock().unwrap();
+            (session.poll(now_ms, &state), session.encoding())
+        };
+        if let Some(message) = message {
+            if let Err(e) = sender.send(frame_type(encoding), &encoding.encode(&message)) {
+                warn!("Telemetry push failed: {}", e);
+                return;
+            }
+        }
+        let wait_ms = session.lock().unwrap().wait_ms(started.elapsed().as_millis() as u64);
+        drop(session);
+        thread::sleep(Duration::from_millis(wait_ms.unwrap_or(TELEMETRY_IDLE_MS).max(1)));
+    }
+}
+
 pub struct Server {
     server: EspHttpServer,
 }
 
 impl Server {
     pub fn new<F>(
+        info: api::BoardInfo,
         state: Arc<Mutex<api::State>>,
-        mut command_handler: F,
+        logger: Option<Arc<Mutex<FlightLogger<LogPartition>>>>,
+        ota: FirmwareUpdater<OtaDriver>,
+        wifi: WiFi<'static>,
+        command_handler: F,
     ) -> Result<Self>
-        where F: Fn(&api::Command) -> Result<()> + Send + 'static
+        where F: Fn(&api::Command) -> Result<(), CommandError> + Send + Sync + 'static
     {
         use embedded_svc::http::server::{Method};
-        use embedded_svc::io::Write;
-        use esp_idf_svc::http::server::{EspHttpServer};
+        use embedded_svc::io::{Read, Write};
 
         let mut conf = esp_idf_svc::http::server::Configuration::default();
         conf.max_resp_handlers = 100;
         conf.max_uri_handlers = 100;
+        conf.uri_match_wildcard = true;
 
         let mut server = EspHttpServer::new(&conf)?;
+        let command_handler = Arc::new(command_handler);
 
         fn serve_file<'a>(server: &'a mut EspHttpServer, path: &'static str, content: &'static [u8]) -> Result<(), EspError> {
             server.fn_handler(format!("/{}", path).as_ref(), Method::Get, move |req| {
-                let content_type = if path.ends_with(".js") { "application/javascript" } else if path.ends_with(".wasm") { "application/wasm" } else if path.ends_with(".html") { "text/html" } else if path.ends_with(".htm") { "text/html" } else if path.ends_with(".html") { "text/html" } else if path.ends_with(".css") { "text/css" } else { "text/html" };
+                let content_type = http::content_type(path);
 
                 req.into_response(200, None, &[
                     ("Content-Type", content_type),
                     ("Content-Encoding", "gzip"),
-                    ("Access-Control-Allow-Origin", "*"),
+                    CORS_HEADER,
                 ])?.write_all(content)?;
                 Ok(())
             })?;
             Ok(())
         }
 
+        let state_ = state.clone();
+        let ws_command_handler = command_handler.clone();
+        let sessions: Mutex<HashMap<i32, Arc<Mutex<TelemetrySession>>>> = Mutex::new(HashMap::new());
+        let started = Instant::now();
         server
-            .fn_handler("/state", Method::Get, move |req| {
-                let state = state.lock().unwrap().to_owned();
+            .ws_handler("/ws", move |ws| {
+                use embedded_svc::ws::{Receiver, Sender};
 
-                req.into_response(200, None, &[("Content-Type", "application/json"),
-                    ("Access-Control-Allow-Origin", "*"),
-                ])?.write_all(serde_json::to_string(&state).unwrap().as_bytes())?;
+                if ws.is_new() {
+                    info!("Telemetry client {} connected", ws.session());
+                    let session = Arc::new(Mutex::new(TelemetrySession::default()));
+                    let weak_session = Arc::downgrade(&session);
+                    let sender = ws.create_detached_sender()?;
+                    let state = state_.clone();
+                    sessions.lock().unwrap().insert(ws.session(), session);
+                    thread::Builder::new()
+                        .stack_size(8 * 1024)
+                        .spawn(move || push_telemetry(weak_session, sender, state, started))?;
+                    return Ok::<(), anyhow::Error>(());
+                }
+                if ws.is_closed() {
+                    info!("Telemetry client {} disconnected", ws.session());
+                    sessions.lock().unwrap().remove(&ws.session());
+                    return Ok(());
+                }
+
+                let (received, len) = ws.recv(&mut [])?;
+                let encoding = match received {
+                    FrameType::Binary(_) => Encoding::Postcard,
+                    _ => Encoding::Json,
+                };
+                let reply = if len > MAX_MESSAGE_SIZE {
+                    Some(telemetry::message_too_large())
+                } else {
+                    let mut frame = vec![0u8; len];
+                    ws.recv(&mut frame)?;
+                    let session = sessions.lock().unwrap().get(&ws.session()).cloned();
+                    let now_ms = started.elapsed().as_millis() as u64;
+                    session.and_then(|session| session.lock().unwrap().handle(&frame, encoding, now_ms, &*ws_command_handler))
+                };
+                if let Some(reply) = reply {
+                    ws.send(frame_type(encoding), &encoding.encode(&reply))?;
+                }
+                Ok(())
+            })?;
+
+        let state_ = state.clone();
+        let wifi = Mutex::new(wifi);
+        server
+            .fn_handler("/wifi/scan", Method::Get, move |req| {
+                let response = http::get_wifi_scan(&wifi, &state_, req.header("Accept"));
+                send(req, response)?;
+                Ok(())
+            })?;
+
+        let state_ = state.clone();
+        server
+            .fn_handler("/info", Method::Get, move |req| {
+                send(req, http::get_info(&info))?;
+                Ok(())
+            })?
+            .fn_handler("/state", Method::Get, move |req| {
+                let response = http::get_state(&state_, req.header("Accept"));
+                send(req, response)?;
+                Ok(())
+            })?
+            .fn_handler("/selftest", Method::Get, move |req| {
+                let response = http::get_self_test(&state, req.header("Accept"));
+                send(req, response)?;
                 Ok(())
             })?
             .fn_handler("/command", Method::Post, move |mut req| {
-                struct ReqRead<'a, A>
-                {
-                    req: &'a mut Request<A>,
-                }
-
-                impl<'a, A> io::Read for ReqRead<'a, A>
-                    where A: Connection
-                {
-                    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-                        self.req.read(buf).map_err(|e| { io::Error::new(ErrorKind::BrokenPipe, "") })
+                let content_type = req.header("Content-Type").map(str::to_owned);
+                let accept = req.header("Accept").map(str::to_owned);
+                let mut body = Vec::new();
+                let mut buf = [0u8; 256];
+                loop {
+                    let read = req.read(&mut buf)?;
+                    if read == 0 {
+                        break;
+                    }
+                    body.extend_from_slice(&buf[..read]);
+                    if body.len() > MAX_COMMAND_SIZE {
+                        let encoding = Encoding::negotiate(accept.as_deref());
+                        send(req, http::command_failed(ApiError::MalformedCommand, "Command too large".into(), encoding))?;
+                        return Ok(());
                     }
                 }
 
-                let command = serde_json::from_reader::<_, api::Command>(ReqRead { req: &mut req });
+                send(req, http::post_command(&body, content_type.as_deref(), accept.as_deref(), &*command_handler))?;
+                Ok(())
+            })?
+        ;
 
-                //TODO headers (cross-origin, content-type)
-                match command {
-                    Ok(command) => {
-                        match command_handler(&command) {
-                            Ok(_) => { req.into_ok_response()?; }
-                            Err(_) => { req.into_status_response(500)?; }
-                        }
+        let ota = Mutex::new(ota);
+        server
+            .fn_handler("/ota", Method::Post, move |mut req| {
+                let length = req.content_len().map(|len| len as usize);
+                let response = http::post_ota(&ota, length, |buf: &mut [u8]| req.read(buf));
+                send(req, response)?;
+                Ok(())
+            })?;
 
-                        Ok(())
+        let logger_ = logger.clone();
+        server
+            .fn_handler("/logs", Method::Get, move |req| {
+                send(req, http::get_logs(logger_.as_deref()))?;
+                Ok(())
+            })?;
+
+        let logger_ = logger.clone();
+        server
+            .fn_handler("/logs/*", Method::Get, move |req| {
+                let (logger, id) = match http::check_log_download(logger_.as_deref(), req.uri()) {
+                    Ok(download) => download,
+                    Err(response) => {
+                        send(req, response)?;
+                        return Ok(());
                     }
-                    Err(_) => { req.into_response(400, Some("Unable to parse command"), &[]).map(|_| ()) }
-                }?;
+                };
 
+                // The logger is locked only while a chunk is read, so logging
+                // continues during the download.
+                let disposition = http::log_disposition(id);
+                let mut response = req.into_response(200, None, &[("Content-Type", "application/octet-stream"),
+                    ("Content-Disposition", &disposition),
+                    CORS_HEADER,
+                ])?;
+                let mut reader = logger.lock().unwrap().reader(id);
+                let mut buf = [0u8; LOG_CHUNK_SIZE];
+                loop {
+                    let read = logger.lock().unwrap().read(&mut reader, &mut buf)?;
+                    if read == 0 {
+                        break;
+                    }
+                    response.write_all(&buf[..read])?;
+                }
                 Ok(())
-            })?
-        ;
+            })?;
+
+        server
+            .fn_handler("/logs/*", Method::Delete, move |req| {
+                let response = http::delete_log(logger.as_deref(), req.uri());
+                send(req, response)?;
+                Ok(())
+            })?;
 
         let f = DIST.get_file("index.html").unwrap();
         serve_file(&mut server, "", f.contents())?;
@@ -1,10 +1,12 @@
 use std::sync::{Arc, Mutex};
-use embedded_svc::wifi::{Configuration, AccessPointConfiguration, ClientConfiguration, AuthMethod};
+use embedded_svc::wifi::{Configuration, AccessPointConfiguration, AccessPointInfo, ClientConfiguration, AuthMethod};
 use esp_idf_hal::peripheral::Peripheral;
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
-use log::info;
+use log::{error, info, warn};
 use anyhow::Result;
+use rrr_core::hal::{HalError, WifiScanner};
+use rrr_core::wifi::connection_order;
 use crate::api::*;
 
 
@@ -14,8 +16,11 @@ pub struct WiFi<'a> {
 }
 
 impl<'a> WiFi<'a> {
+    /// Joins the first of `networks` that connects. Without stored networks
+    /// the board opens `access_point`; when none of them connects, an open one.
     pub fn new(
-        configuration: WifiConnectionConfiguration,
+        networks: &[WifiCredentials],
+        access_point: WifiCredentials,
         modem: impl Peripheral<P=esp_idf_hal::modem::Modem> + 'static,
         sysloop: EspSystemEventLoop,
         state: Arc<Mutex<State>>,
@@ -23,73 +28,34 @@ impl<'a> WiFi<'a> {
         let esp_wifi = EspWifi::new(modem, sysloop.clone(), None)?;
         let mut wifi = BlockingWifi::wrap(esp_wifi, sysloop)?;
 
-        let cfg = match configuration.clone() {
-            WifiConnectionConfiguration {
-                connection_type: WifiConnectionType::StartAccessPoint,
-                credentials: WifiCredentials { ssid, password }
-            } => {
-                Configuration::AccessPoint(
-                    AccessPointConfiguration {
-                        ssid: heapless::String::from(ssid.as_str()),
-                        channel: 1,
-                        password: heapless::String::from(password.as_str()),
-                        auth_method: AuthMethod::WPA2Personal,
-                        ..Default::default()
-                    })
-            }
-            WifiConnectionConfiguration {
-                connection_type: WifiConnectionType::ConnectToExternal,
-                credentials: WifiCredentials { ssid, password }
-            } => {
-                Configuration::Client(
-                    ClientConfiguration {
-                        ssid: heapless::String::from(ssid.as_str()),
-                        password: heapless::String::from(password.as_str()),
-                        channel: None,
-                        ..Default::default()
-                    },
-                )
-            }
+        let access_point = if networks.is_empty() {
+            Some(access_point)
+        } else if Self::connect_any(&mut wifi, networks, &state) {
+            None
+        } else {
+            info!("WIFI Connect -- FAIL");
+            Some(WifiCredentials { ssid: "RRR-wifi".into(), password: "".into() })
         };
 
-        let client_configuration_result = wifi.set_configuration(&cfg);
-
-        let connection_result = client_configuration_result.and_then(|_| {
+        if let Some(WifiCredentials { ssid, password }) = access_point {
+            // The idle station interface is there for scans.
+            wifi.set_configuration(&Configuration::Mixed(
+                ClientConfiguration::default(),
+                AccessPointConfiguration {
+                    ssid: heapless::String::from(ssid.as_str()),
+                    channel: 1,
+                    password: heapless::String::from(password.as_str()),
+                    auth_method: if password.is_empty() { AuthMethod::None } else { AuthMethod::WPA2Personal },
+                    ..Default::default()
+                },
+            ))?;
             wifi.start()?;
-            wifi.connect()?;
-            info!("WIFI Connect -- OK");
-            let mut configuration = configuration.clone();
-            configuration.credentials.password = "".into();
-            state.lock().unwrap().wifi_state = configuration;
-            Ok(())
-        });
-
-        match connection_result {
-            Ok(_) => (),
-            Err(_) => {
-                info!("WIFI Connect -- FAIL");
-                let ap_configuration_result = wifi.set_configuration(&Configuration::AccessPoint(
-                    AccessPointConfiguration {
-                        ssid: "RRR-wifi".into(),
-                        channel: 1,
-                        ..Default::default()
-                    },
-                ));
-                ap_configuration_result.and_then(|_| {
-                    wifi.start()?;
-                    info!("WIFI AP Start -- OK");
-                    state.lock().unwrap().wifi_state =
-                        WifiConnectionConfiguration {
-                            connection_type: WifiConnectionType::StartAccessPoint,
-                            credentials: WifiCredentials {
-                                ssid: "RRR-wifi".into(),
-                                password: "".into(),
-                            },
-                        };
-                    Ok(())
-                })?;
-            }
-        };
+            info!("WIFI AP Start -- OK");
+            state.lock().unwrap().wifi_state = WifiConnectionConfiguration {
+                connection_type: WifiConnectionType::StartAccessPoint,
+                credentials: WifiCredentials { ssid, password: "".into() },
+            };
+        }
 
         wifi.wait_netif_up()?;
         let ip_info = wifi.wifi().ap_netif().get_ip_info()?;
@@ -97,4 +63,75 @@ impl<'a> WiFi<'a> {
         info!("DHCP info: {:?}", ip_info);
         Ok(Self { wifi, state })
     }
-}
\ No newline at end of file
+
+    /// Tries the stored networks in order, those in range first.
+    fn connect_any(wifi: &mut BlockingWifi<EspWifi<'a>>, networks: &[WifiCredentials], state: &Mutex<State>) -> bool {
+        let found = wifi.set_configuration(&Configuration::Client(ClientConfiguration::default()))
+            .and_then(|_| wifi.start())
+            .and_then(|_| wifi.scan());
+        let found: Vec<WifiNetwork> = match found {
+            Ok(found) => found.into_iter().map(network_info).collect(),
+            Err(e) => {
+                warn!("WIFI Scan -- FAIL: {}", e);
+                Vec::new()
+            }
+        };
+
+        for WifiCredentials { ssid, password } in connection_order(networks, &found) {
+            let configuration = Configuration::Client(ClientConfiguration {
+                ssid: heapless::String::from(ssid.as_str()),
+                password: heapless::String::from(password.as_str()),
+                auth_method: if password.is_empty() { AuthMethod::None } else { AuthMethod::WPA2Personal },
+                channel: None,
+                ..Default::default()
+            });
+            match wifi.set_configuration(&configuration).and_then(|_| wifi.connect()) {
+                Ok(()) => {
+                    info!("WIFI Connect {} -- OK", ssid);
+                    state.lock().unwrap().wifi_state = WifiConnectionConfiguration {
+                        connection_type: WifiConnectionType::ConnectToExternal,
+                        credentials: WifiCredentials { ssid, password: "".into() },
+                    };
+                    return true;
+                }
+                Err(e) => {
+                    info!("WIFI Connect {} -- FAIL: {}", ssid, e);
+                    let _ = wifi.disconnect();
+                }
+            }
+        }
+        false
+    }
+}
+
+fn wifi_auth(method: AuthMethod) -> WifiAuth {
+    match method {
+        AuthMethod::None => WifiAuth::Open,
+        AuthMethod::WEP => WifiAuth::Wep,
+        AuthMethod::WPA => WifiAuth::Wpa,
+        AuthMethod::WPA2Personal | AuthMethod::WPAWPA2Personal => WifiAuth::Wpa2,
+        AuthMethod::WPA3Personal | AuthMethod::WPA2WPA3Personal => WifiAuth::Wpa3,
+        AuthMethod::WPA2Enterprise => WifiAuth::Enterprise,
+        _ => WifiAuth::Other,
+    }
+}
+
+fn network_info(ap: AccessPointInfo) -> WifiNetwork {
+    WifiNetwork {
+        ssid: ap.ssid.as_str().into(),
+        rssi: ap.signal_strength,
+        channel: ap.channel,
+        auth: wifi_auth(ap.auth_method),
+    }
+}
+
+impl WifiScanner for WiFi<'_> {
+    /// Blocks for the scan, about two seconds with the access point up.
+    fn scan(&mut self) -> Result<Vec<WifiNetwork>, HalError> {
+        let found = self.wifi.scan().map_err(|e| {
+            error!("WIFI Scan -- FAIL: {}", e);
+            HalError::NoResponse
+        })?;
+        Ok(found.into_iter().map(network_info).collect())
+    }
+}
@@ -31,15 +31,19 @@ pub struct CardProps {
     pub title: String,
     #[prop_or_default]
     pub icon: Option<String>,
+    /// Greys out the content, for values the board stopped updating.
+    #[prop_or_default]
+    pub stale: bool,
 }
 
 #[function_component]
 pub fn Card(props: &CardProps) -> Html {
     html!(
-        <div class="card">
+        <div class={classes!("card", props.stale.then_some("stale"))}>
             <div class="header">
                 if(props.icon.is_some()) {<MatIcon>{props.icon.clone().unwrap()}</MatIcon>}
                 <h2>{props.title.clone()}</h2>
+                if(props.stale) {<span class="stale-label">{"stale"}</span>}
             </div>
             <div class="card-content">{props.children.clone()}</div>
         </div>
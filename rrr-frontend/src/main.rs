@@ -11,10 +11,11 @@ use yew_hooks::prelude::*;
 use reqwasm::http::Request;
 use wasm_bindgen_futures::spawn_local;
 use serde::{Serialize, Deserialize};
-use serde::de::DeserializeOwned;
 use material_yew::*;
 use material_yew::text_inputs::TextFieldType;
 
+use futures::{SinkExt, StreamExt};
+use gloo::net::websocket::{futures::WebSocket, Message};
 use gloo::timers::callback::{Timeout};
 use wasm_bindgen::JsCast;
 use web_sys::console::log;
@@ -32,52 +33,497 @@ struct RestButtonProps {
 fn RestButton(props: &RestButtonProps) -> Html {
     let text = props.text.clone();
     let commmand: Command = props.command.clone();
+    let send_command = use_send_command();
 
     let onclick: Callback<MouseEvent, ()> = Callback::from(move |_| {
-        let s = "http://rrr.local/command".to_owned();
-        let s = s.clone();
-        let commmand = commmand.clone();
-        spawn_local(async move {
-            Request::post(&s)
-                .body(serde_json::to_string(&commmand).unwrap())
-                .send()
-                .await
-                .unwrap();
-        });
-        ()
+        send_command.emit(commmand.clone());
     });
 
     html! {<span class={if props.equal_size {"equal-size"} else {""}} {onclick}><MatButton label={text} outlined=true/></span>}
 }
 
-static command_uri: &str = "http://rrr.local/command";
+/// Board the frontend talks to. Set `RRR_API_BASE` at build time to use
+/// another one, e.g. `http://localhost:8080` for the virtual board.
+fn api_url(path: &str) -> String {
+    format!("{}{}", option_env!("RRR_API_BASE").unwrap_or("http://rrr.local"), path)
+}
 
-fn send_command(command: Command) {
-    spawn_local(async move {
-        Request::post(command_uri)
-            .body(serde_json::to_string(&command).unwrap())
-            .send()
-            .await
-            .unwrap();
-    });
+/// Error message of the last command, shown until the next one succeeds or it is dismissed.
+#[derive(Clone, PartialEq)]
+struct CommandStatus(UseStateHandle<Option<String>>);
+
+async fn post_command(command: &Command) -> Result<(), String> {
+    let response = Request::post(&api_url("/command"))
+        .body(serde_json::to_string(command).unwrap())
+        .send()
+        .await
+        .map_err(|_| String::from("Board not reachable"))?;
+    command_result(response).await
+}
+
+/// Outcome reported in a `CommandResponse` body.
+async fn command_result(response: reqwasm::http::Response) -> Result<(), String> {
+    match response.json::<CommandResponse>().await {
+        Ok(CommandResponse::Ok) => Ok(()),
+        Ok(CommandResponse::Failed { error, message }) => Err(format!("{}: {}", error, message)),
+        Err(_) => Err(format!("Unexpected response {}", response.status())),
+    }
+}
+
+/// Sends a firmware image to `POST /ota`; the browser sets its `Content-Length`.
+async fn upload_firmware(file: web_sys::File) -> Result<(), String> {
+    let response = Request::post(&api_url("/ota"))
+        .header("Content-Type", "application/octet-stream")
+        .body(file)
+        .send()
+        .await
+        .map_err(|_| String::from("Board not reachable"))?;
+    command_result(response).await
+}
+
+#[hook]
+fn use_send_command() -> Callback<Command> {
+    use_send_command_then(Callback::noop())
+}
+
+/// Like [`use_send_command`], calling `then` once the board answered.
+#[hook]
+fn use_send_command_then(then: Callback<()>) -> Callback<Command> {
+    let status = use_context::<CommandStatus>().expect("CommandStatus not provided");
+    Callback::from(move |command: Command| {
+        if let Err(e) = command.validate() {
+            status.0.set(Some(e.to_string()));
+            return;
+        }
+        let status = status.clone();
+        let then = then.clone();
+        spawn_local(async move {
+            let result = post_command(&command).await;
+            if let Err(message) = &result {
+                log!(format!("command failed: {}", message));
+            }
+            status.0.set(result.err());
+            then.emit(());
+        });
+    })
+}
+
+#[function_component]
+fn CommandStatusBar() -> Html {
+    let status = use_context::<CommandStatus>().expect("CommandStatus not provided");
+    let Some(message) = (*status.0).clone() else {
+        return html! {};
+    };
+    let onclick = move |_| status.0.set(None);
+
+    html! {
+        <div class="command-error">
+            <MatIcon>{"error"}</MatIcon>
+            <span class="message">{message}</span>
+            <span {onclick}><MatIconButton icon="close"/></span>
+        </div>
+    }
+}
+
+/// Outcome of the `GET /info` check done when the frontend connects.
+#[derive(Clone, PartialEq)]
+enum Compatibility {
+    Unknown,
+    Compatible(BoardInfo),
+    Incompatible(BoardInfo),
+    /// The board answered but has no `/info`, its firmware predates the check.
+    NoInfo,
+}
+
+async fn check_compatibility() -> Option<Compatibility> {
+    let response = Request::get(&api_url("/info")).send().await.ok()?;
+    let compatibility = match response.json::<BoardInfo>().await {
+        Ok(info) if info.is_compatible() => Compatibility::Compatible(info),
+        Ok(info) => Compatibility::Incompatible(info),
+        Err(_) => Compatibility::NoInfo,
+    };
+    Some(compatibility)
+}
+
+/// Warns when the board speaks another API version than this frontend.
+#[function_component]
+fn CompatibilityBanner() -> Html {
+    let compatibility = use_state_eq(|| Compatibility::Unknown);
+    let retry = use_state_eq(|| 0u32);
+
+    {
+        let compatibility = compatibility.clone();
+        let retry = retry.clone();
+        use_effect_with_deps(move |attempt| {
+            let attempt = *attempt;
+            spawn_local(async move {
+                match check_compatibility().await {
+                    Some(result) => compatibility.set(result),
+                    // Not reachable yet, ask again once it is.
+                    None => Timeout::new(2000, move || retry.set(attempt + 1)).forget(),
+                }
+            });
+            || ()
+        }, *retry);
+    }
+
+    let message = match &*compatibility {
+        Compatibility::Unknown | Compatibility::Compatible(_) => return html! {},
+        Compatibility::Incompatible(info) => format!(
+            "Board runs API version {} (firmware {}), this app needs version {}. Update the firmware or the app.",
+            info.api_version, info.firmware_version, API_VERSION,
+        ),
+        Compatibility::NoInfo => String::from("Board firmware is too old for this app, update it."),
+    };
+
+    html! {
+        <div class="compatibility-warning">
+            <MatIcon>{"warning"}</MatIcon>
+            <span class="message">{message}</span>
+        </div>
+    }
+}
+
+/// Networks in range of the board, strongest first.
+async fn scan_wifi() -> Result<Vec<WifiNetwork>, String> {
+    let response = Request::get(&api_url("/wifi/scan"))
+        .send()
+        .await
+        .map_err(|_| String::from("Board not reachable"))?;
+    if !response.ok() {
+        return Err(format!("Scan failed: {}", response.status_text()));
+    }
+    response.json().await.map_err(|_| String::from("Unexpected scan result"))
+}
+
+fn signal_icon(rssi: i8) -> &'static str {
+    match rssi {
+        x if x >= -60 => "network_wifi",
+        x if x >= -70 => "network_wifi_3_bar",
+        x if x >= -80 => "network_wifi_2_bar",
+        _ => "network_wifi_1_bar",
+    }
+}
+
+/// The networks the board tries at boot, first to last.
+#[derive(Properties, PartialEq)]
+struct StoredNetworksProps {
+    networks: Vec<String>,
+    send_command: Callback<Command>,
+}
+
+#[function_component]
+fn StoredNetworks(props: &StoredNetworksProps) -> Html {
+    if props.networks.is_empty() {
+        return html! { <div class="wifi-scan-status">{"No networks stored, the board opens its own"}</div> };
+    }
+    let last = props.networks.len() - 1;
+    props.networks.iter().enumerate().map(|(priority, ssid)| {
+        let send = |command: Command| {
+            let send_command = props.send_command.clone();
+            move |_| send_command.emit(command.clone())
+        };
+        let up = send(Command::MoveWifiNetwork { ssid: ssid.clone(), priority: priority.saturating_sub(1) as u8 });
+        let down = send(Command::MoveWifiNetwork { ssid: ssid.clone(), priority: (priority + 1) as u8 });
+        let remove = send(Command::RemoveWifiNetwork { ssid: ssid.clone() });
+        html! {
+            <div class="wifi-network">
+                <span class="ssid">{ssid.clone()}</span>
+                <span onclick={up}><MatIconButton icon="arrow_upward" disabled={priority == 0}/></span>
+                <span onclick={down}><MatIconButton icon="arrow_downward" disabled={priority == last}/></span>
+                <span onclick={remove}><MatIconButton icon="delete"/></span>
+            </div>
+        }
+    }).collect()
 }
 
+/// Picks a network from a scan, or takes an SSID typed in for hidden ones.
 #[function_component]
 fn WifiSettings() -> Html {
+    let networks = use_state(|| None::<Result<Vec<WifiNetwork>, String>>);
+    let scan = use_state_eq(|| 0u32);
     let ssid = use_state(|| String::new());
     let password = use_state(|| String::new());
+    let manual = use_state_eq(|| false);
+    let stored = use_state_eq(|| Vec::<String>::new());
+    let reload = use_state_eq(|| 0u32);
+
+    {
+        let stored = stored.clone();
+        use_effect_with_deps(move |_| {
+            spawn_local(async move {
+                if let Ok(state) = fetch_state().await {
+                    stored.set(state.wifi_networks);
+                }
+            });
+            || ()
+        }, *reload);
+    }
+    let reload_ = reload.clone();
+    let send_command = use_send_command_then(Callback::from(move |_| reload_.set(*reload_ + 1)));
+
+    {
+        let networks = networks.clone();
+        use_effect_with_deps(move |_| {
+            networks.set(None);
+            spawn_local(async move { networks.set(Some(scan_wifi().await)) });
+            || ()
+        }, *scan);
+    }
+
+    let scan_ = scan.clone();
+    let rescan = move |_| scan_.set(*scan_ + 1);
+
+    let list = match &*networks {
+        None => html! { <div class="wifi-scan-status">{"Scanning..."}</div> },
+        Some(Err(message)) => html! { <div class="wifi-scan-status">{message}</div> },
+        Some(Ok(networks)) => networks.iter().map(|network| {
+            let ssid_ = ssid.clone();
+            let password_ = password.clone();
+            let manual_ = manual.clone();
+            let name = network.ssid.clone();
+            let onclick = move |_| {
+                ssid_.set(name.clone());
+                password_.set(String::new());
+                manual_.set(false);
+            };
+            let selected = !*manual && *ssid == network.ssid;
+            html! {
+                <div class={classes!("wifi-network", selected.then_some("selected"))} {onclick}>
+                    <MatIcon>{signal_icon(network.rssi)}</MatIcon>
+                    <span class="ssid">{network.ssid.clone()}</span>
+                    if network.auth.needs_password() { <MatIcon>{"lock"}</MatIcon> }
+                </div>
+            }
+        }).collect::<Html>(),
+    };
+
+    let ssid_ = ssid.clone();
+    let manual_ = manual.clone();
+    let other = move |_| {
+        ssid_.set(String::new());
+        manual_.set(true);
+    };
+
+    // Open networks are only known as such from the scan.
+    let open = !*manual && matches!(&*networks, Some(Ok(networks))
+        if networks.iter().any(|network| network.ssid == *ssid && !network.auth.needs_password()));
 
     let ssid1 = ssid.clone();
     let password1 = password.clone();
+    let send_command_ = send_command.clone();
     let onclick = move |_| {
-        let cmd = Command::SetWifi { ssid: (*ssid1).clone(), password: (*password1).clone() };
-        send_command(cmd);
+        let cmd = Command::AddWifiNetwork { ssid: (*ssid1).clone(), password: (*password1).clone() };
+        send_command_.emit(cmd);
     };
 
     html! { <div>
-                <MatTextField label="ssid" value={(*ssid).clone()} oninput={move |s:String| {ssid.set(s)}}/>
-                <MatTextField label="password" value={(*password).clone()} oninput={move |s:String| {password.set(s)}}/>
-                <span {onclick}><MatButton label="Set wifi" outlined=true/></span>
+                <span class="wifi-scan-title">{"stored"}</span>
+                <StoredNetworks networks={(*stored).clone()} {send_command}/>
+                <HorizontalLayout>
+                    <span class="wifi-scan-title">{"networks"}</span>
+                    <span onclick={rescan}><MatIconButton icon="refresh" disabled={networks.is_none()}/></span>
+                </HorizontalLayout>
+                {list}
+                <div class={classes!("wifi-network", manual.then_some("selected"))} onclick={other}>
+                    <MatIcon>{"edit"}</MatIcon>
+                    <span class="ssid">{"Other network"}</span>
+                </div>
+                if *manual {
+                    <MatTextField label="ssid" value={(*ssid).clone()} oninput={move |s:String| {ssid.set(s)}}/>
+                }
+                if !open {
+                    <MatTextField label="password" value={(*password).clone()} oninput={move |s:String| {password.set(s)}}/>
+                }
+                <span {onclick}><MatButton label="Add network" outlined=true/></span>
+        </div>
+    }
+}
+
+/// Reboot and factory reset, confirmed by typing the phrase the board asks for.
+#[function_component]
+fn ResetSettings() -> Html {
+    let confirm = use_state(|| String::new());
+    let send_command = use_send_command();
+
+    let confirm_ = confirm.clone();
+    let send_command_ = send_command.clone();
+    let reset = move |_| send_command_.emit(Command::Reset { confirm: (*confirm_).clone() });
+    let confirm_ = confirm.clone();
+    let reset_nvs = move |_| send_command.emit(Command::ResetNvs { confirm: (*confirm_).clone() });
+
+    html! { <div>
+                <MatTextField label={format!("type \"{}\" or \"{}\"", RESET_CONFIRMATION, RESET_NVS_CONFIRMATION)}
+                    value={(*confirm).clone()} oninput={move |s: String| confirm.set(s)}/>
+                <span onclick={reset}><MatButton label="Reboot" outlined=true/></span>
+                <span onclick={reset_nvs}><MatButton label="Factory reset" outlined=true/></span>
+        </div>
+    }
+}
+
+fn number_field(label: &'static str, value: &UseStateHandle<String>) -> Html {
+    let value_ = value.clone();
+    html! {
+        <MatTextField outlined=true field_type={TextFieldType::Number} label={label}
+            value={(**value).clone()} oninput={move |s: String| value_.set(s)}/>
+    }
+}
+
+#[function_component]
+fn AltimeterSettings() -> Html {
+    let qnh = use_state(|| String::from("1013.25"));
+    let field_elevation = use_state(|| String::new());
+    let send_command = use_send_command();
+
+    let qnh_ = qnh.clone();
+    let send_command_ = send_command.clone();
+    let set_qnh = move |_| {
+        if let Ok(hpa) = qnh_.parse::<f32>() {
+            send_command_.emit(Command::SetQnh { pressure: hpa * 100f32 });
+        }
+    };
+    let field_elevation_ = field_elevation.clone();
+    let set_field_elevation = move |_| {
+        if let Ok(altitude) = field_elevation_.parse::<f32>() {
+            send_command.emit(Command::SetFieldElevation { altitude });
+        }
+    };
+
+    html! { <div>
+                <RestButton text="Zero altitude" command={Command::ZeroAltitude}/>
+                <HorizontalLayout>
+                    {number_field("QNH, hPa", &qnh)}
+                    <span onclick={set_qnh}><MatButton label="Set QNH" outlined=true/></span>
+                </HorizontalLayout>
+                <HorizontalLayout>
+                    {number_field("field elevation, m", &field_elevation)}
+                    <span onclick={set_field_elevation}><MatButton label="Set elevation" outlined=true/></span>
+                </HorizontalLayout>
+        </div>
+    }
+}
+
+#[function_component]
+fn RecoverySettings() -> Html {
+    let drogue_channel = use_state(|| String::from("1"));
+    let drogue_delay = use_state(|| String::from("0"));
+    let drogue_backup = use_state(|| String::new());
+    let main_channel = use_state(|| String::from("2"));
+    let main_altitude = use_state(|| String::from("150"));
+    let main_backup = use_state(|| String::new());
+    let send_command = use_send_command();
+
+    let fields = (drogue_channel.clone(), drogue_delay.clone(), drogue_backup.clone(),
+                  main_channel.clone(), main_altitude.clone(), main_backup.clone());
+    let onclick = move |_| {
+        let (drogue_channel, drogue_delay, drogue_backup, main_channel, main_altitude, main_backup) = fields.clone();
+        let config = RecoveryConfig {
+            drogue_channel: drogue_channel.parse().ok(),
+            drogue_delay_ms: drogue_delay.parse().unwrap_or(0),
+            drogue_backup_ms: drogue_backup.parse().ok(),
+            main_channel: main_channel.parse().ok(),
+            main_altitude: main_altitude.parse().unwrap_or(RecoveryConfig::default().main_altitude),
+            main_backup_ms: main_backup.parse().ok(),
+            ..Default::default()
+        };
+        send_command.emit(Command::SetRecoveryConfig { config });
+    };
+
+    html! { <div>
+                {number_field("drogue channel", &drogue_channel)}
+                {number_field("drogue delay, ms", &drogue_delay)}
+                {number_field("drogue backup, ms", &drogue_backup)}
+                {number_field("main channel", &main_channel)}
+                {number_field("main altitude, m", &main_altitude)}
+                {number_field("main backup, ms", &main_backup)}
+                <span {onclick}><MatButton label="Set recovery" outlined=true/></span>
+        </div>
+    }
+}
+
+#[function_component]
+fn ServoCalibrationSettings() -> Html {
+    let default = ServoCalibration::default();
+    let channel = use_state(|| String::from("1"));
+    let min_pulse = use_state(|| default.min_pulse_us.to_string());
+    let center_pulse = use_state(|| default.center_pulse_us.to_string());
+    let max_pulse = use_state(|| default.max_pulse_us.to_string());
+    let trim = use_state(|| default.trim_us.to_string());
+    let min_position = use_state(|| default.min_position.to_string());
+    let max_position = use_state(|| default.max_position.to_string());
+    let reverse = use_state(|| false);
+    let send_command = use_send_command();
+
+    let fields = (channel.clone(), min_pulse.clone(), center_pulse.clone(), max_pulse.clone(),
+                  trim.clone(), min_position.clone(), max_position.clone(), reverse.clone());
+    let onclick = move |_| {
+        let (channel, min_pulse, center_pulse, max_pulse, trim, min_position, max_position, reverse) = fields.clone();
+        let default = ServoCalibration::default();
+        let calibration = ServoCalibration {
+            min_pulse_us: min_pulse.parse().unwrap_or(default.min_pulse_us),
+            center_pulse_us: center_pulse.parse().unwrap_or(default.center_pulse_us),
+            max_pulse_us: max_pulse.parse().unwrap_or(default.max_pulse_us),
+            trim_us: trim.parse().unwrap_or(default.trim_us),
+            reverse: *reverse,
+            min_position: min_position.parse().unwrap_or(default.min_position),
+            max_position: max_position.parse().unwrap_or(default.max_position),
+        };
+        send_command.emit(Command::SetServoCalibration { channel: channel.parse().unwrap_or(1), calibration });
+    };
+
+    html! { <div>
+                {number_field("servo channel", &channel)}
+                {number_field("min pulse, us", &min_pulse)}
+                {number_field("center pulse, us", &center_pulse)}
+                {number_field("max pulse, us", &max_pulse)}
+                {number_field("trim, us", &trim)}
+                {number_field("min position", &min_position)}
+                {number_field("max position", &max_position)}
+                <HorizontalLayout>
+                    <div>{"reverse"}</div>
+                    <MatCheckbox checked={*reverse} onchange={move |b| {reverse.set(b);}}/>
+                </HorizontalLayout>
+                <span {onclick}><MatButton label="Set calibration" outlined=true/></span>
+        </div>
+    }
+}
+
+/// Uploads a firmware image, the board reports the progress in the state.
+#[function_component]
+fn FirmwareUpdate() -> Html {
+    let input = use_node_ref();
+    let uploading = use_state_eq(|| false);
+    let status = use_context::<CommandStatus>().expect("CommandStatus not provided");
+
+    let input_ = input.clone();
+    let uploading_ = uploading.clone();
+    let onclick = move |_| {
+        if *uploading_ {
+            return;
+        }
+        let file = input_.cast::<HtmlInputElement>()
+            .and_then(|input| input.files())
+            .and_then(|files| files.get(0));
+        let Some(file) = file else {
+            status.0.set(Some(String::from("Choose a firmware image signed with rrr-sign first")));
+            return;
+        };
+        uploading_.set(true);
+        let status = status.clone();
+        let uploading = uploading_.clone();
+        spawn_local(async move {
+            let result = upload_firmware(file).await;
+            if let Err(message) = &result {
+                log!(format!("firmware upload failed: {}", message));
+            }
+            uploading.set(false);
+            status.0.set(result.err());
+        });
+    };
+
+    html! { <div>
+                <input type="file" accept=".bin" ref={input}/>
+                <span {onclick}><MatButton label={if *uploading {"Uploading..."} else {"Upload firmware"}} outlined=true disabled={*uploading}/></span>
         </div>
     }
 }
@@ -85,13 +531,16 @@ fn WifiSettings() -> Html {
 #[function_component]
 fn App() -> Html {
     let current_tab = use_state(|| 0);
+    let command_status = CommandStatus(use_state(|| None));
 
     let current_tab_ = current_tab.clone();
     let onactivated = move |current_id: usize| { current_tab_.set(current_id) };
 
     html! {
+        <ContextProvider<CommandStatus> context={command_status}>
         <div class={classes!("content-frame")}>
             <div class={classes!("content-root")}>
+                <CompatibilityBanner/>
                 <MatTabBar {onactivated}>
                     <MatTab min_width=true icon="dashboard"/>
                     <MatTab min_width=true icon="bolt"/>
@@ -108,38 +557,92 @@ fn App() -> Html {
                             <RestButton equal_size=true text="GREEN" command={Command::SetLedColor {r: 0, g: 20, b: 0}}/>
                         </HorizontalLayout>
                     </Card>
+                    <Card title="pyro" icon="flare">
+                        <HorizontalLayout>
+                            <RestButton equal_size=true text="ARM" command={Command::ArmPyro}/>
+                            <RestButton equal_size=true text="DISARM" command={Command::DisarmPyro}/>
+                            <RestButton equal_size=true text="FIRE 1" command={Command::FirePyro {channel: 1, duration_ms: 500}}/>
+                            <RestButton equal_size=true text="FIRE 2" command={Command::FirePyro {channel: 2, duration_ms: 500}}/>
+                        </HorizontalLayout>
+                    </Card>
                     <Card title="servo" icon="open_with">
                         <ServoComponent/>
                     </Card>
                 </TabPage>
                 <TabPage id=2 current_id={*current_tab}>
-                    <WifiSettings/>
+                    <Card title="wifi" icon="wifi">
+                        <WifiSettings/>
+                    </Card>
+                    <Card title="altimeter" icon="height">
+                        <AltimeterSettings/>
+                    </Card>
+                    <Card title="recovery" icon="paragliding">
+                        <RecoverySettings/>
+                    </Card>
+                    <Card title="servo calibration" icon="tune">
+                        <ServoCalibrationSettings/>
+                    </Card>
+                    <Card title="firmware" icon="system_update">
+                        <FirmwareUpdate/>
+                    </Card>
+                    <Card title="reset" icon="restart_alt">
+                        <ResetSettings/>
+                    </Card>
                 </TabPage>
             </div>
+            <CommandStatusBar/>
         </div>
+        </ContextProvider<CommandStatus>>
     }
 }
 
-#[function_component]
-fn StateComponent() -> Html {
-    let state = use_state_eq(|| State::default());
-    let update_required = use_state_eq(|| true);
+/// Dashboard refresh rate requested over the telemetry socket.
+const TELEMETRY_PERIOD_MS: u32 = 100;
+/// Delay before trying the socket again after it closed or could not connect.
+const TELEMETRY_RECONNECT_MS: u32 = 5000;
 
-    async fn fetch_state() -> Result<State, Error> {
-        fetch::<State>("http://rrr.local/state".to_string()).await
-    }
+fn ws_url(path: &str) -> String {
+    api_url(path).replacen("http", "ws", 1)
+}
 
-    async fn fetch<T>(url: String) -> Result<T, Error>
-        where
-            T: DeserializeOwned,
-    {
-        let response = Request::get(&url).send().await;
-        if let Ok(data) = response {
-            (data.json::<T>().await).map_or(Err(Error::DeserializeError), |repo| Ok(repo))
-        } else {
-            Err(Error::RequestError)
+/// Feeds states pushed on `/ws` into `state` until the socket closes.
+async fn stream_telemetry(state: UseStateHandle<State>, streaming: UseStateHandle<bool>) {
+    let Ok(socket) = WebSocket::open(&ws_url("/ws")) else {
+        return;
+    };
+    let (mut sink, mut stream) = socket.split();
+    // Binary frames make the board answer in postcard.
+    let subscribe = ClientMessage::Subscribe { period_ms: TELEMETRY_PERIOD_MS };
+    if sink.send(Message::Bytes(Encoding::Postcard.encode(&subscribe))).await.is_err() {
+        return;
+    }
+    while let Some(Ok(message)) = stream.next().await {
+        let Message::Bytes(data) = message else { continue };
+        if let Ok(ServerMessage::State(update)) = Encoding::Postcard.decode(&data) {
+            streaming.set(true);
+            state.set(*update);
         }
     }
+}
+
+/// Asks for postcard, boards without it answer in JSON.
+async fn fetch_state() -> Result<State, Error> {
+    let response = Request::get(&api_url("/state"))
+        .header("Accept", POSTCARD_CONTENT_TYPE)
+        .send()
+        .await
+        .map_err(|_| Error::RequestError)?;
+    let encoding = response.headers().get("Content-Type")
+        .and_then(|content_type| Encoding::from_content_type(&content_type))
+        .unwrap_or_default();
+    let body = response.binary().await.map_err(|_| Error::RequestError)?;
+    encoding.decode(&body).map_err(|_| Error::DeserializeError)
+}
+
+#[function_component]
+fn StateComponent() -> Html {
+    let state = use_state_eq(|| State::default());
+    let update_required = use_state_eq(|| true);
 
     let u3 = update_required.clone();
 
@@ -156,8 +659,27 @@ fn StateComponent() -> Html {
         ans2
     });
 
+    let streaming = use_state_eq(|| false);
+    let reconnect = use_state_eq(|| 0u32);
+    {
+        let state = state.clone();
+        let streaming = streaming.clone();
+        let reconnect = reconnect.clone();
+        use_effect_with_deps(move |attempt| {
+            let attempt = *attempt;
+            spawn_local(async move {
+                stream_telemetry(state, streaming.clone()).await;
+                log!("telemetry socket closed, polling /state");
+                streaming.set(false);
+                Timeout::new(TELEMETRY_RECONNECT_MS, move || reconnect.set(attempt + 1)).forget();
+            });
+            || ()
+        }, *reconnect);
+    }
+
+    // Polling pauses while the socket delivers states and resumes when it closes.
     let u2 = update_required.clone();
-    if *u2 {
+    if *u2 && !*streaming {
         async_request.run();
         u2.set(false);
     }
@@ -176,22 +698,100 @@ fn StateComponent() -> Html {
 
     fn pyro_status(pyro: &PyroChannelState) -> &'static str {
         match pyro {
-            PyroChannelState { fire: true, test_voltage: _ } => { "active!!!" }
-            PyroChannelState { fire: false, test_voltage: tv } if *tv > 1.0f32 => { "connected" }
+            PyroChannelState { fire: true, .. } => { "active!!!" }
+            PyroChannelState { post_fire_continuity: Some(false), .. } => { "fired" }
+            PyroChannelState { post_fire_continuity: Some(true), .. } => { "fired, still connected" }
+            PyroChannelState { test_voltage: tv, .. } if *tv > 1.0f32 => { "connected" }
             _ => { "not connected" }
         }
     }
 
+    fn flight_event(time_ms: &Option<u64>) -> String {
+        match time_ms {
+            None => String::from("-"),
+            Some(t) => format!("{:.1}", *t as f32 / 1000f32),
+        }
+    }
+
+    fn sensor_health(health: &SensorHealth) -> String {
+        match (health.status, &health.last_error) {
+            (SensorStatus::Ok, _) | (_, None) => format!("{:?}", health.status),
+            (status, Some(error)) => format!("{:?}: {} ({} errors)", status, error, health.total_errors),
+        }
+    }
+
+    fn health_class(health: &SensorHealth) -> Option<&'static str> {
+        match health.status {
+            SensorStatus::Ok => None,
+            SensorStatus::Degraded => Some("degraded"),
+            SensorStatus::Failed => Some("failed"),
+        }
+    }
+
+    fn check_class(status: CheckStatus) -> Option<&'static str> {
+        match status {
+            CheckStatus::Pass => None,
+            CheckStatus::Warn => Some("degraded"),
+            CheckStatus::Fail => Some("failed"),
+        }
+    }
+
+    fn slot_info(slot: &Option<SlotInfo>) -> String {
+        match slot {
+            None => String::from("-"),
+            Some(slot) => format!("{} {} ({:?})", slot.label, slot.version.as_deref().unwrap_or("empty"), slot.status),
+        }
+    }
+
+    fn slot_class(slot: &Option<SlotInfo>) -> Option<&'static str> {
+        match slot.as_ref().map(|slot| slot.status) {
+            Some(SlotStatus::Unverified) => Some("degraded"),
+            Some(SlotStatus::Invalid) => Some("failed"),
+            _ => None,
+        }
+    }
+
+    fn ota_status(ota: &OtaState) -> String {
+        match (ota.status, &ota.version) {
+            (OtaStatus::Complete, Some(version)) => format!("{} complete, reboot to start it", version),
+            (OtaStatus::Complete, None) => String::from("Complete, reboot to start it"),
+            (status, Some(version)) => format!("{:?} {}", status, version),
+            (status, None) => format!("{:?}", status),
+        }
+    }
+
+    fn ota_progress(ota: &OtaState) -> String {
+        let percent = if ota.size > 0 { ota.received as f32 * 100.0 / ota.size as f32 } else { 0.0 };
+        format!("{} / {} bytes ({:.0} %)", ota.received, ota.size, percent)
+    }
+
     fn servo_state(servo: &Option<f32>) -> String {
         match servo {
             None => String::from("off"),
-            Some(a) => format!("{:.4}", a),
+            Some(a) => format!("{:+.2}", a),
         }
     }
 
     html! {
         <div class="state">
-            <Card title="battery" icon={battery_icon}>
+            <Card title="preflight" icon="checklist">
+                <HorizontalLayout>
+                    <span class="first-column"><VerticalLayout>
+                        <div>{"ready to fly"}</div>
+                        <div>{"last reset"}</div>
+                        { for state.self_test.checks.iter().filter(|c| c.status != CheckStatus::Pass).map(|c| html!(<div>{format!("{:?}", c.check)}</div>)) }
+                    </VerticalLayout></span>
+                    <VerticalLayout>
+                        <div class={classes!((!state.self_test.ready_to_fly).then_some("failed"))}>{if state.self_test.ready_to_fly {"yes"} else {"no"}}</div>
+                        <div>{format!("{:?}", state.reset_reason)}</div>
+                        { for state.self_test.checks.iter().filter(|c| c.status != CheckStatus::Pass).map(|c| html!(
+                            <div class={classes!(check_class(c.status))}>{c.message.clone().unwrap_or_default()}</div>
+                        )) }
+                    </VerticalLayout>
+                </HorizontalLayout>
+                { for state.self_test.blocking.iter().map(|reason| html!(<div class="failed">{reason}</div>)) }
+            </Card>
+            <Card title="battery" icon={battery_icon} stale={state.samples.battery.stale}>
                 <HorizontalLayout>
                     <span class="first-column"><VerticalLayout>
                         <div>{"Battery charge"}</div>
@@ -211,35 +811,136 @@ fn StateComponent() -> Html {
                     </VerticalLayout>
                 </HorizontalLayout>
             </Card>
-            <Card title="pyro" icon="flare">
+            <Card title="pyro" icon="flare" stale={state.samples.pyro.stale}>
                 <HorizontalLayout>
                     <span class="first-column"><VerticalLayout>
+                        <div>{"armed"}</div>
                         <div>{"channel 1"}</div>
                         <div>{"channel 2"}</div>
                     </VerticalLayout></span>
                     <VerticalLayout>
+                        <div>{if state.pyro.armed {"yes"} else {"no"}}</div>
                         <div>{pyro_status(&state.pyro.channel1)}</div>
                         <div>{pyro_status(&state.pyro.channel2)}</div>
                     </VerticalLayout>
                 </HorizontalLayout>
             </Card>
-            <Card title="barometer" icon="speed">
+            <Card title="barometer" icon="speed" stale={state.samples.barometer.stale}>
                 <HorizontalLayout>
                     <span class="first-column"><VerticalLayout>
-                        <div>{"Altitude"}</div>
+                        <div>{"Altitude AGL"}</div>
+                        <div>{"Altitude MSL"}</div>
+                        <div>{"vertical velocity"}</div>
+                        <div>{"pressure"}</div>
+                        <div>{"QNH"}</div>
                         <div>{"temperature"}</div>
                     </VerticalLayout></span>
                     <VerticalLayout>
-                        <div>{format!("{:.1}", state.barometer.altitude)}</div>
+                        <div>{format!("{:.1}", state.barometer.altitude_agl)}</div>
+                        <div>{format!("{:.1}", state.barometer.altitude_msl)}</div>
+                        <div>{format!("{:.1}", state.barometer.vertical_velocity)}</div>
+                        <div>{format!("{:.1}", state.barometer.pressure / 100f32)}</div>
+                        <div>{format!("{:.1}", state.barometer.qnh / 100f32)}</div>
                         <div>{format!("{:.1}", state.barometer.temperature)}</div>
                     </VerticalLayout>
                     <div class="separator"/>
                     <VerticalLayout>
                         <div>{"M"}</div>
+                        <div>{"M"}</div>
+                        <div>{"M/s"}</div>
+                        <div>{"hPa"}</div>
+                        <div>{"hPa"}</div>
                         <div>{"°C"}</div>
                     </VerticalLayout>
                 </HorizontalLayout>
             </Card>
+            <Card title="flight" icon="rocket_launch" stale={state.samples.barometer.stale}>
+                <HorizontalLayout>
+                    <span class="first-column"><VerticalLayout>
+                        <div>{"phase"}</div>
+                        <div>{"max altitude"}</div>
+                        <div>{"launch"}</div>
+                        <div>{"burnout"}</div>
+                        <div>{"apogee"}</div>
+                        <div>{"landing"}</div>
+                        <div>{"drogue"}</div>
+                        <div>{"main"}</div>
+                    </VerticalLayout></span>
+                    <VerticalLayout>
+                        <div>{format!("{:?}", state.flight.phase)}</div>
+                        <div>{format!("{:.1}", state.flight.max_altitude)}</div>
+                        <div>{flight_event(&state.flight.launch_time_ms)}</div>
+                        <div>{flight_event(&state.flight.burnout_time_ms)}</div>
+                        <div>{flight_event(&state.flight.apogee_time_ms)}</div>
+                        <div>{flight_event(&state.flight.landing_time_ms)}</div>
+                        <div>{flight_event(&state.recovery.drogue_deploy_time_ms)}</div>
+                        <div>{flight_event(&state.recovery.main_deploy_time_ms)}</div>
+                    </VerticalLayout>
+                    <div class="separator"/>
+                    <VerticalLayout>
+                        <div/>
+                        <div>{"M"}</div>
+                        <div>{"s"}</div>
+                        <div>{"s"}</div>
+                        <div>{"s"}</div>
+                        <div>{"s"}</div>
+                        <div>{"s"}</div>
+                        <div>{"s"}</div>
+                    </VerticalLayout>
+                </HorizontalLayout>
+            </Card>
+            <Card title="sensors" icon="sensors">
+                <HorizontalLayout>
+                    <span class="first-column"><VerticalLayout>
+                        <div>{"barometer"}</div>
+                        <div>{"fuel gauge"}</div>
+                        <div>{"continuity"}</div>
+                    </VerticalLayout></span>
+                    <VerticalLayout>
+                        <div class={classes!(health_class(&state.health.barometer))}>{sensor_health(&state.health.barometer)}</div>
+                        <div class={classes!(health_class(&state.health.fuel_gauge))}>{sensor_health(&state.health.fuel_gauge)}</div>
+                        <div class={classes!(health_class(&state.health.continuity))}>{sensor_health(&state.health.continuity)}</div>
+                    </VerticalLayout>
+                </HorizontalLayout>
+            </Card>
+            <Card title="firmware" icon="system_update">
+                <HorizontalLayout>
+                    <span class="first-column"><VerticalLayout>
+                        <div>{"running"}</div>
+                        <div>{"previous"}</div>
+                        if state.ota.status != OtaStatus::Idle {
+                            <div>{"update"}</div>
+                            <div>{"received"}</div>
+                        }
+                        if state.ota.error.is_some() { <div>{"error"}</div> }
+                    </VerticalLayout></span>
+                    <VerticalLayout>
+                        <div class={classes!(slot_class(&state.firmware.running))}>{slot_info(&state.firmware.running)}</div>
+                        <div class={classes!(slot_class(&state.firmware.previous))}>{slot_info(&state.firmware.previous)}</div>
+                        if state.ota.status != OtaStatus::Idle {
+                            <div class={classes!((state.ota.status == OtaStatus::Failed).then_some("failed"))}>
+                                {ota_status(&state.ota)}
+                            </div>
+                            <div>{ota_progress(&state.ota)}</div>
+                        }
+                        if let Some(error) = &state.ota.error { <div class="failed">{error}</div> }
+                    </VerticalLayout>
+                </HorizontalLayout>
+            </Card>
+            <Card title="log" icon="save">
+                <HorizontalLayout>
+                    <span class="first-column"><VerticalLayout>
+                        <div>{"session"}</div>
+                        <div>{"mode"}</div>
+                        <div>{"records"}</div>
+                    </VerticalLayout></span>
+                    <VerticalLayout>
+                        <div>{state.log.session}</div>
+                        <div>{if state.log.recording_fast {"flight"} else {"pad"}}</div>
+                        <div>{format!("{} / {}", state.log.records_written, state.log.capacity_records)}</div>
+                    </VerticalLayout>
+                </HorizontalLayout>
+            </Card>
             <Card title="servo" icon="open_with">
                 <HorizontalLayout>
                     <span class="first-column"><VerticalLayout>
@@ -247,8 +948,8 @@ fn StateComponent() -> Html {
                         <div>{"servo 2"}</div>
                     </VerticalLayout></span>
                     <VerticalLayout>
-                        <div>{servo_state(&state.servo.servo1_duty)}</div>
-                        <div>{servo_state(&state.servo.servo2_duty)}</div>
+                        <div>{servo_state(&state.servo.servo1_position)}</div>
+                        <div>{servo_state(&state.servo.servo2_position)}</div>
                     </VerticalLayout>
                 </HorizontalLayout>
             </Card>
@@ -272,6 +973,8 @@ fn ServoComponent() -> Html {
         }
     }
 
+    let send_command = use_send_command();
+
     let duty1_enabled_ = duty1_enabled.clone();
     let duty2_enabled_ = duty2_enabled.clone();
     let duty1_ = duty1.clone();
@@ -285,7 +988,7 @@ fn ServoComponent() -> Html {
         {Some(((*duty2_) as f32) * 0.01f32)} else {None};
 
         let command = Command::SetPwmDutyCycle {duty_1, duty_2};
-        send_command(command);
+        send_command.emit(command);
     };
 
     html! {
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use rrr_api::LogInfo;
+use serde::{Deserialize, Serialize};
+use crate::format::{decode_frame, frame_session, Frame, FormatError, LogHeader, LogRecord, FRAME_SIZE, HEADER_CHUNK_SIZE};
+
+/// All frames of one logging session found in a log.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct Session {
+    pub id: u16,
+    /// The most recent complete header of the session.
+    pub header: Option<LogHeader>,
+    /// Records ordered by time.
+    pub records: Vec<LogRecord>,
+    /// Frames of this session that could not be used.
+    pub corrupted_frames: u32,
+}
+
+impl Session {
+    pub fn first_sequence(&self) -> Option<u32> {
+        self.records.iter().map(|r| r.sequence).min()
+    }
+
+    pub fn duration_ms(&self) -> u32 {
+        match (self.records.first(), self.records.last()) {
+            (Some(first), Some(last)) => last.time_ms.saturating_sub(first.time_ms),
+            _ => 0,
+        }
+    }
+
+    pub fn max_altitude(&self) -> f32 {
+        self.records.iter().map(|r| r.altitude_agl).fold(0.0, f32::max)
+    }
+}
+
+/// Header chunks grouped by session and by the sequence number of their first chunk.
+#[derive(Default)]
+struct Headers {
+    chunks: BTreeMap<(u16, u32), BTreeMap<u8, [u8; HEADER_CHUNK_SIZE]>>,
+}
+
+impl Headers {
+    fn insert(&mut self, session: u16, sequence: u32, index: u8, data: [u8; HEADER_CHUNK_SIZE]) {
+        let start = sequence.wrapping_sub(index as u32);
+        self.chunks.entry((session, start)).or_default().insert(index, data);
+    }
+
+    /// The latest complete header of each session and the number of chunks
+    /// belonging to incomplete or unreadable headers.
+    fn finish(self) -> BTreeMap<u16, (Option<LogHeader>, u32)> {
+        let mut headers: BTreeMap<u16, (Option<LogHeader>, u32)> = BTreeMap::new();
+        for ((session, _), parts) in self.chunks {
+            let header = parts.get(&0)
+                .map(LogHeader::chunk_count)
+                .filter(|count| (0..*count).all(|i| parts.contains_key(&(i as u8))))
+                .and_then(|count| LogHeader::decode(&parts.values().take(count).copied().collect::<Vec<_>>()).ok());
+            let entry = headers.entry(session).or_default();
+            match header {
+                Some(header) => entry.0 = Some(header),
+                None => entry.1 += parts.len() as u32,
+            }
+        }
+        headers
+    }
+}
+
+/// Decodes a raw log into sessions, ordered by their oldest frame.
+///
+/// Erased and deleted frames are skipped silently. Frames with a bad checksum
+/// or an unknown kind cannot be attributed reliably and are only counted when
+/// their session number matches a session seen elsewhere in the log.
+pub fn decode(data: &[u8]) -> Vec<Session> {
+    let mut sessions: BTreeMap<u16, Session> = BTreeMap::new();
+    let mut oldest: BTreeMap<u16, u32> = BTreeMap::new();
+    let mut headers = Headers::default();
+    let mut rejected: Vec<u16> = Vec::new();
+
+    for buf in data.chunks_exact(FRAME_SIZE) {
+        let buf: &[u8; FRAME_SIZE] = buf.try_into().unwrap();
+        let (session, sequence) = match decode_frame(buf) {
+            Ok(Frame::Sample(record)) => {
+                let (session, sequence) = (record.session, record.sequence);
+                sessions.entry(session).or_default().records.push(record);
+                (session, sequence)
+            }
+            Ok(Frame::HeaderChunk { sequence, session, index, data }) => {
+                headers.insert(session, sequence, index, data);
+                sessions.entry(session).or_default();
+                (session, sequence)
+            }
+            Err(FormatError::Erased | FormatError::Deleted) => continue,
+            Err(_) => {
+                rejected.push(frame_session(buf));
+                continue;
+            }
+        };
+        let oldest = oldest.entry(session).or_insert(sequence);
+        *oldest = (*oldest).min(sequence);
+    }
+
+    for (session, (header, corrupted)) in headers.finish() {
+        let entry = sessions.entry(session).or_default();
+        entry.header = header;
+        entry.corrupted_frames += corrupted;
+    }
+
+    for session in rejected {
+        if let Some(entry) = sessions.get_mut(&session) {
+            entry.corrupted_frames += 1;
+        }
+    }
+
+    let mut sessions: Vec<Session> = sessions.into_iter()
+        .map(|(id, mut session)| {
+            session.id = id;
+            session.records.sort_by_key(|r| (r.time_ms, r.sequence));
+            session
+        })
+        .collect();
+    sessions.sort_by_key(|s| oldest.get(&s.id).copied().unwrap_or(u32::MAX));
+    sessions
+}
+
+#[derive(Default)]
+struct SessionTotals {
+    frames: u32,
+    records: u32,
+    oldest_sequence: u32,
+    first_time_ms: Option<u32>,
+    last_time_ms: u32,
+    max_altitude: f32,
+}
+
+/// Collects per-session totals frame by frame, for listing a log that does
+/// not fit in memory. Only valid frames are counted.
+#[derive(Default)]
+pub struct Summary {
+    sessions: BTreeMap<u16, SessionTotals>,
+    headers: Headers,
+}
+
+impl Summary {
+    pub fn add(&mut self, buf: &[u8; FRAME_SIZE]) {
+        let (session, sequence) = match decode_frame(buf) {
+            Ok(Frame::Sample(record)) => {
+                let totals = self.sessions.entry(record.session).or_insert_with(|| SessionTotals {
+                    oldest_sequence: record.sequence,
+                    ..Default::default()
+                });
+                totals.records += 1;
+                totals.first_time_ms = Some(totals.first_time_ms.map_or(record.time_ms, |t| t.min(record.time_ms)));
+                totals.last_time_ms = totals.last_time_ms.max(record.time_ms);
+                totals.max_altitude = totals.max_altitude.max(record.altitude_agl);
+                (record.session, record.sequence)
+            }
+            Ok(Frame::HeaderChunk { sequence, session, index, data }) => {
+                self.headers.insert(session, sequence, index, data);
+                (session, sequence)
+            }
+            Err(_) => return,
+        };
+
+        let totals = self.sessions.entry(session).or_insert_with(|| SessionTotals {
+            oldest_sequence: sequence,
+            ..Default::default()
+        });
+        totals.frames += 1;
+        totals.oldest_sequence = totals.oldest_sequence.min(sequence);
+    }
+
+    /// Sessions ordered by their oldest frame.
+    pub fn finish(self) -> Vec<LogInfo> {
+        let mut headers = self.headers.finish();
+        let mut sessions: Vec<_> = self.sessions.into_iter()
+            .map(|(id, totals)| {
+                let header = headers.remove(&id).and_then(|(header, _)| header);
+                let info = LogInfo {
+                    id,
+                    size: totals.frames * FRAME_SIZE as u32,
+                    records: totals.records,
+                    start_time: header.and_then(|h| h.start_time),
+                    duration_ms: totals.first_time_ms.map_or(0, |first| totals.last_time_ms - first),
+                    max_altitude: totals.max_altitude,
+                    active: false,
+                };
+                (totals.oldest_sequence, info)
+            })
+            .collect();
+        sessions.sort_by_key(|(oldest, _)| *oldest);
+        sessions.into_iter().map(|(_, info)| info).collect()
+    }
+}
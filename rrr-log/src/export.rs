@@ -0,0 +1,58 @@
+use std::io::{self, Write};
+use crate::decode::Session;
+
+pub const CSV_COLUMNS: [&str; 16] = [
+    "session",
+    "sequence",
+    "time_ms",
+    "phase",
+    "altitude_agl",
+    "vertical_velocity",
+    "pressure",
+    "battery_voltage",
+    "temperature",
+    "pyro_armed",
+    "pyro1_fire",
+    "pyro2_fire",
+    "pyro1_continuity",
+    "pyro2_continuity",
+    "servo1_position",
+    "servo2_position",
+];
+
+/// Writes the records of all sessions as CSV with a header row. Servos that
+/// were switched off are left empty.
+pub fn write_csv<W: Write>(sessions: &[Session], mut out: W) -> io::Result<()> {
+    writeln!(out, "{}", CSV_COLUMNS.join(","))?;
+    let servo = |p: Option<f32>| p.map(|p| p.to_string()).unwrap_or_default();
+
+    for record in sessions.iter().flat_map(|s| &s.records) {
+        writeln!(
+            out,
+            "{},{},{},{:?},{},{},{},{},{},{},{},{},{},{},{},{}",
+            record.session,
+            record.sequence,
+            record.time_ms,
+            record.phase,
+            record.altitude_agl,
+            record.vertical_velocity,
+            record.pressure,
+            record.battery_voltage,
+            record.temperature,
+            record.pyro_armed,
+            record.pyro_fire[0],
+            record.pyro_fire[1],
+            record.pyro_continuity[0],
+            record.pyro_continuity[1],
+            servo(record.servo_position[0]),
+            servo(record.servo_position[1]),
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the sessions, including their headers, as a pretty-printed JSON array.
+pub fn write_json<W: Write>(sessions: &[Session], mut out: W) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut out, sessions)?;
+    writeln!(out)
+}
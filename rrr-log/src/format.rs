@@ -0,0 +1,246 @@
+use rrr_api::{FlightPhase, RecoveryConfig};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Size of every frame in a log.
+pub const FRAME_SIZE: usize = 32;
+/// Version of the header and record layout, stored in every header.
+pub const FORMAT_VERSION: u8 = 1;
+
+const KIND_SAMPLE: u8 = 0x01;
+const KIND_HEADER: u8 = 0x02;
+const PAYLOAD_START: usize = 8;
+const CRC_START: usize = FRAME_SIZE - 2;
+/// Bytes of the header blob carried by each header frame.
+pub const HEADER_CHUNK_SIZE: usize = CRC_START - PAYLOAD_START;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum FormatError {
+    #[error("Frame is erased")]
+    Erased,
+    #[error("Frame was deleted")]
+    Deleted,
+    #[error("Frame checksum mismatch")]
+    BadChecksum,
+    #[error("Unknown frame kind {0}")]
+    UnknownKind(u8),
+    #[error("Unsupported log format version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Malformed log header")]
+    MalformedHeader,
+}
+
+/// Session metadata written when logging starts and again at launch.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct LogHeader {
+    pub firmware_version: String,
+    /// Unix time in seconds when logging started, if the board clock was set.
+    #[serde(default)]
+    pub start_time: Option<u64>,
+    pub pad_interval_ms: u32,
+    pub flight_interval_ms: u32,
+    pub pre_launch_ms: u32,
+    pub qnh: f32,
+    pub ground_pressure: Option<f32>,
+    pub recovery: RecoveryConfig,
+}
+
+/// One sample of everything worth keeping after a flight.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct LogRecord {
+    /// Assigned by the logger, increases monotonically across sessions.
+    pub sequence: u32,
+    /// Assigned by the logger, one session per boot.
+    pub session: u16,
+    pub phase: FlightPhase,
+    pub time_ms: u32,
+    pub altitude_agl: f32,
+    pub vertical_velocity: f32,
+    pub pressure: f32,
+    pub battery_voltage: f32,
+    pub temperature: f32,
+    pub pyro_armed: bool,
+    pub pyro_fire: [bool; 2],
+    pub pyro_continuity: [bool; 2],
+    pub servo_position: [Option<f32>; 2],
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Frame {
+    Sample(LogRecord),
+    HeaderChunk { sequence: u32, session: u16, index: u8, data: [u8; HEADER_CHUNK_SIZE] },
+}
+
+const PHASES: [FlightPhase; 7] = [
+    FlightPhase::Idle,
+    FlightPhase::Armed,
+    FlightPhase::Boost,
+    FlightPhase::Coast,
+    FlightPhase::Apogee,
+    FlightPhase::Descent,
+    FlightPhase::Landed,
+];
+
+/// CRC-16/CCITT-FALSE.
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ (*byte as u16) << 8, |crc, _| {
+            if crc & 0x8000 != 0 { crc << 1 ^ 0x1021 } else { crc << 1 }
+        })
+    })
+}
+
+fn frame(sequence: u32, session: u16, kind: u8, tag: u8, payload: &[u8]) -> [u8; FRAME_SIZE] {
+    let mut buf = [0u8; FRAME_SIZE];
+    buf[0..4].copy_from_slice(&sequence.to_le_bytes());
+    buf[4..6].copy_from_slice(&session.to_le_bytes());
+    buf[6] = kind;
+    buf[7] = tag;
+    buf[PAYLOAD_START..PAYLOAD_START + payload.len()].copy_from_slice(payload);
+    let crc = crc16(&buf[..CRC_START]);
+    buf[CRC_START..].copy_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+/// Sequence number of a frame without validating it; `u32::MAX` for erased flash.
+pub fn frame_sequence(buf: &[u8]) -> u32 {
+    u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])
+}
+
+pub fn frame_session(buf: &[u8]) -> u16 {
+    u16::from_le_bytes([buf[4], buf[5]])
+}
+
+pub fn decode_frame(buf: &[u8; FRAME_SIZE]) -> Result<Frame, FormatError> {
+    if buf.iter().all(|b| *b == 0xFF) {
+        return Err(FormatError::Erased);
+    }
+    if buf.iter().all(|b| *b == 0x00) {
+        return Err(FormatError::Deleted);
+    }
+    if crc16(&buf[..CRC_START]).to_le_bytes() != buf[CRC_START..] {
+        return Err(FormatError::BadChecksum);
+    }
+
+    match buf[6] {
+        KIND_SAMPLE => Ok(Frame::Sample(LogRecord::decode_payload(buf))),
+        KIND_HEADER => {
+            let mut data = [0u8; HEADER_CHUNK_SIZE];
+            data.copy_from_slice(&buf[PAYLOAD_START..CRC_START]);
+            Ok(Frame::HeaderChunk {
+                sequence: frame_sequence(buf),
+                session: frame_session(buf),
+                index: buf[7],
+                data,
+            })
+        }
+        kind => Err(FormatError::UnknownKind(kind)),
+    }
+}
+
+impl LogRecord {
+    /// Sample frame layout, little-endian:
+    ///
+    /// | offset | type | field                                                  |
+    /// |--------|------|--------------------------------------------------------|
+    /// | 0      | u32  | sequence                                               |
+    /// | 4      | u16  | session                                                |
+    /// | 6      | u8   | frame kind, `0x01`                                     |
+    /// | 7      | u8   | bits 0-2 flight phase, 3 armed, 4-5 fire, 6-7 continuity |
+    /// | 8      | u32  | time since boot, ms                                    |
+    /// | 12     | f32  | altitude above ground, m                               |
+    /// | 16     | f32  | vertical velocity, m/s                                 |
+    /// | 20     | f32  | pressure, Pa                                           |
+    /// | 24     | u16  | battery voltage, mV                                    |
+    /// | 26     | i16  | temperature, 0.01 °C                                   |
+    /// | 28     | i8   | servo 1 position × 100, `i8::MIN` = off                |
+    /// | 29     | i8   | servo 2 position × 100, `i8::MIN` = off                |
+    /// | 30     | u16  | CRC-16/CCITT-FALSE of bytes 0..30                      |
+    pub fn encode(&self) -> [u8; FRAME_SIZE] {
+        let phase = PHASES.iter().position(|p| *p == self.phase).unwrap_or(0) as u8;
+        let tag = phase
+            | (self.pyro_armed as u8) << 3
+            | (self.pyro_fire[0] as u8) << 4
+            | (self.pyro_fire[1] as u8) << 5
+            | (self.pyro_continuity[0] as u8) << 6
+            | (self.pyro_continuity[1] as u8) << 7;
+        let servo = |p: Option<f32>| p.map_or(i8::MIN, |p| (p * 100.0).round().clamp(-100.0, 100.0) as i8);
+
+        let mut payload = [0u8; CRC_START - PAYLOAD_START];
+        payload[0..4].copy_from_slice(&self.time_ms.to_le_bytes());
+        payload[4..8].copy_from_slice(&self.altitude_agl.to_le_bytes());
+        payload[8..12].copy_from_slice(&self.vertical_velocity.to_le_bytes());
+        payload[12..16].copy_from_slice(&self.pressure.to_le_bytes());
+        payload[16..18].copy_from_slice(&((self.battery_voltage * 1000.0).round() as u16).to_le_bytes());
+        payload[18..20].copy_from_slice(&((self.temperature * 100.0).round() as i16).to_le_bytes());
+        payload[20] = servo(self.servo_position[0]) as u8;
+        payload[21] = servo(self.servo_position[1]) as u8;
+
+        frame(self.sequence, self.session, KIND_SAMPLE, tag, &payload)
+    }
+
+    fn decode_payload(buf: &[u8; FRAME_SIZE]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+        let f32_at = |i: usize| f32::from_bits(u32_at(i));
+        let flag = |bit: u8| buf[7] & (1 << bit) != 0;
+        let servo = |b: u8| match b as i8 {
+            i8::MIN => None,
+            p => Some(p as f32 / 100.0),
+        };
+
+        Self {
+            sequence: frame_sequence(buf),
+            session: frame_session(buf),
+            phase: PHASES.get((buf[7] & 0x07) as usize).copied().unwrap_or_default(),
+            time_ms: u32_at(8),
+            altitude_agl: f32_at(12),
+            vertical_velocity: f32_at(16),
+            pressure: f32_at(20),
+            battery_voltage: u16_at(24) as f32 / 1000.0,
+            temperature: u16_at(26) as i16 as f32 / 100.0,
+            pyro_armed: flag(3),
+            pyro_fire: [flag(4), flag(5)],
+            pyro_continuity: [flag(6), flag(7)],
+            servo_position: [servo(buf[28]), servo(buf[29])],
+        }
+    }
+}
+
+impl LogHeader {
+    /// Splits the header into consecutive header frames. The header blob is the
+    /// format version byte, a little-endian u16 length and the JSON encoded
+    /// header, cut into 22-byte chunks; byte 7 of each frame is the chunk index.
+    pub fn encode(&self, session: u16, first_sequence: u32) -> Vec<[u8; FRAME_SIZE]> {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        let mut blob = Vec::with_capacity(json.len() + 3);
+        blob.push(FORMAT_VERSION);
+        blob.extend_from_slice(&(json.len() as u16).to_le_bytes());
+        blob.extend_from_slice(&json);
+
+        blob.chunks(HEADER_CHUNK_SIZE)
+            .enumerate()
+            .map(|(index, chunk)| frame(first_sequence + index as u32, session, KIND_HEADER, index as u8, chunk))
+            .collect()
+    }
+
+    /// Number of header frames needed for a blob starting with `first_chunk`.
+    pub fn chunk_count(first_chunk: &[u8; HEADER_CHUNK_SIZE]) -> usize {
+        let length = u16::from_le_bytes([first_chunk[1], first_chunk[2]]) as usize + 3;
+        (length + HEADER_CHUNK_SIZE - 1) / HEADER_CHUNK_SIZE
+    }
+
+    /// Decodes a header from its chunks in index order.
+    pub fn decode(chunks: &[[u8; HEADER_CHUNK_SIZE]]) -> Result<Self, FormatError> {
+        let blob: Vec<u8> = chunks.iter().flatten().copied().collect();
+        let (&version, rest) = blob.split_first().ok_or(FormatError::MalformedHeader)?;
+        if version != FORMAT_VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+        let length = rest.get(0..2)
+            .map(|l| u16::from_le_bytes([l[0], l[1]]) as usize)
+            .ok_or(FormatError::MalformedHeader)?;
+        let json = rest.get(2..2 + length).ok_or(FormatError::MalformedHeader)?;
+        serde_json::from_slice(json).map_err(|_| FormatError::MalformedHeader)
+    }
+}
@@ -0,0 +1,16 @@
+//! Binary flight log format shared by the firmware and host tools.
+//!
+//! A log is a sequence of 32-byte frames, either a raw dump of the `log`
+//! partition or the frames of a single session as served by the board. Each
+//! frame starts with a sequence number, a session number and a frame kind and
+//! ends with a CRC-16, so erased, torn or overwritten frames are detected and
+//! skipped. Frames overwritten with zeros have been deleted. Every session
+//! starts with a [`LogHeader`] split across header frames and repeats it at
+//! launch with the configuration used for the flight.
+
+pub mod decode;
+pub mod export;
+pub mod format;
+
+pub use decode::{decode, Session, Summary};
+pub use format::{LogHeader, LogRecord, FORMAT_VERSION, FRAME_SIZE};
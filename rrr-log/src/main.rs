@@ -0,0 +1,102 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
+use rrr_log::export::{write_csv, write_json};
+
+const USAGE: &str = "\
+Usage: rrr-log <log.bin> [--format csv|json] [--session <id>] [--output <file>]
+
+Decodes a flight log downloaded from the board or dumped from its log
+partition. Without --output the result is written to stdout.";
+
+struct Args {
+    input: String,
+    json: bool,
+    session: Option<u16>,
+    output: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut input = None;
+    let mut json = false;
+    let mut session = None;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" | "-f" => match args.next().as_deref() {
+                Some("csv") => json = false,
+                Some("json") => json = true,
+                other => return Err(format!("Unknown format {:?}", other.unwrap_or(""))),
+            },
+            "--session" | "-s" => {
+                let value = args.next().ok_or("Missing session id")?;
+                session = Some(value.parse().map_err(|_| format!("Invalid session id {}", value))?);
+            }
+            "--output" | "-o" => output = Some(args.next().ok_or("Missing output file")?),
+            "--help" | "-h" => return Err(String::new()),
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+
+    Ok(Args { input: input.ok_or("Missing input file")?, json, session, output })
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{}\n", message);
+            }
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let data = match std::fs::read(&args.input) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", args.input, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut sessions = rrr_log::decode(&data);
+    if let Some(id) = args.session {
+        sessions.retain(|s| s.id == id);
+    }
+    for session in &sessions {
+        let corrupted = session.corrupted_frames;
+        eprintln!(
+            "session {}: {} records, {:.1} s, max {:.1} m{}{}",
+            session.id,
+            session.records.len(),
+            session.duration_ms() as f32 / 1000.0,
+            session.max_altitude(),
+            if session.header.is_none() { ", no header" } else { "" },
+            if corrupted > 0 { format!(", {} corrupted frames", corrupted) } else { String::new() },
+        );
+    }
+
+    let result = match &args.output {
+        Some(path) => File::create(path).and_then(|file| write(&args, &sessions, BufWriter::new(file))),
+        None => write(&args, &sessions, io::stdout().lock()),
+    };
+    if let Err(err) = result {
+        eprintln!("Failed to write output: {}", err);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn write<W: Write>(args: &Args, sessions: &[rrr_log::Session], mut out: W) -> io::Result<()> {
+    if args.json {
+        write_json(sessions, &mut out)?;
+    } else {
+        write_csv(sessions, &mut out)?;
+    }
+    out.flush()
+}
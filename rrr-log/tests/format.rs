@@ -0,0 +1,169 @@
+use rrr_api::{FlightPhase, RecoveryConfig};
+use rrr_log::export::{write_csv, write_json, CSV_COLUMNS};
+use rrr_log::format::{decode_frame, Frame, FormatError};
+use rrr_log::*;
+
+fn record(sequence: u32, time_ms: u32) -> LogRecord {
+    LogRecord {
+        sequence,
+        session: 7,
+        phase: FlightPhase::Coast,
+        time_ms,
+        altitude_agl: 432.5,
+        vertical_velocity: -12.25,
+        pressure: 95_123.5,
+        battery_voltage: 3.912,
+        temperature: -4.56,
+        pyro_armed: true,
+        pyro_fire: [false, true],
+        pyro_continuity: [true, false],
+        servo_position: [Some(-0.5), None],
+    }
+}
+
+fn header() -> LogHeader {
+    LogHeader {
+        firmware_version: "1.2.3".into(),
+        start_time: None,
+        pad_interval_ms: 1000,
+        flight_interval_ms: 20,
+        pre_launch_ms: 3000,
+        qnh: 101_325.0,
+        ground_pressure: Some(97_000.0),
+        recovery: RecoveryConfig { drogue_channel: Some(1), main_channel: Some(2), ..Default::default() },
+    }
+}
+
+fn log_bytes(frames: &[[u8; FRAME_SIZE]]) -> Vec<u8> {
+    frames.iter().flatten().copied().collect()
+}
+
+#[test]
+fn record_round_trips_through_encoding() {
+    let record = record(123_456, 987_654);
+    assert_eq!(decode_frame(&record.encode()), Ok(Frame::Sample(record)));
+}
+
+#[test]
+fn every_phase_round_trips() {
+    for phase in [
+        FlightPhase::Idle,
+        FlightPhase::Armed,
+        FlightPhase::Boost,
+        FlightPhase::Coast,
+        FlightPhase::Apogee,
+        FlightPhase::Descent,
+        FlightPhase::Landed,
+    ] {
+        let record = LogRecord { phase, pyro_continuity: [true, true], ..record(1, 2) };
+        assert_eq!(decode_frame(&record.encode()), Ok(Frame::Sample(record)));
+    }
+}
+
+#[test]
+fn header_round_trips_through_frames() {
+    let frames = header().encode(7, 100);
+    assert!(frames.len() > 1);
+
+    let sessions = decode(&log_bytes(&frames));
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, 7);
+    assert_eq!(sessions[0].header, Some(header()));
+}
+
+#[test]
+fn corrupted_frames_are_rejected() {
+    let mut frame = record(1, 2).encode();
+    frame[12] ^= 0x01;
+    assert_eq!(decode_frame(&frame), Err(FormatError::BadChecksum));
+    assert_eq!(decode_frame(&[0xFF; FRAME_SIZE]), Err(FormatError::Erased));
+
+    let mut frames = header().encode(7, 0);
+    let first = frames.len() as u32;
+    frames.extend((0..10).map(|i| record(first + i, i * 20).encode()));
+    frames[first as usize + 3][20] ^= 0x80;
+
+    let sessions = decode(&log_bytes(&frames));
+    assert_eq!(sessions[0].records.len(), 9);
+    assert_eq!(sessions[0].corrupted_frames, 1);
+    assert!(sessions[0].header.is_some());
+}
+
+#[test]
+fn incomplete_header_is_ignored() {
+    let mut frames = header().encode(7, 0);
+    frames.remove(1);
+    frames.push(record(50, 0).encode());
+
+    let sessions = decode(&log_bytes(&frames));
+    assert_eq!(sessions[0].header, None);
+    assert_eq!(sessions[0].records.len(), 1);
+}
+
+#[test]
+fn latest_header_of_a_session_wins() {
+    let mut frames = header().encode(7, 0);
+    let launch = LogHeader { qnh: 99_000.0, ..header() };
+    frames.extend(launch.encode(7, 100));
+
+    assert_eq!(decode(&log_bytes(&frames))[0].header, Some(launch));
+}
+
+#[test]
+fn sessions_are_ordered_by_age_even_after_wrap_around() {
+    let mut frames: Vec<_> = (0..5).map(|i| LogRecord { session: 2, ..record(100 + i, i * 20) }.encode()).collect();
+    frames.extend((0..5).map(|i| LogRecord { session: 1, ..record(10 + i, i * 20) }.encode()));
+    frames.push([0xFF; FRAME_SIZE]);
+
+    let sessions = decode(&log_bytes(&frames));
+    assert_eq!(sessions.iter().map(|s| s.id).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(sessions[1].duration_ms(), 80);
+}
+
+#[test]
+fn csv_has_one_row_per_record() {
+    let frames: Vec<_> = (0..3).map(|i| record(i, i * 20).encode()).collect();
+    let mut csv = Vec::new();
+    write_csv(&decode(&log_bytes(&frames)), &mut csv).unwrap();
+
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0].split(',').count(), CSV_COLUMNS.len());
+    assert_eq!(lines[2], "7,1,20,Coast,432.5,-12.25,95123.5,3.912,-4.56,true,false,true,true,false,-0.5,");
+}
+
+#[test]
+fn json_round_trips() {
+    let mut frames = header().encode(7, 0);
+    frames.extend((0..3).map(|i| record(10 + i, i * 20).encode()));
+    let sessions = decode(&log_bytes(&frames));
+
+    let mut json = Vec::new();
+    write_json(&sessions, &mut json).unwrap();
+    let parsed: Vec<Session> = serde_json::from_slice(&json).unwrap();
+    assert_eq!(parsed, sessions);
+}
+
+#[test]
+fn summary_matches_full_decode() {
+    let mut frames = LogHeader { start_time: Some(1_700_000_000), ..header() }.encode(3, 0);
+    let first = frames.len() as u32;
+    frames.extend((0..10).map(|i| LogRecord { session: 3, altitude_agl: i as f32, ..record(first + i, i * 20) }.encode()));
+    frames[first as usize] = [0; FRAME_SIZE];
+
+    let mut summary = Summary::default();
+    frames.iter().for_each(|frame| summary.add(frame));
+    let info = summary.finish();
+    let sessions = decode(&log_bytes(&frames));
+
+    assert_eq!(info.len(), 1);
+    assert_eq!(info[0].id, 3);
+    assert_eq!(info[0].records, sessions[0].records.len() as u32);
+    assert_eq!(info[0].records, 9);
+    assert_eq!(info[0].size, (frames.len() as u32 - 1) * FRAME_SIZE as u32);
+    assert_eq!(info[0].start_time, Some(1_700_000_000));
+    assert_eq!(info[0].duration_ms, sessions[0].duration_ms());
+    assert_eq!(info[0].max_altitude, sessions[0].max_altitude());
+    assert_eq!(sessions[0].corrupted_frames, 0);
+}
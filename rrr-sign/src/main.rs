@@ -0,0 +1,132 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rrr_core::signing::*;
+
+const USAGE: &str = "\
+Usage: rrr-sign keygen <secret.key>
+       rrr-sign sign --key <secret.key> <firmware.bin> [--output <file>]
+       rrr-sign verify --public-key <hex> <signed.bin>
+
+Signs firmware images for POST /ota. keygen writes a new secret key and prints
+its public key, which the firmware is built with as RRR_OTA_PUBLIC_KEY. sign
+appends the signature to the image, written to <firmware>.signed.bin without
+--output.";
+
+enum Args {
+    Keygen { key: String },
+    Sign { key: String, input: String, output: Option<String> },
+    Verify { public_key: String, input: String },
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let command = args.next().ok_or("Missing command")?;
+    let mut key = None;
+    let mut public_key = None;
+    let mut input = None;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--key" | "-k" if command == "sign" => key = Some(args.next().ok_or("Missing key file")?),
+            "--public-key" | "-p" if command == "verify" => public_key = Some(args.next().ok_or("Missing public key")?),
+            "--output" | "-o" if command == "sign" => output = Some(args.next().ok_or("Missing output file")?),
+            "--help" | "-h" => return Err(String::new()),
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+
+    match command.as_str() {
+        "keygen" => Ok(Args::Keygen { key: input.ok_or("Missing key file")? }),
+        "sign" => Ok(Args::Sign {
+            key: key.ok_or("Missing --key")?,
+            input: input.ok_or("Missing input file")?,
+            output,
+        }),
+        "verify" => Ok(Args::Verify {
+            public_key: public_key.ok_or("Missing --public-key")?,
+            input: input.ok_or("Missing input file")?,
+        }),
+        "--help" | "-h" => Err(String::new()),
+        _ => Err(format!("Unknown command {}", command)),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{}\n", message);
+            }
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match args {
+        Args::Keygen { key } => keygen(&key),
+        Args::Sign { key, input, output } => sign(&key, &input, output),
+        Args::Verify { public_key, input } => verify(&public_key, &input),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn keygen(path: &str) -> Result<(), String> {
+    let mut secret = [0; SECRET_KEY_LENGTH];
+    getrandom::getrandom(&mut secret).map_err(|e| format!("No random numbers: {}", e))?;
+    let key = SigningKey::from_bytes(&secret);
+
+    // An existing key is never replaced, boards built with it would refuse every update.
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    writeln!(file, "{}", to_hex(&secret)).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+
+    eprintln!("Build the firmware with RRR_OTA_PUBLIC_KEY set to");
+    println!("{}", to_hex(key.verifying_key().as_bytes()));
+    Ok(())
+}
+
+fn read_key(path: &str) -> Result<SigningKey, String> {
+    let hex = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let secret = from_hex(hex.trim())
+        .and_then(|bytes| <[u8; SECRET_KEY_LENGTH]>::try_from(bytes).ok())
+        .ok_or_else(|| format!("{} is not a secret key", path))?;
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+fn sign(key: &str, input: &str, output: Option<String>) -> Result<(), String> {
+    let key = read_key(key)?;
+    let image = std::fs::read(input).map_err(|e| format!("Failed to read {}: {}", input, e))?;
+    if verify_image(&image, &key.verifying_key()).is_ok() {
+        return Err(format!("{} is signed already", input));
+    }
+    let signed_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let signed = sign_image(&image, &key, signed_at);
+
+    let output = output.unwrap_or_else(|| Path::new(input).with_extension("signed.bin").display().to_string());
+    std::fs::write(&output, signed).map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    eprintln!("Signed {} bytes with {}, written to {}", image.len(), to_hex(key.verifying_key().as_bytes()), output);
+    Ok(())
+}
+
+fn verify(public_key: &str, input: &str) -> Result<(), String> {
+    let key = parse_public_key(public_key).map_err(|e| e.to_string())?;
+    let signed = std::fs::read(input).map_err(|e| format!("Failed to read {}: {}", input, e))?;
+    let trailer = verify_image(&signed, &key).map_err(|e| format!("{}: {}", input, e))?;
+    eprintln!("{}: {} bytes signed at {}", input, trailer.image_size, trailer.signed_at);
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use std::fs;
+use std::process::Command;
+use rrr_core::mock::firmware_image;
+use rrr_core::ota::ESP32C3_CHIP_ID;
+use rrr_core::signing::{parse_public_key, verify_image, TRAILER_SIZE};
+
+fn rrr_sign(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_rrr-sign")).args(args).output().unwrap()
+}
+
+#[test]
+fn keygen_sign_verify() {
+    let dir = std::env::temp_dir().join(format!("rrr-sign-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).display().to_string();
+
+    let output = rrr_sign(&["keygen", &path("secret.key")]);
+    assert!(output.status.success());
+    let public_key = String::from_utf8(output.stdout).unwrap();
+    let key = parse_public_key(&public_key).unwrap();
+    // The key is not replaced.
+    assert!(!rrr_sign(&["keygen", &path("secret.key")]).status.success());
+
+    let image = firmware_image("1.2.0", ESP32C3_CHIP_ID, 3000);
+    fs::write(path("firmware.bin"), &image).unwrap();
+    assert!(rrr_sign(&["sign", "--key", &path("secret.key"), &path("firmware.bin")]).status.success());
+    let signed = fs::read(path("firmware.signed.bin")).unwrap();
+    assert_eq!(signed.len(), image.len() + TRAILER_SIZE);
+    assert_eq!(verify_image(&signed, &key).unwrap().image_size, 3000);
+
+    assert!(rrr_sign(&["verify", "--public-key", public_key.trim(), &path("firmware.signed.bin")]).status.success());
+    assert!(!rrr_sign(&["verify", "--public-key", public_key.trim(), &path("firmware.bin")]).status.success());
+    assert!(!rrr_sign(&["sign", "--key", &path("secret.key"), &path("firmware.signed.bin"), "-o", &path("twice.bin")]).status.success());
+    assert!(!rrr_sign(&["sign", &path("firmware.bin")]).status.success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,10 @@
+use std::process::Command;
+
+/// Exposes the commit being built as `RRR_GIT_HASH`, left unset outside a checkout.
+fn main() {
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    let output = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output().ok();
+    if let Some(output) = output.filter(|o| o.status.success()) {
+        println!("cargo:rustc-env=RRR_GIT_HASH={}", String::from_utf8_lossy(&output.stdout).trim());
+    }
+}
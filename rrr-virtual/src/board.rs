@@ -0,0 +1,172 @@
+//! The firmware's subsystems wired to simulated hardware.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use log::*;
+use rrr_api::{BoardInfo, ResetReason, WifiAuth, WifiNetwork};
+use rrr_core::avionics::{log_header, Avionics, CommandError, BAROMETER_PERIOD_MS};
+use rrr_core::hal::BatteryReading;
+use rrr_core::logger::{FlightLogger, LogError, LoggerConfig};
+use rrr_core::mock::*;
+use rrr_core::ota::{FirmwareUpdater, ESP32C3_CHIP_ID};
+use rrr_core::selftest::SelfTest;
+use rrr_core::signing::{parse_public_key, VerifyingKey};
+use rrr_log::LogHeader;
+use thiserror::Error;
+use crate::profile::Profile;
+
+/// Size of the simulated log partition.
+pub const LOG_SECTORS: usize = 64;
+pub const LOG_SECTOR_SIZE: u32 = 4096;
+/// Size of the simulated update partition, as `ota_0` on the board.
+pub const FIRMWARE_SLOT_SIZE: u32 = 0x1E0000;
+
+/// Voltage measured across an intact igniter.
+const IGNITER_VOLTS: f32 = 3.3;
+/// Battery discharge while powered, percent per hour.
+const DISCHARGE_RATE: f32 = -6.0;
+const BOARD_NAME: &str = "virtual";
+
+pub type VirtualAvionics = Avionics<MockPyroOutput, MockServoOutput, MockStatusLed, MemorySettings>;
+
+#[derive(Error, Debug)]
+pub enum BoardError {
+    #[error(transparent)]
+    Avionics(#[from] CommandError),
+    #[error(transparent)]
+    Logger(#[from] LogError),
+}
+
+/// Handles to the simulated devices stay available to drive the inputs and
+/// inspect the outputs while the avionics run.
+pub struct VirtualBoard {
+    pub avionics: VirtualAvionics,
+    pub logger: Arc<Mutex<FlightLogger<MemoryStorage>>>,
+    pub barometer: MockBarometer,
+    pub fuel_gauge: MockFuelGauge,
+    pub continuity: MockContinuity,
+    pub pyro: [MockPyroOutput; 2],
+    pub servos: [MockServoOutput; 2],
+    pub led: MockStatusLed,
+    pub settings: MemorySettings,
+    /// Receives the images uploaded to `POST /ota`.
+    pub firmware: MockFirmwareSlot,
+    /// Key uploaded images must be signed with, `RRR_OTA_PUBLIC_KEY` at build time.
+    pub ota_key: Option<VerifyingKey>,
+    /// Networks `GET /wifi/scan` finds.
+    pub wifi: MockWifiScanner,
+}
+
+impl VirtualBoard {
+    pub fn new() -> Result<Self, BoardError> {
+        let pyro: [MockPyroOutput; 2] = Default::default();
+        let servos: [MockServoOutput; 2] = Default::default();
+        let led = MockStatusLed::default();
+        let settings = MemorySettings::default();
+        let avionics = Avionics::new(pyro.to_vec(), servos.clone(), led.clone(), settings.clone())?;
+        avionics.state().lock().unwrap().reset_reason = ResetReason::PowerOn;
+
+        let header = log_header(&avionics.state().lock().unwrap(), &Self::header_template());
+        let storage = MemoryStorage::new(LOG_SECTORS, LOG_SECTOR_SIZE);
+        let logger = FlightLogger::new(storage, LoggerConfig::default(), header)?;
+
+        let continuity = MockContinuity::new(2);
+        continuity.set_volts(1, IGNITER_VOLTS);
+        continuity.set_volts(2, IGNITER_VOLTS);
+
+        let wifi = MockWifiScanner::default();
+        wifi.set_networks(vec![
+            WifiNetwork { ssid: "field-hotspot".into(), rssi: -58, channel: 6, auth: WifiAuth::Wpa2 },
+            WifiNetwork { ssid: "club-guest".into(), rssi: -81, channel: 1, auth: WifiAuth::Open },
+            WifiNetwork { ssid: "clubhouse".into(), rssi: -72, channel: 11, auth: WifiAuth::Wpa3 },
+        ]);
+
+        Ok(Self {
+            avionics,
+            logger: Arc::new(Mutex::new(logger)),
+            barometer: MockBarometer::default(),
+            fuel_gauge: MockFuelGauge::default(),
+            continuity,
+            pyro,
+            servos,
+            led,
+            settings,
+            firmware: MockFirmwareSlot::new(FIRMWARE_SLOT_SIZE, env!("CARGO_PKG_VERSION")),
+            ota_key: Self::ota_key(),
+            wifi,
+        })
+    }
+
+    /// The `GET /info` reply of the virtual board.
+    pub fn info(&self) -> BoardInfo {
+        self.avionics.board_info(BOARD_NAME, env!("CARGO_PKG_VERSION"), option_env!("RRR_GIT_HASH"))
+    }
+
+    /// Writes uploaded images to the simulated update partition. It takes
+    /// the board's images, which the virtual board never boots.
+    pub fn firmware_updater(&self) -> FirmwareUpdater<MockFirmwareSlot> {
+        FirmwareUpdater::new(self.firmware.clone(), self.avionics.state(), ESP32C3_CHIP_ID, self.ota_key)
+    }
+
+    fn ota_key() -> Option<VerifyingKey> {
+        let key = option_env!("RRR_OTA_PUBLIC_KEY")?;
+        parse_public_key(key).map_err(|e| error!("RRR_OTA_PUBLIC_KEY: {}", e)).ok()
+    }
+
+    fn header_template() -> LogHeader {
+        LogHeader {
+            firmware_version: format!("virtual-{}", env!("CARGO_PKG_VERSION")),
+            ..Default::default()
+        }
+    }
+
+    /// Runs the boot-time self-test on the simulated devices.
+    pub fn self_test(&self) {
+        let mut test = SelfTest::default();
+        test.check_fuel_gauge(&mut self.fuel_gauge.clone());
+        test.check_barometer(&mut self.barometer.clone());
+        test.check_continuity(&mut self.continuity.clone());
+        test.check_settings(&mut self.settings.clone());
+        test.check_log(Some(&*self.logger));
+        test.check_firmware(&self.firmware);
+        self.avionics.finish_self_test(test);
+        if let Err(e) = self.firmware_updater().confirm_boot() {
+            error!("Confirming the firmware failed: {}", e);
+        }
+    }
+
+    /// Starts the firmware loops and a thread moving the simulated sensors along `profile`.
+    pub fn start(&self, profile: Profile) {
+        self.self_test();
+        self.avionics.spawn_barometer(self.barometer.clone());
+        self.avionics.spawn_fuel_gauge(self.fuel_gauge.clone());
+        self.avionics.spawn_continuity(self.continuity.clone());
+        self.avionics.spawn_pyro_poll();
+        self.avionics.spawn_staleness_check();
+        let header = log_header(&self.avionics.state().lock().unwrap(), &Self::header_template());
+        self.avionics.spawn_logger(self.logger.clone(), header);
+
+        let avionics = self.avionics.clone();
+        let barometer = self.barometer.clone();
+        let fuel_gauge = self.fuel_gauge.clone();
+        let continuity = self.continuity.clone();
+        let pyro = self.pyro.clone();
+        thread::spawn(move || loop {
+            let time_ms = avionics.now_ms();
+            barometer.set(profile.pressure(time_ms), 20.0);
+
+            let soc = (100.0 + DISCHARGE_RATE * time_ms as f32 / 3_600_000.0).max(0.0);
+            fuel_gauge.set(BatteryReading { soc, voltage: 3.3 + 0.9 * soc / 100.0, charge_rate: DISCHARGE_RATE });
+
+            // An igniter is open once it has been fired.
+            for (channel, output) in (1..).zip(&pyro) {
+                if output.history().contains(&true) {
+                    continuity.set_volts(channel, 0.0);
+                }
+            }
+
+            thread::sleep(Duration::from_millis(BAROMETER_PERIOD_MS));
+        });
+    }
+}
@@ -0,0 +1,7 @@
+//! A virtual board: the firmware's host-independent logic driven by simulated
+//! sensors and served over the same HTTP API as the real board, for working
+//! on the frontend and ground tools without hardware.
+
+pub mod board;
+pub mod profile;
+pub mod server;
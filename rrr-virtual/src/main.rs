@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+use log::*;
+use rrr_core::avionics::RESET_DELAY_MS;
+use rrr_virtual::board::VirtualBoard;
+use rrr_virtual::profile::{Profile, RecordedTrace, ScriptedFlight};
+use rrr_virtual::server::Server;
+
+const USAGE: &str = "\
+Usage: rrr-virtual [--port <port>] [--dist <dir>] [--launch-after <s>] [--trace <file.csv>]
+
+Runs the flight computer firmware against simulated sensors and serves its
+HTTP API and the frontend on http://localhost:<port> (default 8080).
+
+  --dist          Built frontend to serve, defaults to ../rrr-frontend/dist.
+  --launch-after  Seconds until the scripted flight lifts off (default 30).
+  --trace         Replays recorded barometer data instead, as time_ms,pressure lines.";
+
+struct Args {
+    port: u16,
+    dist: PathBuf,
+    profile: Profile,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut port = 8080;
+    let mut dist = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../rrr-frontend/dist"));
+    let mut flight = ScriptedFlight::default();
+    let mut trace = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" | "-p" => {
+                let value = args.next().ok_or("Missing port")?;
+                port = value.parse().map_err(|_| format!("Invalid port {}", value))?;
+            }
+            "--dist" | "-d" => dist = PathBuf::from(args.next().ok_or("Missing frontend directory")?),
+            "--launch-after" | "-l" => {
+                let value = args.next().ok_or("Missing launch time")?;
+                let seconds: f32 = value.parse().map_err(|_| format!("Invalid launch time {}", value))?;
+                flight.launch_ms = (seconds * 1000.0) as u64;
+            }
+            "--trace" | "-t" => {
+                let file = args.next().ok_or("Missing trace file")?;
+                let csv = std::fs::read_to_string(&file).map_err(|e| format!("Cannot read {}: {}", file, e))?;
+                trace = Some(RecordedTrace::parse(&csv).map_err(|e| format!("{}: {}", file, e))?);
+            }
+            "--help" | "-h" => return Err(String::new()),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+
+    let profile = match trace {
+        Some(trace) => Profile::Recorded(trace),
+        None => Profile::Scripted(flight),
+    };
+    Ok(Args { port, dist, profile })
+}
+
+/// Prints log messages to stderr, the host counterpart of the ESP-IDF logger.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{} {}: {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{}\n", message);
+            }
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    log::set_logger(&StderrLogger).unwrap();
+    log::set_max_level(LevelFilter::Info);
+
+    let board = match VirtualBoard::new() {
+        Ok(board) => board,
+        Err(e) => {
+            error!("Board setup failed: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    board.start(args.profile);
+
+    let dist = if args.dist.join("index.html").is_file() {
+        Some(args.dist)
+    } else {
+        warn!("No frontend build in {}, serving the API only", args.dist.display());
+        None
+    };
+
+    let listener = match tiny_http::Server::http(("0.0.0.0", args.port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Cannot listen on port {}: {}", args.port, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    info!("Virtual board on http://localhost:{}", args.port);
+
+    let avionics = board.avionics.clone();
+    let server = Server::new(board.info(), board.avionics.state(), Some(board.logger.clone()), board.firmware_updater(), board.wifi.clone(), dist, move |c| avionics.handle_command(c));
+    thread::spawn(move || server.run(listener));
+
+    // The simulated flash does not survive the process, so a reset ends it.
+    let request = board.avionics.wait_for_reset();
+    thread::sleep(Duration::from_millis(RESET_DELAY_MS));
+    let mut logger = board.logger.lock().unwrap();
+    if let Err(e) = logger.flush() {
+        error!("Log flush failed: {}", e);
+    }
+    info!("{:?} requested, exiting", request);
+    ExitCode::SUCCESS
+}
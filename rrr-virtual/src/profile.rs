@@ -0,0 +1,121 @@
+//! Barometric pressure over time, fed to the simulated barometer.
+
+use rrr_core::altitude::{altitude_pressure, STANDARD_PRESSURE};
+
+const GRAVITY: f32 = 9.81;
+
+/// A simple ballistic flight: constant thrust, unpowered coast and a
+/// descent at constant speed under the parachute.
+#[derive(Clone, Debug)]
+pub struct ScriptedFlight {
+    /// Time after start-up at which the motor ignites.
+    pub launch_ms: u64,
+    /// Launch site elevation, meters.
+    pub ground_altitude: f32,
+    /// Net upward acceleration during the burn, m/s².
+    pub thrust_acceleration: f32,
+    pub burn_ms: u64,
+    /// Negative, m/s.
+    pub descent_velocity: f32,
+}
+
+impl Default for ScriptedFlight {
+    fn default() -> Self {
+        Self {
+            launch_ms: 30_000,
+            ground_altitude: 250.0,
+            thrust_acceleration: 60.0,
+            burn_ms: 1_500,
+            descent_velocity: -15.0,
+        }
+    }
+}
+
+impl ScriptedFlight {
+    /// Height above the launch site.
+    pub fn altitude_agl(&self, time_ms: u64) -> f32 {
+        let Some(t) = time_ms.checked_sub(self.launch_ms).map(|t| t as f32 / 1000.0) else {
+            return 0.0;
+        };
+        let burn = self.burn_ms as f32 / 1000.0;
+        if t < burn {
+            return 0.5 * self.thrust_acceleration * t * t;
+        }
+
+        let burnout_altitude = 0.5 * self.thrust_acceleration * burn * burn;
+        let burnout_velocity = self.thrust_acceleration * burn;
+        let coast = t - burn;
+        let apogee_time = burnout_velocity / GRAVITY;
+        if coast < apogee_time {
+            return burnout_altitude + burnout_velocity * coast - 0.5 * GRAVITY * coast * coast;
+        }
+
+        let apogee = burnout_altitude + burnout_velocity * burnout_velocity / (2.0 * GRAVITY);
+        (apogee + self.descent_velocity * (coast - apogee_time)).max(0.0)
+    }
+
+    pub fn pressure(&self, time_ms: u64) -> f32 {
+        altitude_pressure(self.ground_altitude + self.altitude_agl(time_ms), STANDARD_PRESSURE)
+    }
+}
+
+/// Pressure samples recorded on a real flight, linearly interpolated.
+#[derive(Clone, Debug)]
+pub struct RecordedTrace {
+    samples: Vec<(u64, f32)>,
+}
+
+impl RecordedTrace {
+    /// Parses `time_ms,pressure` lines. A header line and empty lines are skipped.
+    pub fn parse(csv: &str) -> Result<Self, String> {
+        let mut samples: Vec<(u64, f32)> = Vec::new();
+        for (number, line) in csv.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (number == 0 && !line.starts_with(|c: char| c.is_ascii_digit())) {
+                continue;
+            }
+            let mut fields = line.split(',').map(str::trim);
+            let (Some(time), Some(pressure)) = (fields.next(), fields.next()) else {
+                return Err(format!("Line {}: expected time_ms,pressure", number + 1));
+            };
+            let time = time.parse().map_err(|_| format!("Line {}: invalid time {}", number + 1, time))?;
+            let pressure = pressure.parse().map_err(|_| format!("Line {}: invalid pressure {}", number + 1, pressure))?;
+            if samples.last().is_some_and(|(last, _)| *last >= time) {
+                return Err(format!("Line {}: time is not increasing", number + 1));
+            }
+            samples.push((time, pressure));
+        }
+        if samples.is_empty() {
+            return Err("Trace has no samples".into());
+        }
+        Ok(Self { samples })
+    }
+
+    /// Holds the first and last sample outside the recorded time span.
+    pub fn pressure(&self, time_ms: u64) -> f32 {
+        let next = self.samples.partition_point(|(time, _)| *time <= time_ms);
+        match (self.samples.get(next.wrapping_sub(1)), self.samples.get(next)) {
+            (Some((t0, p0)), Some((t1, p1))) => {
+                let fraction = (time_ms - t0) as f32 / (t1 - t0) as f32;
+                p0 + (p1 - p0) * fraction
+            }
+            (Some((_, p)), None) | (None, Some((_, p))) => *p,
+            (None, None) => STANDARD_PRESSURE,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Profile {
+    Scripted(ScriptedFlight),
+    Recorded(RecordedTrace),
+}
+
+impl Profile {
+    pub fn pressure(&self, time_ms: u64) -> f32 {
+        match self {
+            Profile::Scripted(flight) => flight.pressure(time_ms),
+            Profile::Recorded(trace) => trace.pressure(time_ms),
+        }
+    }
+}
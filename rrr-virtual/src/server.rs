@@ -0,0 +1,260 @@
+//! The board's HTTP API on top of `tiny_http`, with the frontend served from
+//! a directory instead of flash.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::*;
+use rrr_api::{ApiError, BoardInfo, Command, Encoding, ServerMessage, State};
+use rrr_core::avionics::CommandError;
+use rrr_core::hal::{FirmwareSlot, WifiScanner};
+use rrr_core::http::{self, CORS_HEADER, LOG_CHUNK_SIZE, MAX_COMMAND_SIZE};
+use rrr_core::logger::{FlightLogger, LogError, LogStorage};
+use rrr_core::ota::FirmwareUpdater;
+use rrr_core::telemetry::{self, TelemetrySession, MAX_MESSAGE_SIZE};
+use tiny_http::{Header, Method, Request};
+use tungstenite::protocol::{Role, WebSocket, WebSocketConfig};
+use tungstenite::Message;
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).unwrap()
+}
+
+fn header_value<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request.headers().iter().find(|h| h.field.equiv(name)).map(|h| h.value.as_str())
+}
+
+fn is_websocket(request: &Request) -> bool {
+    header_value(request, "Upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+}
+
+fn frame(message: &ServerMessage, encoding: Encoding) -> Message {
+    let data = encoding.encode(message);
+    if encoding.is_text() {
+        Message::Text(String::from_utf8(data).unwrap())
+    } else {
+        Message::Binary(data)
+    }
+}
+
+fn send(request: Request, response: http::Response) {
+    let mut body = response.body;
+    if body.is_empty() {
+        body = response.message.unwrap_or_default().as_bytes().to_vec();
+    }
+    let mut reply = tiny_http::Response::from_data(body)
+        .with_status_code(response.status)
+        .with_header(header(CORS_HEADER.0, CORS_HEADER.1));
+    if let Some(content_type) = response.content_type {
+        reply.add_header(header("Content-Type", content_type));
+    }
+    if let Err(e) = request.respond(reply) {
+        warn!("Sending response failed: {}", e);
+    }
+}
+
+pub struct Server<S: LogStorage, U: FirmwareSlot, W: WifiScanner, F> {
+    started: Instant,
+    info: BoardInfo,
+    state: Arc<Mutex<State>>,
+    logger: Option<Arc<Mutex<FlightLogger<S>>>>,
+    ota: Mutex<FirmwareUpdater<U>>,
+    wifi: Mutex<W>,
+    /// Built frontend (`trunk build` output); only the API is served without it.
+    dist: Option<PathBuf>,
+    command_handler: F,
+}
+
+impl<S, U, W, F> Server<S, U, W, F>
+    where S: LogStorage, U: FirmwareSlot, W: WifiScanner, F: Fn(&Command) -> Result<(), CommandError>
+{
+    pub fn new(
+        info: BoardInfo,
+        state: Arc<Mutex<State>>,
+        logger: Option<Arc<Mutex<FlightLogger<S>>>>,
+        ota: FirmwareUpdater<U>,
+        wifi: W,
+        dist: Option<PathBuf>,
+        command_handler: F,
+    ) -> Self {
+        Self { started: Instant::now(), info, state, logger, ota: Mutex::new(ota), wifi: Mutex::new(wifi), dist, command_handler }
+    }
+
+    /// Serves requests one after the other until the listener is closed.
+    /// Telemetry sockets get a thread each.
+    pub fn run(&self, listener: tiny_http::Server)
+        where S: Send, U: Send, W: Send, F: Sync
+    {
+        thread::scope(|scope| {
+            for request in listener.incoming_requests() {
+                if is_websocket(&request) {
+                    scope.spawn(|| self.handle(request));
+                } else {
+                    self.handle(request);
+                }
+            }
+        });
+    }
+
+    pub fn handle(&self, mut request: Request) {
+        let path = request.url().split('?').next().unwrap_or_default().to_owned();
+        let logger = self.logger.as_deref();
+
+        match (request.method(), path.as_str()) {
+            (Method::Get, "/info") => send(request, http::get_info(&self.info)),
+            (Method::Get, "/state") => {
+                let response = http::get_state(&self.state, header_value(&request, "Accept"));
+                send(request, response);
+            }
+            (Method::Get, "/selftest") => {
+                let response = http::get_self_test(&self.state, header_value(&request, "Accept"));
+                send(request, response);
+            }
+            (Method::Get, "/wifi/scan") => {
+                let response = http::get_wifi_scan(&self.wifi, &self.state, header_value(&request, "Accept"));
+                send(request, response);
+            }
+            (Method::Get, "/ws") => self.telemetry(request),
+            (Method::Post, "/command") => {
+                let content_type = header_value(&request, "Content-Type").map(str::to_owned);
+                let accept = header_value(&request, "Accept").map(str::to_owned);
+                let encoding = Encoding::negotiate(accept.as_deref());
+                let mut body = Vec::new();
+                let read = request.as_reader().take(MAX_COMMAND_SIZE as u64 + 1).read_to_end(&mut body);
+                let response = match read {
+                    Err(e) => http::command_failed(ApiError::MalformedCommand, e.to_string(), encoding),
+                    Ok(_) if body.len() > MAX_COMMAND_SIZE => http::command_failed(ApiError::MalformedCommand, "Command too large".into(), encoding),
+                    Ok(_) => http::post_command(&body, content_type.as_deref(), accept.as_deref(), &self.command_handler),
+                };
+                send(request, response);
+            }
+            (Method::Post, "/ota") => {
+                let length = request.body_length();
+                let body = request.as_reader();
+                let response = http::post_ota(&self.ota, length, |buffer: &mut [u8]| body.read(buffer));
+                send(request, response);
+            }
+            (Method::Get, "/logs") => send(request, http::get_logs(logger)),
+            (Method::Get, _) if path.starts_with("/logs/") => self.download_log(request, &path),
+            (Method::Delete, _) if path.starts_with("/logs/") => send(request, http::delete_log(logger, &path)),
+            (Method::Get, _) => self.serve_file(request, &path),
+            _ => send(request, http::Response::error(404, "Not found")),
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn telemetry(&self, request: Request) {
+        let accept = match header_value(&request, "Sec-WebSocket-Key") {
+            Some(key) if is_websocket(&request) => tungstenite::handshake::derive_accept_key(key.as_bytes()),
+            _ => return send(request, http::Response::error(400, "WebSocket upgrade required")),
+        };
+        let response = tiny_http::Response::empty(101)
+            .with_header(header("Sec-WebSocket-Accept", &accept));
+        let stream = request.upgrade("websocket", response);
+        let config = WebSocketConfig { max_message_size: Some(MAX_MESSAGE_SIZE), ..Default::default() };
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, Some(config));
+
+        info!("Telemetry client connected");
+        match self.serve_telemetry(&mut socket) {
+            Ok(()) | Err(tungstenite::Error::ConnectionClosed) => info!("Telemetry client disconnected"),
+            Err(e) => warn!("Telemetry connection failed: {}", e),
+        }
+    }
+
+    /// tiny_http hands out the upgraded connection as one blocking stream, so
+    /// reads and pushes cannot overlap. While subscribed, every push is followed
+    /// by a ping, and client messages are handled until its pong comes back.
+    #[allow(clippy::result_large_err)] // tungstenite's own error type
+    fn serve_telemetry<T: Read + Write>(&self, socket: &mut WebSocket<T>) -> tungstenite::Result<()> {
+        let mut session = TelemetrySession::default();
+        loop {
+            let subscribed = session.period_ms().is_some();
+            if subscribed {
+                if let Some(message) = session.poll(self.now_ms(), &self.state) {
+                    socket.send(frame(&message, session.encoding()))?;
+                }
+                socket.send(Message::Ping(Vec::new()))?;
+            }
+
+            loop {
+                let reply = match socket.read() {
+                    Ok(Message::Text(text)) => session.handle(text.as_bytes(), Encoding::Json, self.now_ms(), &self.command_handler),
+                    Ok(Message::Binary(data)) => session.handle(&data, Encoding::Postcard, self.now_ms(), &self.command_handler),
+                    Ok(Message::Pong(_)) => break,
+                    Ok(Message::Close(_)) => return Ok(()),
+                    Ok(_) => None,
+                    Err(e @ tungstenite::Error::Capacity(_)) => {
+                        socket.send(frame(&telemetry::message_too_large(), session.encoding()))?;
+                        return Err(e);
+                    }
+                    Err(e) => return Err(e),
+                };
+                if let Some(reply) = reply {
+                    socket.send(frame(&reply, session.encoding()))?;
+                }
+                // Without a subscription there is no pong to wait for.
+                if !subscribed {
+                    break;
+                }
+            }
+
+            if let Some(wait_ms) = session.wait_ms(self.now_ms()) {
+                thread::sleep(Duration::from_millis(wait_ms));
+            }
+        }
+    }
+
+    fn download_log(&self, request: Request, path: &str) {
+        let (logger, id) = match http::check_log_download(self.logger.as_deref(), path) {
+            Ok(download) => download,
+            Err(response) => return send(request, response),
+        };
+
+        let mut data = Vec::new();
+        let mut reader = logger.lock().unwrap().reader(id);
+        let mut buf = [0u8; LOG_CHUNK_SIZE];
+        loop {
+            let read = logger.lock().unwrap().read(&mut reader, &mut buf);
+            match read {
+                Ok(0) => break,
+                Ok(read) => data.extend_from_slice(&buf[..read]),
+                Err(LogError::SessionNotFound) => return send(request, http::Response::error(404, "No such log")),
+                Err(_) => return send(request, http::Response::error(500, "Log storage failure")),
+            }
+        }
+
+        let reply = tiny_http::Response::from_data(data)
+            .with_header(header("Content-Type", "application/octet-stream"))
+            .with_header(header("Content-Disposition", &http::log_disposition(id)))
+            .with_header(header(CORS_HEADER.0, CORS_HEADER.1));
+        if let Err(e) = request.respond(reply) {
+            warn!("Sending log {} failed: {}", id, e);
+        }
+    }
+
+    fn serve_file(&self, request: Request, path: &str) {
+        let file = path.trim_start_matches('/');
+        let file = if file.is_empty() { "index.html" } else { file };
+        let relative = Path::new(file);
+        let inside_dist = relative.components().all(|c| matches!(c, Component::Normal(_)));
+
+        let content = self.dist.as_ref()
+            .filter(|_| inside_dist)
+            .and_then(|dist| fs::read(dist.join(relative)).ok());
+        match content {
+            Some(content) => send(request, http::Response {
+                status: 200,
+                message: None,
+                content_type: Some(http::content_type(file)),
+                body: content,
+            }),
+            None => send(request, http::Response::error(404, "Not found")),
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+use rrr_api::{ApiError, BoardInfo, ClientMessage, Command, CommandResponse, Encoding, LogInfo, SelfTestReport, Sensor, ServerMessage, State};
+use rrr_api::{OtaStatus, ResetReason, WifiNetwork, POSTCARD_CONTENT_TYPE, RESET_CONFIRMATION};
+use rrr_core::altitude::{pressure_altitude, STANDARD_PRESSURE};
+use rrr_core::avionics::ResetRequest;
+use rrr_core::mock::firmware_image;
+use rrr_core::ota::ESP32C3_CHIP_ID;
+use rrr_core::signing::{sign_image, SigningKey, TRAILER_SIZE};
+use rrr_virtual::board::{VirtualBoard, FIRMWARE_SLOT_SIZE};
+use rrr_virtual::profile::{RecordedTrace, ScriptedFlight};
+use rrr_virtual::server::Server;
+use tungstenite::Message;
+
+struct Reply {
+    status: u16,
+    headers: String,
+    body: Vec<u8>,
+}
+
+fn request(port: u16, method: &str, path: &str, body: &str) -> Reply {
+    request_with_headers(port, method, path, &[], body.as_bytes())
+}
+
+fn request_with_headers(port: u16, method: &str, path: &str, headers: &[(&str, &str)], body: &[u8]) -> Reply {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n", method, path).unwrap();
+    for (name, value) in headers {
+        write!(stream, "{}: {}\r\n", name, value).unwrap();
+    }
+    write!(stream, "Content-Length: {}\r\n\r\n", body.len()).unwrap();
+    stream.write_all(body).unwrap();
+    let mut data = Vec::new();
+    stream.read_to_end(&mut data).unwrap();
+
+    let split = data.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let headers = String::from_utf8(data[..split].to_vec()).unwrap();
+    let status = headers.split(' ').nth(1).unwrap().parse().unwrap();
+    Reply { status, headers, body: data[split + 4..].to_vec() }
+}
+
+fn ota_key() -> SigningKey {
+    SigningKey::from_bytes(&[3; 32])
+}
+
+fn start(dist: Option<PathBuf>) -> (VirtualBoard, u16) {
+    let mut board = VirtualBoard::new().unwrap();
+    board.ota_key = Some(ota_key().verifying_key());
+    let listener = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let port = listener.server_addr().to_ip().unwrap().port();
+    let avionics = board.avionics.clone();
+    let server = Server::new(board.info(), board.avionics.state(), Some(board.logger.clone()), board.firmware_updater(), board.wifi.clone(), dist, move |c| avionics.handle_command(c));
+    thread::spawn(move || server.run(listener));
+    (board, port)
+}
+
+fn command(port: u16, command: &Command) -> Reply {
+    request(port, "POST", "/command", &serde_json::to_string(command).unwrap())
+}
+
+#[test]
+fn scripted_flight_lands_where_it_started() {
+    let flight = ScriptedFlight::default();
+    let ground = pressure_altitude(flight.pressure(0), STANDARD_PRESSURE);
+    assert!((ground - flight.ground_altitude).abs() < 0.5);
+
+    let apogee = (0..200_000).step_by(100).map(|t| flight.altitude_agl(t)).fold(0.0, f32::max);
+    assert!((apogee - 480.0).abs() < 5.0);
+    assert_eq!(flight.altitude_agl(flight.launch_ms), 0.0);
+    assert_eq!(flight.altitude_agl(1_000_000), 0.0);
+}
+
+#[test]
+fn recorded_trace_is_interpolated() {
+    let trace = RecordedTrace::parse("time_ms,pressure\n0,100000\n1000,99000\n\n3000,99000\n").unwrap();
+    assert_eq!(trace.pressure(0), 100_000.0);
+    assert_eq!(trace.pressure(500), 99_500.0);
+    assert_eq!(trace.pressure(2000), 99_000.0);
+    assert_eq!(trace.pressure(10_000), 99_000.0);
+
+    assert!(RecordedTrace::parse("0,100000\n0,99000\n").is_err());
+    assert!(RecordedTrace::parse("0,abc\n").is_err());
+    assert!(RecordedTrace::parse("time_ms,pressure\n").is_err());
+}
+
+#[test]
+fn serves_state_and_commands() {
+    let (board, port) = start(None);
+
+    let reply = request(port, "GET", "/state", "");
+    assert_eq!(reply.status, 200);
+    assert!(reply.headers.contains("Access-Control-Allow-Origin: *"));
+    let state: State = serde_json::from_slice(&reply.body).unwrap();
+    assert!(!state.pyro.armed);
+
+    assert_eq!(command(port, &Command::ArmPyro).status, 200);
+    assert_eq!(command(port, &Command::SetLedColor { r: 0, g: 0, b: 9 }).status, 200);
+    assert_eq!(board.led.color(), Some((0, 0, 9)));
+    let state: State = serde_json::from_slice(&request(port, "GET", "/state", "").body).unwrap();
+    assert!(state.pyro.armed);
+
+    let reply = command(port, &Command::DisarmPyro);
+    assert_eq!(serde_json::from_slice::<CommandResponse>(&reply.body).unwrap(), CommandResponse::Ok);
+}
+
+#[test]
+fn serves_board_info() {
+    let (board, port) = start(None);
+
+    let reply = request(port, "GET", "/info", "");
+    assert_eq!(reply.status, 200);
+    let info: BoardInfo = serde_json::from_slice(&reply.body).unwrap();
+    assert_eq!(info, board.info());
+    assert!(info.is_compatible());
+    assert_eq!(info.board, "virtual");
+    assert_eq!((info.pyro_channels, info.servo_channels), (2, 2));
+    assert!(info.sensors.contains(&Sensor::Barometer));
+    assert!(info.supports(&Command::ZeroAltitude));
+}
+
+#[test]
+fn serves_self_test() {
+    let (board, port) = start(None);
+    let report = |port| serde_json::from_slice::<SelfTestReport>(&request(port, "GET", "/selftest", "").body).unwrap();
+    assert!(!report(port).ready_to_fly);
+
+    board.self_test();
+    let report = report(port);
+    assert!(report.ready_to_fly, "{:?}", report.blocking);
+    assert_eq!(report.checks.len(), 6);
+    let state = board.avionics.state().lock().unwrap().clone();
+    assert!(state.self_test == report);
+}
+
+#[test]
+fn firmware_upload() {
+    let (board, port) = start(None);
+    let image = firmware_image("9.9.9", ESP32C3_CHIP_ID, 20_000);
+    let signed = sign_image(&image, &ota_key(), 0);
+    let reply = request_with_headers(port, "POST", "/ota", &[("Content-Type", "application/octet-stream")], &signed);
+    assert_eq!(reply.status, 200);
+    assert_eq!(board.firmware.image(), Some(image.clone()));
+    let ota = board.avionics.state().lock().unwrap().ota.clone();
+    assert_eq!((ota.status, ota.received, ota.size), (OtaStatus::Complete, 20_084, 20_084));
+    let state: State = serde_json::from_slice(&request(port, "GET", "/state", "").body).unwrap();
+    assert_eq!(state.firmware.running.unwrap().version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+    assert_eq!(state.firmware.previous.unwrap().version.as_deref(), Some("9.9.9"));
+
+    let mut corrupt = signed.clone();
+    corrupt[10_000] ^= 0xFF;
+    let reply = request_with_headers(port, "POST", "/ota", &[], &corrupt);
+    assert_eq!(reply.status, 400);
+    assert!(String::from_utf8_lossy(&reply.body).contains("SHA-256"));
+
+    let reply = request_with_headers(port, "POST", "/ota", &[], &image);
+    assert_eq!(reply.status, 403);
+    let foreign = sign_image(&image, &SigningKey::from_bytes(&[4; 32]), 0);
+    let reply = request_with_headers(port, "POST", "/ota", &[], &foreign);
+    assert_eq!(reply.status, 403);
+
+    let reply = request_with_headers(port, "POST", "/ota", &[], &vec![0; FIRMWARE_SLOT_SIZE as usize + TRAILER_SIZE + 1]);
+    assert_eq!(reply.status, 413);
+    assert_eq!(board.avionics.state().lock().unwrap().ota.status, OtaStatus::Failed);
+}
+
+#[test]
+fn wifi_scan() {
+    let (_board, port) = start(None);
+    let reply = request(port, "GET", "/wifi/scan", "");
+    assert_eq!(reply.status, 200);
+    let networks: Vec<WifiNetwork> = serde_json::from_slice(&reply.body).unwrap();
+    let ssids: Vec<&str> = networks.iter().map(|network| network.ssid.as_str()).collect();
+    assert_eq!(ssids, ["field-hotspot", "clubhouse", "club-guest"]);
+
+    for ssid in ["field-hotspot", "club-guest"] {
+        let add = Command::AddWifiNetwork { ssid: ssid.into(), password: String::new() };
+        assert_eq!(command(port, &add).status, 200);
+    }
+    let move_to_top = Command::MoveWifiNetwork { ssid: "club-guest".into(), priority: 0 };
+    assert_eq!(command(port, &move_to_top).status, 200);
+    let state: State = serde_json::from_slice(&request(port, "GET", "/state", "").body).unwrap();
+    assert_eq!(state.wifi_networks, ["club-guest", "field-hotspot"]);
+}
+
+#[test]
+fn command_errors_are_reported() {
+    let (_board, port) = start(None);
+    let error = |reply: &Reply| match serde_json::from_slice(&reply.body).unwrap() {
+        CommandResponse::Failed { error, message } => {
+            assert!(!message.is_empty());
+            error
+        }
+        CommandResponse::Ok => panic!("command succeeded"),
+    };
+
+    let reply = command(port, &Command::FirePyro { channel: 1, duration_ms: 100 });
+    assert_eq!((reply.status, error(&reply)), (409, ApiError::NotArmed));
+
+    command(port, &Command::ArmPyro);
+    let reply = command(port, &Command::FirePyro { channel: 3, duration_ms: 100 });
+    assert_eq!((reply.status, error(&reply)), (400, ApiError::InvalidArgument));
+
+    let reply = request(port, "POST", "/command", "{\"Nope\":1}");
+    assert_eq!((reply.status, error(&reply)), (400, ApiError::MalformedCommand));
+    assert!(reply.headers.contains("application/json"));
+
+    let reply = command(port, &Command::Reset { confirm: String::new() });
+    assert_eq!((reply.status, error(&reply)), (400, ApiError::InvalidArgument));
+}
+
+#[test]
+fn reset_is_requested_after_confirmation() {
+    let (board, port) = start(None);
+    assert_eq!(board.avionics.state().lock().unwrap().reset_reason, ResetReason::PowerOn);
+
+    let reply = command(port, &Command::Reset { confirm: RESET_CONFIRMATION.into() });
+    assert_eq!(reply.status, 200);
+    assert_eq!(board.avionics.reset_request(), Some(ResetRequest::Restart));
+}
+
+#[test]
+fn streams_telemetry_over_websocket() {
+    let (_board, port) = start(None);
+    let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let (mut socket, _) = tungstenite::client(format!("ws://127.0.0.1:{}/ws", port), stream).unwrap();
+    let mut send = |message: &ClientMessage| {
+        socket.send(Message::Text(serde_json::to_string(message).unwrap())).unwrap();
+    };
+    send(&ClientMessage::Subscribe { period_ms: 50 });
+    send(&ClientMessage::Command { id: 1, command: Command::ArmPyro });
+
+    let started = Instant::now();
+    let mut states = Vec::new();
+    let mut responses = Vec::new();
+    while states.len() < 10 {
+        let Message::Text(text) = socket.read().unwrap() else { continue };
+        match serde_json::from_str(&text).unwrap() {
+            ServerMessage::State(state) => states.push(*state),
+            ServerMessage::CommandResponse { id, response } => responses.push((id, response)),
+            ServerMessage::Rejected { message } => panic!("rejected: {}", message),
+        }
+    }
+    // Ten states at 20 Hz, far faster than polling `/state` every second.
+    assert!(started.elapsed() < Duration::from_millis(2000));
+    assert!(responses == [(1, CommandResponse::Ok)]);
+    assert!(states.last().unwrap().pyro.armed);
+}
+
+#[test]
+fn negotiates_binary_encoding() {
+    let (_board, port) = start(None);
+    let postcard = Encoding::Postcard;
+
+    let reply = request_with_headers(port, "GET", "/state", &[("Accept", POSTCARD_CONTENT_TYPE)], b"");
+    assert!(reply.headers.contains(POSTCARD_CONTENT_TYPE));
+    let json = request(port, "GET", "/state", "");
+    assert!(json.headers.contains("application/json"));
+    assert!(reply.body.len() * 4 < json.body.len());
+    assert!(!postcard.decode::<State>(&reply.body).unwrap().pyro.armed);
+
+    let headers = [("Content-Type", POSTCARD_CONTENT_TYPE), ("Accept", POSTCARD_CONTENT_TYPE)];
+    let reply = request_with_headers(port, "POST", "/command", &headers, &postcard.encode(&Command::ArmPyro));
+    assert_eq!(reply.status, 200);
+    assert!(postcard.decode::<CommandResponse>(&reply.body).unwrap() == CommandResponse::Ok);
+
+    // JSON replies unless asked otherwise, whatever the body was.
+    let reply = request_with_headers(port, "POST", "/command", &headers[..1], &postcard.encode(&Command::DisarmPyro));
+    assert!(serde_json::from_slice::<CommandResponse>(&reply.body).unwrap() == CommandResponse::Ok);
+
+    let reply = request_with_headers(port, "POST", "/command", &[("Content-Type", "text/plain")], b"ArmPyro");
+    assert_eq!(reply.status, 400);
+
+    let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let (mut socket, _) = tungstenite::client(format!("ws://127.0.0.1:{}/ws", port), stream).unwrap();
+    socket.send(Message::Binary(postcard.encode(&ClientMessage::Subscribe { period_ms: 50 }))).unwrap();
+    loop {
+        match socket.read().unwrap() {
+            Message::Binary(data) => {
+                assert!(matches!(postcard.decode(&data).unwrap(), ServerMessage::State(_)));
+                break;
+            }
+            Message::Text(_) => panic!("expected binary frames"),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn websocket_requires_upgrade() {
+    let (_board, port) = start(None);
+    assert_eq!(request(port, "GET", "/ws", "").status, 400);
+}
+
+#[test]
+fn serves_logs() {
+    let (_board, port) = start(None);
+
+    let reply = request(port, "GET", "/logs", "");
+    assert_eq!(reply.status, 200);
+    let logs: Vec<LogInfo> = serde_json::from_slice(&reply.body).unwrap();
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].active);
+
+    let reply = request(port, "GET", &format!("/logs/{}", logs[0].id), "");
+    assert_eq!(reply.status, 200);
+    assert!(reply.headers.contains("application/octet-stream"));
+    assert_eq!(rrr_log::decode(&reply.body)[0].id, logs[0].id);
+
+    assert_eq!(request(port, "GET", "/logs/999", "").status, 404);
+    assert_eq!(request(port, "DELETE", &format!("/logs/{}", logs[0].id), "").status, 409);
+}
+
+#[test]
+fn serves_frontend_from_directory() {
+    let dist = std::env::temp_dir().join(format!("rrr-virtual-dist-{}", std::process::id()));
+    std::fs::create_dir_all(&dist).unwrap();
+    std::fs::write(dist.join("index.html"), "<html></html>").unwrap();
+    std::fs::write(dist.join("app.wasm"), [0, 97, 115, 109]).unwrap();
+    let (_board, port) = start(Some(dist.clone()));
+
+    let reply = request(port, "GET", "/", "");
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.body, b"<html></html>");
+    assert!(reply.headers.contains("text/html"));
+
+    let reply = request(port, "GET", "/app.wasm", "");
+    assert!(reply.headers.contains("application/wasm"));
+    assert_eq!(reply.body, [0, 97, 115, 109]);
+
+    assert_eq!(request(port, "GET", "/missing.js", "").status, 404);
+    assert_eq!(request(port, "GET", "/../Cargo.toml", "").status, 404);
+    std::fs::remove_dir_all(dist).unwrap();
+}